@@ -0,0 +1,222 @@
+use tracing::debug;
+use crate::common::io::{Future, Poll};
+use crate::scalar::memory::Dram;
+
+/// Timing parameters of the AXI port in front of the external memory
+#[derive(Copy, Clone, Debug)]
+pub struct AxiConfig {
+    /// Cycles from accepting a read until its first data beat
+    pub read_latency: u32,
+    /// Cycles from the last write data beat until the write response
+    pub write_latency: u32,
+    /// Bursts that may be in flight at once, further requests wait to be accepted
+    pub max_outstanding: usize,
+    /// Most beats in one burst, longer requests are split into several bursts
+    pub burst_len: u32,
+    /// Bytes moved per data beat, one beat per cycle on each of the read and write channels
+    pub bytes_per_beat: u32,
+}
+
+impl Default for AxiConfig {
+    fn default() -> Self {
+        Self { read_latency: 20, write_latency: 10, max_outstanding: 4, burst_len: 16, bytes_per_beat: 8 }
+    }
+}
+
+/// Transfer counts of the AXI port
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AxiStats {
+    pub read_bursts: u64,
+    pub write_bursts: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Cycles requests waited to be accepted because the outstanding limit was reached
+    pub stall_cycles: u64,
+}
+
+/// External DRAM behind an AXI-like request/response port.
+///
+/// Requests are scheduled when they are made: a burst is accepted once the address
+/// channel is free and fewer than `max_outstanding` bursts are in flight, then its
+/// beats follow the latency on the read or write data channel, after any beats
+/// already scheduled there. The returned future resolves when the last burst
+/// completes, and the data moves at that point. `load` and `store` access the DRAM
+/// directly without timing, for debug accesses and image loading.
+pub struct ExternalMemory {
+    pub dram: Dram,
+    pub config: AxiConfig,
+    pub stats: AxiStats,
+    /// Current cycle
+    now: u64,
+    /// Completion cycles of the bursts in flight, in increasing order
+    outstanding: Vec<u64>,
+    /// First cycle the address channel can accept another burst
+    address_free: u64,
+    /// First cycles the read and write data channels are free
+    read_free: u64,
+    write_free: u64,
+}
+
+impl ExternalMemory {
+    /// Create an external memory with the given DRAM and port timing
+    pub fn new(dram: Dram, config: AxiConfig) -> Self {
+        Self {
+            dram,
+            config,
+            stats: AxiStats::default(),
+            now: 0,
+            outstanding: Vec::new(),
+            address_free: 0,
+            read_free: 0,
+            write_free: 0,
+        }
+    }
+
+    /// Whether the address falls inside the DRAM
+    pub fn contains(&self, addr: u32) -> bool {
+        self.dram.contains(addr)
+    }
+
+    /// Load `size` bytes (1, 2 or 4) without timing
+    pub fn load(&self, addr: u32, size: u32) -> u32 {
+        self.dram.load(addr, size)
+    }
+
+    /// Store the low `size` bytes (1, 2 or 4) of `value` without timing
+    pub fn store(&mut self, addr: u32, size: u32, value: u32) {
+        self.dram.store(addr, size, value)
+    }
+
+    /// Largest number of bytes one burst moves
+    pub fn burst_bytes(&self) -> u32 {
+        self.config.burst_len.max(1) * self.config.bytes_per_beat.max(1)
+    }
+
+    /// Request a read of `len` bytes at `addr`
+    pub fn read(&mut self, addr: u32, len: u32) -> AxiRead {
+        debug!("AXI read request addr=0x{:08x} len={}", addr, len);
+        self.stats.bytes_read += len as u64;
+        AxiRead { addr, len, ready_at: self.schedule(len, false) }
+    }
+
+    /// Request a write of `data` at `addr`
+    pub fn write(&mut self, addr: u32, data: Vec<u8>) -> AxiWrite {
+        debug!("AXI write request addr=0x{:08x} len={}", addr, data.len());
+        self.stats.bytes_written += data.len() as u64;
+        AxiWrite { addr, ready_at: self.schedule(data.len() as u32, true), data }
+    }
+
+    /// Request a write of `len` bytes at `addr` whose data is already in memory,
+    /// resolving when the write response arrives
+    pub fn write_response(&mut self, addr: u32, len: u32) -> AxiWrite {
+        debug!("AXI write request addr=0x{:08x} len={}, data already in memory", addr, len);
+        self.stats.bytes_written += len as u64;
+        AxiWrite { addr, ready_at: self.schedule(len, true), data: Vec::new() }
+    }
+
+    /// Post a write of `len` bytes at `addr` whose data is already in memory, occupying
+    /// the write channel without waiting for the response
+    pub fn post_write(&mut self, addr: u32, len: u32) {
+        debug!("AXI posted write addr=0x{:08x} len={}", addr, len);
+        self.stats.bytes_written += len as u64;
+        self.schedule(len, true);
+    }
+
+    /// Schedule the bursts of a `len`-byte transfer, returning the cycle the last completes
+    fn schedule(&mut self, len: u32, write: bool) -> u64 {
+        let beat = self.config.bytes_per_beat.max(1);
+        let mut beats = len.div_ceil(beat).max(1);
+        let mut done = self.now;
+        while beats > 0 {
+            let burst = beats.min(self.config.burst_len.max(1));
+            beats -= burst;
+
+            // Wait for the address channel and for a free outstanding slot
+            let mut accept = self.now.max(self.address_free);
+            let limit = self.config.max_outstanding.max(1);
+            if let Some(&freed) = self.outstanding.iter().rev().nth(limit - 1) {
+                accept = accept.max(freed);
+            }
+            self.stats.stall_cycles += accept - self.now.max(self.address_free);
+            self.address_free = accept + 1;
+
+            done = if write {
+                let start = accept.max(self.write_free);
+                self.write_free = start + burst as u64;
+                self.stats.write_bursts += 1;
+                self.write_free + self.config.write_latency as u64
+            } else {
+                let start = (accept + self.config.read_latency as u64).max(self.read_free);
+                self.read_free = start + burst as u64;
+                self.stats.read_bursts += 1;
+                self.read_free
+            };
+            let index = self.outstanding.partition_point(|&cycle| cycle <= done);
+            self.outstanding.insert(index, done);
+        }
+        done
+    }
+
+    /// Advance the port by one cycle
+    pub fn tick(&mut self) {
+        self.now += 1;
+        let now = self.now;
+        self.outstanding.retain(|&cycle| cycle > now);
+    }
+}
+
+impl Default for ExternalMemory {
+    fn default() -> Self {
+        Self::new(Dram::default(), AxiConfig::default())
+    }
+}
+
+/// Read request future, resolving to the bytes read
+pub struct AxiRead {
+    pub addr: u32,
+    pub len: u32,
+    /// Cycle the last burst completes
+    pub ready_at: u64,
+}
+
+impl Future for AxiRead {
+    /// Bytes read, in address order
+    type Output = Vec<u8>;
+    /// External memory as input context
+    type Input = ExternalMemory;
+
+    /// Poll the read request
+    fn poll(&mut self, context: &mut Self::Input) -> Poll<Self::Output> {
+        if context.now < self.ready_at {
+            return Poll::Pending;
+        }
+        let data = (0..self.len).map(|i| context.dram.load(self.addr.wrapping_add(i), 1) as u8).collect();
+        Poll::Ready(data)
+    }
+}
+
+/// Write request future, resolving when the write response arrives
+pub struct AxiWrite {
+    pub addr: u32,
+    /// Bytes stored when the write completes, empty if they are already in memory
+    pub data: Vec<u8>,
+    /// Cycle the last burst completes
+    pub ready_at: u64,
+}
+
+impl Future for AxiWrite {
+    type Output = ();
+    /// External memory as input context
+    type Input = ExternalMemory;
+
+    /// Poll the write request, the data lands in memory when it completes
+    fn poll(&mut self, context: &mut Self::Input) -> Poll<Self::Output> {
+        if context.now < self.ready_at {
+            return Poll::Pending;
+        }
+        for (i, byte) in self.data.iter().enumerate() {
+            context.dram.store(self.addr.wrapping_add(i as u32), 1, *byte as u32);
+        }
+        Poll::Ready(())
+    }
+}
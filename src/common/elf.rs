@@ -0,0 +1,176 @@
+use std::io::{self, ErrorKind};
+
+/// ELF machine number for RISC-V
+const EM_RISCV: u16 = 243;
+/// Program header type of a loadable segment
+const PT_LOAD: u32 = 1;
+/// Segment flag marking executable code
+const PF_X: u32 = 1;
+/// Section header type of a symbol table
+const SHT_SYMTAB: u32 = 2;
+/// Symbol types that do not name an address in the image
+const STT_SECTION: u8 = 3;
+const STT_FILE: u8 = 4;
+
+/// A loadable segment of an ELF image
+pub struct Segment {
+    /// Physical address the segment is loaded at
+    pub addr: u32,
+    /// Bytes present in the file, the rest of `mem_size` is zero-filled
+    pub data: Vec<u8>,
+    pub mem_size: u32,
+    pub executable: bool,
+}
+
+/// A named address from the ELF symbol table
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub addr: u32,
+    pub size: u32,
+}
+
+/// Symbols sorted by address for nearest-symbol lookups
+#[derive(Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Build a table from unsorted symbols
+    pub fn new(mut symbols: Vec<Symbol>) -> Self {
+        symbols.sort_by_key(|s| s.addr);
+        Self { symbols }
+    }
+
+    /// Find a symbol by name
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name)
+    }
+
+    /// Symbols starting exactly at `addr`
+    pub fn at(&self, addr: u32) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter().filter(move |s| s.addr == addr)
+    }
+
+    /// Nearest symbol at or below `addr`, with the offset of `addr` into it.
+    /// Sized symbols only match addresses they cover.
+    pub fn lookup(&self, addr: u32) -> Option<(&Symbol, u32)> {
+        let end = self.symbols.partition_point(|s| s.addr <= addr);
+        self.symbols[..end]
+            .iter()
+            .rev()
+            .find(|s| s.size == 0 || addr - s.addr < s.size)
+            .map(|s| (s, addr - s.addr))
+    }
+}
+
+/// A parsed 32-bit little-endian RISC-V ELF executable
+pub struct Elf {
+    pub entry: u32,
+    pub segments: Vec<Segment>,
+    pub symbols: SymbolTable,
+}
+
+impl Elf {
+    /// Whether the bytes start with the ELF magic
+    pub fn is_elf(bytes: &[u8]) -> bool {
+        bytes.starts_with(b"\x7fELF")
+    }
+
+    /// Parse an ELF image from memory
+    pub fn parse(bytes: &[u8]) -> io::Result<Self> {
+        if !Self::is_elf(bytes) || bytes.len() < 52 {
+            return Err(invalid("not an ELF file"));
+        }
+        if bytes[4] != 1 || bytes[5] != 1 {
+            return Err(invalid("only 32-bit little-endian ELF files are supported"));
+        }
+        if read_u16(bytes, 18)? != EM_RISCV {
+            return Err(invalid("not a RISC-V ELF file"));
+        }
+
+        let entry = read_u32(bytes, 24)?;
+        let ph_off = read_u32(bytes, 28)? as usize;
+        let sh_off = read_u32(bytes, 32)? as usize;
+        let ph_size = read_u16(bytes, 42)? as usize;
+        let ph_num = read_u16(bytes, 44)? as usize;
+        let sh_size = read_u16(bytes, 46)? as usize;
+        let sh_num = read_u16(bytes, 48)? as usize;
+
+        let mut segments = Vec::new();
+        for i in 0..ph_num {
+            let ph = ph_off + i * ph_size;
+            if read_u32(bytes, ph)? != PT_LOAD {
+                continue;
+            }
+            let offset = read_u32(bytes, ph + 4)? as usize;
+            let file_size = read_u32(bytes, ph + 16)? as usize;
+            segments.push(Segment {
+                addr: read_u32(bytes, ph + 12)?,
+                data: slice(bytes, offset, file_size)?.to_vec(),
+                mem_size: read_u32(bytes, ph + 20)?,
+                executable: read_u32(bytes, ph + 24)? & PF_X != 0,
+            });
+        }
+
+        let mut symbols = Vec::new();
+        for i in 0..sh_num {
+            let sh = sh_off + i * sh_size;
+            if read_u32(bytes, sh + 4)? != SHT_SYMTAB {
+                continue;
+            }
+            let table = slice(bytes, read_u32(bytes, sh + 16)? as usize, read_u32(bytes, sh + 20)? as usize)?;
+            let strtab_sh = sh_off + read_u32(bytes, sh + 24)? as usize * sh_size;
+            let strtab = slice(
+                bytes,
+                read_u32(bytes, strtab_sh + 16)? as usize,
+                read_u32(bytes, strtab_sh + 20)? as usize,
+            )?;
+            for entry in table.chunks_exact(16) {
+                let name = read_str(strtab, read_u32(entry, 0)? as usize);
+                let typ = entry[12] & 0xF;
+                // Skip unnamed, section, file and RISC-V mapping symbols
+                if name.is_empty() || name.starts_with('$') || typ == STT_SECTION || typ == STT_FILE {
+                    continue;
+                }
+                symbols.push(Symbol {
+                    name,
+                    addr: read_u32(entry, 4)?,
+                    size: read_u32(entry, 8)?,
+                });
+            }
+        }
+
+        Ok(Self {
+            entry,
+            segments,
+            symbols: SymbolTable::new(symbols),
+        })
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn slice(bytes: &[u8], offset: usize, len: usize) -> io::Result<&[u8]> {
+    bytes
+        .get(offset..offset + len)
+        .ok_or_else(|| invalid("ELF offset out of bounds"))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> io::Result<u16> {
+    Ok(u16::from_le_bytes(slice(bytes, offset, 2)?.try_into().unwrap()))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(slice(bytes, offset, 4)?.try_into().unwrap()))
+}
+
+/// Read a NUL-terminated string from a string table
+fn read_str(strtab: &[u8], offset: usize) -> String {
+    let tail = strtab.get(offset..).unwrap_or_default();
+    let end = tail.iter().position(|b| *b == 0).unwrap_or(tail.len());
+    String::from_utf8_lossy(&tail[..end]).into_owned()
+}
@@ -0,0 +1,327 @@
+/// `fflags` exception bits
+pub const FLAG_NX: u32 = 1 << 0;
+pub const FLAG_UF: u32 = 1 << 1;
+pub const FLAG_OF: u32 = 1 << 2;
+pub const FLAG_DZ: u32 = 1 << 3;
+pub const FLAG_NV: u32 = 1 << 4;
+
+/// Rounding mode of a floating-point operation
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Rounding {
+    NearestEven,
+    TowardZero,
+    Down,
+    Up,
+    NearestMaxMagnitude,
+    /// Round to odd, only used by `vfncvt.rod.f.f.w`
+    Odd,
+}
+
+impl Rounding {
+    /// Rounding mode encoded in `frm`, `None` for the reserved encodings
+    pub fn from_frm(frm: u32) -> Option<Self> {
+        match frm {
+            0 => Some(Self::NearestEven),
+            1 => Some(Self::TowardZero),
+            2 => Some(Self::Down),
+            3 => Some(Self::Up),
+            4 => Some(Self::NearestMaxMagnitude),
+            _ => None,
+        }
+    }
+}
+
+/// An IEEE 754 binary format narrow enough that `f64` holds its values, sums of two
+/// values and products of two values exactly, so each operation rounds only once.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Format {
+    /// Width in bits
+    pub width: u32,
+    /// Exponent field bits
+    pub exponent: u32,
+    /// Stored mantissa bits, without the implicit leading one
+    pub mantissa: u32,
+}
+
+/// IEEE binary16
+pub const HALF: Format = Format { width: 16, exponent: 5, mantissa: 10 };
+/// IEEE binary32
+pub const SINGLE: Format = Format { width: 32, exponent: 8, mantissa: 23 };
+
+impl Format {
+    /// Format of elements `width` bits wide, `None` if there is no supported format of that width
+    pub fn of_width(width: u32) -> Option<Self> {
+        match width {
+            16 => Some(HALF),
+            32 => Some(SINGLE),
+            _ => None,
+        }
+    }
+
+    fn bias(&self) -> i32 {
+        (1 << (self.exponent - 1)) - 1
+    }
+
+    fn sign_bit(&self) -> u32 {
+        1 << (self.width - 1)
+    }
+
+    fn infinity(&self) -> u32 {
+        ((1 << self.exponent) - 1) << self.mantissa
+    }
+
+    /// The canonical quiet NaN returned by every operation with a NaN result
+    pub fn canonical_nan(&self) -> u32 {
+        self.infinity() | (1 << (self.mantissa - 1))
+    }
+
+    pub fn is_nan(&self, bits: u32) -> bool {
+        bits & !self.sign_bit() > self.infinity()
+    }
+
+    pub fn is_signaling(&self, bits: u32) -> bool {
+        self.is_nan(bits) && bits & (1 << (self.mantissa - 1)) == 0
+    }
+
+    /// Flip the sign, NaNs included
+    pub fn negate(&self, bits: u32) -> u32 {
+        bits ^ self.sign_bit()
+    }
+
+    /// Exact value of `bits`, any NaN becomes an `f64` NaN
+    pub fn to_f64(&self, bits: u32) -> f64 {
+        let sign = if bits & self.sign_bit() != 0 { -1.0 } else { 1.0 };
+        let exponent = (bits >> self.mantissa) & ((1 << self.exponent) - 1);
+        let fraction = bits & ((1 << self.mantissa) - 1);
+        let scale = |exponent: i32| pow2(exponent - self.bias() - self.mantissa as i32);
+        match exponent {
+            0 => sign * fraction as f64 * scale(1),
+            _ if exponent == (1 << self.exponent) - 1 => {
+                if fraction == 0 { sign * f64::INFINITY } else { f64::NAN }
+            }
+            _ => sign * (fraction | (1 << self.mantissa)) as f64 * scale(exponent as i32),
+        }
+    }
+
+    /// Round the exact value `value + error` to this format, where `error` is at most half
+    /// an `f64` ulp of `value`, accruing the exception flags. `value` must be finite.
+    pub fn round(&self, value: f64, error: f64, rounding: Rounding, flags: &mut u32) -> u32 {
+        let negative = value.is_sign_negative();
+        let sign = if negative { self.sign_bit() } else { 0 };
+        if value == 0.0 {
+            return sign;
+        }
+
+        // |value| = m * 2^e with an integer m
+        let bits = value.abs().to_bits();
+        let (m, e) = match bits >> 52 {
+            0 => (bits as u128, -1074),
+            exponent => ((bits & ((1 << 52) - 1) | (1 << 52)) as u128, exponent as i32 - 1075),
+        };
+        // Exponent of the result's last mantissa bit, fixed at the bottom of the subnormal range
+        let top = 127 - m.leading_zeros() as i32 + e;
+        let min_quantum = 1 - self.bias() - self.mantissa as i32;
+        let mut quantum = (top - self.mantissa as i32).max(min_quantum);
+        // At least 29 bits are dropped from an f64 mantissa, so the shift is never zero
+        let shift = (quantum - e).min(120) as u32;
+        let (mut q, rem, half) = (m >> shift, m & ((1 << shift) - 1), 1 << (shift - 1));
+
+        // The error only matters when the dropped bits are zero or exactly half
+        let toward_zero = error != 0.0 && (error < 0.0) != negative;
+        let away = error != 0.0 && !toward_zero;
+        let inexact = rem != 0 || error != 0.0;
+        let mut above_half = rem > half || (rem == half && away);
+        let at_half = rem == half && error == 0.0;
+        if rem == 0 && toward_zero {
+            // Just below a multiple of the quantum, possibly in the binade below
+            q -= 1;
+            above_half = true;
+            if q >> self.mantissa == 0 && quantum > min_quantum {
+                q = (q << 1) | 1;
+                quantum -= 1;
+            }
+        }
+
+        let round_up = inexact
+            && match rounding {
+                Rounding::NearestEven => above_half || (at_half && q & 1 == 1),
+                Rounding::NearestMaxMagnitude => above_half || at_half,
+                Rounding::TowardZero | Rounding::Odd => false,
+                Rounding::Down => negative,
+                Rounding::Up => !negative,
+            };
+        if round_up {
+            q += 1;
+            if q >> (self.mantissa + 1) != 0 {
+                q >>= 1;
+                quantum += 1;
+            }
+        }
+        if rounding == Rounding::Odd && inexact {
+            q |= 1;
+        }
+
+        let exponent = if q >> self.mantissa == 0 { 0 } else { quantum - min_quantum + 1 };
+        if exponent >= (1 << self.exponent) - 1 {
+            *flags |= FLAG_OF | FLAG_NX;
+            let infinite = match rounding {
+                Rounding::NearestEven | Rounding::NearestMaxMagnitude => true,
+                Rounding::TowardZero | Rounding::Odd => false,
+                Rounding::Down => negative,
+                Rounding::Up => !negative,
+            };
+            return sign | if infinite { self.infinity() } else { self.infinity() - 1 };
+        }
+        if inexact {
+            *flags |= if exponent == 0 { FLAG_NX | FLAG_UF } else { FLAG_NX };
+        }
+        sign | (exponent as u32) << self.mantissa | (q as u32 & ((1 << self.mantissa) - 1))
+    }
+
+    /// `a + b`
+    pub fn add(&self, a: u32, b: u32, rounding: Rounding, flags: &mut u32) -> u32 {
+        if let Some(nan) = self.propagate_nan(&[a, b], flags) {
+            return nan;
+        }
+        let (x, y) = (self.to_f64(a), self.to_f64(b));
+        match (x.is_infinite(), y.is_infinite()) {
+            (true, true) if x != y => self.invalid(flags),
+            (true, _) => a,
+            (_, true) => b,
+            _ => {
+                let (sum, error) = exact_sum(x, y, rounding);
+                self.round(sum, error, rounding, flags)
+            }
+        }
+    }
+
+    /// `a * b`
+    pub fn mul(&self, a: u32, b: u32, rounding: Rounding, flags: &mut u32) -> u32 {
+        if let Some(nan) = self.propagate_nan(&[a, b], flags) {
+            return nan;
+        }
+        let product = self.to_f64(a) * self.to_f64(b);
+        if product.is_nan() {
+            // Zero times infinity
+            return self.invalid(flags);
+        }
+        if product.is_infinite() {
+            return (a ^ b) & self.sign_bit() | self.infinity();
+        }
+        self.round(product, 0.0, rounding, flags)
+    }
+
+    /// `a * b + c` with a single rounding
+    pub fn fma(&self, a: u32, b: u32, c: u32, rounding: Rounding, flags: &mut u32) -> u32 {
+        let product = self.to_f64(a) * self.to_f64(b);
+        // Zero times infinity is invalid even when the addend is a quiet NaN
+        if product.is_nan() && !self.is_nan(a) && !self.is_nan(b) {
+            self.propagate_nan(&[c], flags);
+            return self.invalid(flags);
+        }
+        if let Some(nan) = self.propagate_nan(&[a, b, c], flags) {
+            return nan;
+        }
+        let addend = self.to_f64(c);
+        match (product.is_infinite(), addend.is_infinite()) {
+            (true, true) if product != addend => self.invalid(flags),
+            (true, _) => (a ^ b) & self.sign_bit() | self.infinity(),
+            (_, true) => c,
+            _ => {
+                let (sum, error) = exact_sum(product, addend, rounding);
+                self.round(sum, error, rounding, flags)
+            }
+        }
+    }
+
+    /// IEEE 754-2019 `minimumNumber` or `maximumNumber`: a NaN operand yields the other
+    /// operand and -0 orders below +0
+    pub fn min_max(&self, a: u32, b: u32, max: bool, flags: &mut u32) -> u32 {
+        if self.is_signaling(a) || self.is_signaling(b) {
+            *flags |= FLAG_NV;
+        }
+        match (self.is_nan(a), self.is_nan(b)) {
+            (true, true) => return self.canonical_nan(),
+            (true, false) => return b,
+            (false, true) => return a,
+            _ => {}
+        }
+        let (x, y) = (self.to_f64(a), self.to_f64(b));
+        let a_first = if x == y { (a & self.sign_bit() != 0) != max } else { (x < y) != max };
+        if a_first { a } else { b }
+    }
+
+    /// Convert to an integer of `width` bits, saturating out-of-range values and NaNs
+    pub fn to_int(&self, a: u32, signed: bool, width: u32, rounding: Rounding, flags: &mut u32) -> u32 {
+        let (min, max) = if signed { (-(1i64 << (width - 1)), (1i64 << (width - 1)) - 1) } else { (0, (1i64 << width) - 1) };
+        let x = self.to_f64(a);
+        let rounded = match rounding {
+            Rounding::NearestEven => x.round_ties_even(),
+            Rounding::NearestMaxMagnitude => x.round(),
+            Rounding::TowardZero | Rounding::Odd => x.trunc(),
+            Rounding::Down => x.floor(),
+            Rounding::Up => x.ceil(),
+        };
+        let value = if x.is_nan() || rounded > max as f64 {
+            *flags |= FLAG_NV;
+            max
+        } else if rounded < min as f64 {
+            *flags |= FLAG_NV;
+            min
+        } else {
+            if rounded != x {
+                *flags |= FLAG_NX;
+            }
+            rounded as i64
+        };
+        value as u32
+    }
+
+    /// Convert an integer, already sign- or zero-extended, to this format
+    pub fn from_int(&self, value: i64, rounding: Rounding, flags: &mut u32) -> u32 {
+        self.round(value as f64, 0.0, rounding, flags)
+    }
+
+    /// Convert `a` from the `from` format to this format
+    pub fn convert(&self, from: Format, a: u32, rounding: Rounding, flags: &mut u32) -> u32 {
+        if from.propagate_nan(&[a], flags).is_some() {
+            return self.canonical_nan();
+        }
+        let x = from.to_f64(a);
+        if x.is_infinite() {
+            return if x < 0.0 { self.sign_bit() | self.infinity() } else { self.infinity() };
+        }
+        self.round(x, 0.0, rounding, flags)
+    }
+
+    /// The canonical NaN if any operand is a NaN, raising invalid for signaling NaNs
+    fn propagate_nan(&self, operands: &[u32], flags: &mut u32) -> Option<u32> {
+        if operands.iter().any(|&x| self.is_signaling(x)) {
+            *flags |= FLAG_NV;
+        }
+        operands.iter().any(|&x| self.is_nan(x)).then(|| self.canonical_nan())
+    }
+
+    fn invalid(&self, flags: &mut u32) -> u32 {
+        *flags |= FLAG_NV;
+        self.canonical_nan()
+    }
+}
+
+/// `a + b` as a rounded sum and its exact error. An exact zero sum of operands with
+/// opposite signs is +0, or -0 when rounding down.
+fn exact_sum(a: f64, b: f64, rounding: Rounding) -> (f64, f64) {
+    let sum = a + b;
+    if sum == 0.0 && !(a == 0.0 && b == 0.0 && a.is_sign_negative() == b.is_sign_negative()) {
+        return (if rounding == Rounding::Down { -0.0 } else { 0.0 }, 0.0);
+    }
+    // Knuth's TwoSum
+    let b_virtual = sum - a;
+    let error = (a - (sum - b_virtual)) + (b - b_virtual);
+    (sum, error)
+}
+
+/// `2^exponent` for exponents in the normal `f64` range
+fn pow2(exponent: i32) -> f64 {
+    f64::from_bits(((exponent + 1023) as u64) << 52)
+}
@@ -1 +1,4 @@
-pub mod io;
\ No newline at end of file
+pub mod io;
+pub mod elf;
+pub mod float;
+pub mod axi;
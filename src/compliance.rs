@@ -0,0 +1,245 @@
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use crate::common::elf::Elf;
+use crate::scalar::core::ScalarFrontend;
+use crate::scalar::dispatch::Halt;
+
+/// Extension of the reference signature file expected next to a test ELF
+const REFERENCE_EXTENSION: &str = "reference_output";
+/// ELF file type of a linked executable
+const ET_EXEC: u16 = 2;
+
+/// Result of running a single compliance test
+pub enum Outcome {
+    Pass,
+    Fail(String),
+}
+
+/// Runs `rv32ui`/`rv32um` style test ELFs to their `tohost` exit. The core has no
+/// compressed instructions, so `rv32uc` tests are not supported.
+///
+/// Tests must be linked for the simulator's memory map: code in ITCM, data and the
+/// `tohost` symbol in DTCM. A test passes when it exits with code 0 and, if a
+/// `<test>.reference_output` file sits next to it, the words between the
+/// `begin_signature` and `end_signature` symbols match that file line by line.
+pub struct ComplianceRunner {
+    /// Cycle limit for a single test
+    pub max_cycles: u64,
+}
+
+impl ComplianceRunner {
+    /// Create a runner giving each test up to `max_cycles`
+    pub fn new(max_cycles: u64) -> Self {
+        Self { max_cycles }
+    }
+
+    /// Run every test ELF below `dir`, printing one line per test and a summary.
+    /// Returns the number of failed tests.
+    pub fn run_dir(&self, dir: &Path, out: &mut impl Write) -> io::Result<usize> {
+        let mut tests = Vec::new();
+        collect_elfs(dir, &mut tests)?;
+        tests.sort();
+        if tests.is_empty() {
+            let message = format!("no test ELF files found in {}", dir.display());
+            return Err(io::Error::new(ErrorKind::NotFound, message));
+        }
+
+        let mut failed = 0;
+        for path in &tests {
+            let name = path.strip_prefix(dir).unwrap_or(path).display();
+            match self.run_test(path) {
+                Ok((Outcome::Pass, cycles)) => writeln!(out, "PASS {} ({} cycles)", name, cycles)?,
+                Ok((Outcome::Fail(reason), _)) => {
+                    failed += 1;
+                    writeln!(out, "FAIL {}: {}", name, reason)?;
+                }
+                Err(e) => {
+                    failed += 1;
+                    writeln!(out, "FAIL {}: {}", name, e)?;
+                }
+            }
+        }
+        writeln!(out, "{} passed, {} failed", tests.len() - failed, failed)?;
+        Ok(failed)
+    }
+
+    /// Run one test ELF on a fresh core, returning its outcome and cycle count
+    pub fn run_test(&self, path: &Path) -> io::Result<(Outcome, u64)> {
+        let elf = Elf::parse(&fs::read(path)?)?;
+        let mut core = ScalarFrontend::new();
+        core.load_program(&elf.segments, elf.entry, &elf.symbols)?;
+        if core.htif.is_none() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "test has no tohost symbol"));
+        }
+
+        let halt = core.run(self.max_cycles);
+        let cycles = core.cycle;
+        let outcome = match halt {
+            Some(Halt::Exit(0)) => self.check_signature(path, &elf, &mut core)?,
+            Some(Halt::Exit(code)) => Outcome::Fail(format!("test case {} failed", code)),
+            Some(halt) => Outcome::Fail(format!("halted at 0x{:08x}: {:?}", core.pc(), halt)),
+            None => Outcome::Fail(format!("no tohost exit within {} cycles", self.max_cycles)),
+        };
+        Ok((outcome, cycles))
+    }
+
+    /// Compare the signature region with the reference file, if the test has one
+    fn check_signature(&self, path: &Path, elf: &Elf, core: &mut ScalarFrontend) -> io::Result<Outcome> {
+        let mut reference_path = path.as_os_str().to_owned();
+        reference_path.push(".");
+        reference_path.push(REFERENCE_EXTENSION);
+        let reference = match fs::read_to_string(&reference_path) {
+            Ok(reference) => reference,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Outcome::Pass),
+            Err(e) => return Err(e),
+        };
+
+        let (Some(begin), Some(end)) = (elf.symbols.get("begin_signature"), elf.symbols.get("end_signature")) else {
+            return Ok(Outcome::Fail("reference file given but no signature symbols".to_string()));
+        };
+        let mut bus = core.data_bus();
+        let signature: Vec<Option<u32>> = (begin.addr..end.addr).step_by(4).map(|addr| bus.load(addr, 4)).collect();
+        let expected: Vec<&str> = reference.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+
+        if expected.len() != signature.len() {
+            let message = format!("signature has {} words, reference has {}", signature.len(), expected.len());
+            return Ok(Outcome::Fail(message));
+        }
+        for (i, (word, line)) in signature.iter().zip(&expected).enumerate() {
+            let Some(word) = word else {
+                return Ok(Outcome::Fail(format!("signature word {} is unmapped", i)));
+            };
+            let actual = format!("{:08x}", word);
+            if !line.eq_ignore_ascii_case(&actual) {
+                return Ok(Outcome::Fail(format!("signature word {}: expected {}, got {}", i, line, actual)));
+            }
+        }
+        Ok(Outcome::Pass)
+    }
+}
+
+/// Recursively collect the executable ELF files below `dir`
+fn collect_elfs(dir: &Path, tests: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_elfs(&path, tests)?;
+        } else if is_executable(&path) {
+            tests.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Whether the file is a linked ELF executable, skipping object files next to the tests
+fn is_executable(path: &Path) -> bool {
+    let mut header = [0; 18];
+    File::open(path).and_then(|mut file| file.read_exact(&mut header)).is_ok()
+        && Elf::is_elf(&header)
+        && u16::from_le_bytes([header[16], header[17]]) == ET_EXEC
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::asm::{assemble, Assembly};
+
+    /// Program header type of a loadable segment
+    const PT_LOAD_TYPE: u32 = 1;
+
+    /// Program reporting `code` through `tohost`, high half first
+    fn tohost_program(code: u32) -> String {
+        format!(
+            "_start:  li   t0, {}
+                      la   t1, tohost
+                      sw   zero, 4(t1)
+                      sw   t0, 0(t1)
+             spin:    j    spin
+                      .data
+                      .align 3
+             tohost:  .word 0, 0
+             fromhost: .word 0, 0",
+            (code << 1) | 1
+        )
+    }
+
+    /// Link an assembled program into a minimal ELF executable with its symbol table
+    fn link(program: &Assembly) -> Vec<u8> {
+        let names = ["_start", "tohost", "fromhost"];
+        let phoff = 52;
+        let mut data_off = phoff + 32 * program.segments.len();
+        let mut phdrs = Vec::new();
+        let mut body = Vec::new();
+        for segment in &program.segments {
+            let flags: u32 = if segment.executable { 0b101 } else { 0b110 };
+            for field in [PT_LOAD_TYPE, data_off as u32, segment.addr, segment.addr, segment.data.len() as u32, segment.mem_size, flags, 4] {
+                phdrs.extend_from_slice(&field.to_le_bytes());
+            }
+            body.extend_from_slice(&segment.data);
+            data_off += segment.data.len();
+        }
+
+        let mut strtab = vec![0u8];
+        let mut symtab = vec![0u8; 16];
+        for name in names {
+            let symbol = program.symbols.get(name).expect("program defines symbol");
+            symtab.extend_from_slice(&(strtab.len() as u32).to_le_bytes());
+            symtab.extend_from_slice(&symbol.addr.to_le_bytes());
+            symtab.extend_from_slice(&[0; 8]);
+            strtab.extend_from_slice(name.as_bytes());
+            strtab.push(0);
+        }
+        let symtab_off = data_off;
+        let strtab_off = symtab_off + symtab.len();
+        let shoff = strtab_off + strtab.len();
+
+        let mut elf = vec![0u8; 52];
+        elf[..7].copy_from_slice(b"\x7fELF\x01\x01\x01");
+        elf[16..18].copy_from_slice(&ET_EXEC.to_le_bytes());
+        elf[18..20].copy_from_slice(&243u16.to_le_bytes());
+        elf[24..28].copy_from_slice(&program.symbols.get("_start").unwrap().addr.to_le_bytes());
+        elf[28..32].copy_from_slice(&(phoff as u32).to_le_bytes());
+        elf[32..36].copy_from_slice(&(shoff as u32).to_le_bytes());
+        elf[42..44].copy_from_slice(&32u16.to_le_bytes());
+        elf[44..46].copy_from_slice(&(program.segments.len() as u16).to_le_bytes());
+        elf[46..48].copy_from_slice(&40u16.to_le_bytes());
+        elf[48..50].copy_from_slice(&3u16.to_le_bytes());
+        elf.extend_from_slice(&phdrs);
+        elf.extend_from_slice(&body);
+        elf.extend_from_slice(&symtab);
+        elf.extend_from_slice(&strtab);
+
+        // Null section, symbol table linked to the string table, string table
+        let sections: [(u32, usize, usize, u32); 3] = [(0, 0, 0, 0), (2, symtab_off, symtab.len(), 2), (3, strtab_off, strtab.len(), 0)];
+        for (typ, offset, size, link) in sections {
+            for field in [0, typ, 0, 0, offset as u32, size as u32, link, 0, 0, 16] {
+                elf.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+        elf
+    }
+
+    #[test]
+    fn reports_tohost_exit_status() {
+        let dir = std::env::temp_dir().join(format!("coral-compliance-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, code) in [("pass", 0), ("fail", 3)] {
+            let program = assemble(&tohost_program(code)).unwrap();
+            fs::write(dir.join(name), link(&program)).unwrap();
+        }
+        fs::write(dir.join("notes.txt"), "not an ELF").unwrap();
+
+        let runner = ComplianceRunner::new(10_000);
+        assert!(matches!(runner.run_test(&dir.join("pass")), Ok((Outcome::Pass, _))));
+        let mut out = Vec::new();
+        let failed = runner.run_dir(&dir, &mut out).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(failed, 1, "{}", out);
+        assert!(out.contains("FAIL fail: test case 3 failed"), "{}", out);
+        assert!(out.lines().any(|l| l.starts_with("PASS pass (")), "{}", out);
+        assert!(out.ends_with("1 passed, 1 failed\n"), "{}", out);
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+use crate::scalar::core::ScalarFrontend;
+use crate::scalar::csr::{Trap, CAUSE_ILLEGAL_INSTRUCTION, CAUSE_LOAD_ACCESS_FAULT, CAUSE_STORE_ACCESS_FAULT};
+use crate::scalar::dispatch::Halt;
+use crate::scalar::regfile::ABI_NAMES;
+
+/// Number of hardware breakpoint triggers
+const HW_BREAKPOINTS: usize = 4;
+/// Register number of the PC in the GDB RV32 register layout
+const PC_REGNUM: usize = 32;
+/// Cycles simulated between checks for an interrupt from GDB
+const INTERRUPT_POLL_CYCLES: u64 = 1024;
+/// Cycles a single step may wait for its instruction to issue
+const STEP_TIMEOUT: u64 = 10_000;
+
+/// GDB remote serial protocol stub serving a single debugger connection
+pub struct GdbStub {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    /// Software breakpoints inserted with `Z0`
+    sw_breakpoints: BTreeSet<u32>,
+    /// Hardware breakpoints inserted with `Z1`
+    hw_breakpoints: BTreeSet<u32>,
+}
+
+impl GdbStub {
+    /// Wait for GDB to connect on the given local TCP port
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        info!("Waiting for GDB on 127.0.0.1:{}", port);
+        let (stream, peer) = listener.accept()?;
+        info!("GDB connected from {}", peer);
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            sw_breakpoints: BTreeSet::new(),
+            hw_breakpoints: BTreeSet::new(),
+        })
+    }
+
+    /// Serve requests until GDB detaches, kills the target or disconnects
+    pub fn serve(&mut self, core: &mut ScalarFrontend) -> io::Result<()> {
+        while let Some(packet) = self.recv_packet()? {
+            debug!("GDB <- {}", String::from_utf8_lossy(&packet));
+            match packet.first() {
+                Some(b'k') => break,
+                Some(b'D') => {
+                    self.send_packet("OK")?;
+                    break;
+                }
+                _ => {
+                    let reply = match self.handle(&packet, core) {
+                        Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                            info!("GDB disconnected while the target was running");
+                            break;
+                        }
+                        reply => reply?,
+                    };
+                    self.send_packet(&reply)?;
+                }
+            }
+        }
+        info!("GDB session closed");
+        Ok(())
+    }
+
+    /// Handle one packet and build its reply, an empty reply marks it unsupported
+    fn handle(&mut self, packet: &[u8], core: &mut ScalarFrontend) -> io::Result<String> {
+        let Some((&command, args)) = packet.split_first() else {
+            return Ok(String::new());
+        };
+        let reply = match command {
+            b'?' => halt_reply(core).unwrap_or_else(|| "S05".to_string()),
+            b'g' => {
+                let mut out = String::new();
+                for i in 0..32 {
+                    push_reg(&mut out, core.regs.read(i));
+                }
+                push_reg(&mut out, core.pc());
+                out
+            }
+            b'G' => {
+                let values: Vec<u32> = args.chunks(8).filter_map(parse_reg).collect();
+                for (i, value) in values.iter().enumerate().take(PC_REGNUM + 1) {
+                    write_reg(core, i, *value);
+                }
+                "OK".to_string()
+            }
+            b'p' => match parse_hex(args).map(|n| n as usize) {
+                Some(n) if n < PC_REGNUM => {
+                    let mut out = String::new();
+                    push_reg(&mut out, core.regs.read(n as u8));
+                    out
+                }
+                Some(PC_REGNUM) => {
+                    let mut out = String::new();
+                    push_reg(&mut out, core.pc());
+                    out
+                }
+                _ => "E01".to_string(),
+            },
+            b'P' => {
+                let (reg, value) = split_at_byte(args, b'=');
+                match (parse_hex(reg).map(|n| n as usize), parse_reg(value)) {
+                    (Some(n), Some(value)) if n <= PC_REGNUM => {
+                        write_reg(core, n, value);
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            b'm' => {
+                let (addr, len) = split_at_byte(args, b',');
+                match (parse_hex(addr), parse_hex(len)) {
+                    (Some(addr), Some(len)) => read_memory(core, addr, len),
+                    _ => "E01".to_string(),
+                }
+            }
+            b'M' | b'X' => {
+                let (header, data) = split_at_byte(args, b':');
+                let (addr, len) = split_at_byte(header, b',');
+                let bytes = if command == b'M' { decode_hex(data) } else { Some(data.to_vec()) };
+                match (parse_hex(addr), parse_hex(len), bytes) {
+                    (Some(addr), Some(len), Some(bytes)) if bytes.len() == len as usize => {
+                        write_memory(core, addr, &bytes)
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            b'c' | b's' => {
+                if let Some(addr) = parse_hex(args) {
+                    core.redirect(addr);
+                }
+                self.resume(core, command == b's')?
+            }
+            b'Z' | b'z' => self.update_breakpoint(command == b'Z', args),
+            b'H' | b'T' => "OK".to_string(),
+            b'q' => self.query(args),
+            _ => String::new(),
+        };
+        Ok(reply)
+    }
+
+    /// Handle a general query packet
+    fn query(&self, args: &[u8]) -> String {
+        let query = String::from_utf8_lossy(args);
+        if query.starts_with("Supported") {
+            "PacketSize=4000;qXfer:features:read+".to_string()
+        } else if query == "Attached" {
+            "1".to_string()
+        } else if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            let (offset, len) = split_at_byte(range.as_bytes(), b',');
+            match (parse_hex(offset), parse_hex(len)) {
+                (Some(offset), Some(len)) => {
+                    let xml = target_xml();
+                    let start = (offset as usize).min(xml.len());
+                    let end = (start + len as usize).min(xml.len());
+                    let marker = if end == xml.len() { 'l' } else { 'm' };
+                    format!("{}{}", marker, &xml[start..end])
+                }
+                _ => "E01".to_string(),
+            }
+        } else {
+            String::new()
+        }
+    }
+
+    /// Insert or remove a software (`Z0`) or hardware (`Z1`) breakpoint
+    fn update_breakpoint(&mut self, insert: bool, args: &[u8]) -> String {
+        let mut fields = args.split(|b| *b == b',');
+        let (Some(kind), Some(addr)) = (fields.next(), fields.next().and_then(parse_hex)) else {
+            return "E01".to_string();
+        };
+        let set = match kind {
+            b"0" => &mut self.sw_breakpoints,
+            b"1" => &mut self.hw_breakpoints,
+            _ => return String::new(),
+        };
+        if !insert {
+            set.remove(&addr);
+        } else if kind == b"1" && set.len() >= HW_BREAKPOINTS && !set.contains(&addr) {
+            warn!("All {} hardware breakpoints in use", HW_BREAKPOINTS);
+            return "E0E".to_string();
+        } else {
+            set.insert(addr);
+        }
+        "OK".to_string()
+    }
+
+    /// Single-step or continue the core and build the stop reply
+    fn resume(&mut self, core: &mut ScalarFrontend, step: bool) -> io::Result<String> {
+        core.dispatch.control.breakpoints = self.sw_breakpoints.union(&self.hw_breakpoints).copied().collect();
+        core.resume();
+        if let Some(reply) = halt_reply(core) {
+            return Ok(reply);
+        }
+        if step {
+            if !core.step_instruction(STEP_TIMEOUT) {
+                warn!("Step timed out: instruction at 0x{:08x} did not issue", core.pc());
+            }
+            return Ok(halt_reply(core).unwrap_or_else(|| "S05".to_string()));
+        }
+
+        let pc = core.pc();
+        let control = &mut core.dispatch.control;
+        control.hit = None;
+        control.budget = None;
+        control.resume_pc = Some(pc);
+        loop {
+            for _ in 0..INTERRUPT_POLL_CYCLES {
+                core.tick();
+                if core.dispatch.control.hit.is_some() || core.halted().is_some() {
+                    break;
+                }
+            }
+            if let Some(reply) = halt_reply(core) {
+                core.drain();
+                return Ok(reply);
+            }
+            if let Some(addr) = core.dispatch.control.hit {
+                debug!("Breakpoint hit at 0x{:08x}", addr);
+                core.drain();
+                return Ok("S05".to_string());
+            }
+            if self.interrupt_requested()? {
+                debug!("Interrupted by GDB at cycle {}", core.cycle);
+                core.drain();
+                return Ok("S02".to_string());
+            }
+        }
+    }
+
+    /// Check without blocking whether GDB sent an interrupt (Ctrl-C) byte.
+    /// Other bytes received while the target runs are discarded.
+    /// Fails with `UnexpectedEof` once GDB disconnects.
+    fn interrupt_requested(&mut self) -> io::Result<bool> {
+        if self.reader.buffer().is_empty() {
+            self.reader.get_ref().set_nonblocking(true)?;
+            let filled = self.reader.fill_buf().map(|buf| buf.len());
+            self.reader.get_ref().set_nonblocking(false)?;
+            match filled {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => return Err(e),
+                Ok(0) => return Err(io::Error::new(ErrorKind::UnexpectedEof, "GDB disconnected")),
+                Ok(_) => {}
+            }
+        }
+        let buffer = self.reader.buffer();
+        match buffer.iter().position(|b| *b == 0x03) {
+            Some(i) => {
+                self.reader.consume(i + 1);
+                Ok(true)
+            }
+            None => {
+                let len = buffer.len();
+                self.reader.consume(len);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Read a single byte, or `None` once GDB disconnects
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.reader.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// Receive and acknowledge the next packet, with binary escapes removed
+    fn recv_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            // Skip acknowledgements and stray interrupts until a packet starts
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'$') => break,
+                    Some(_) => {}
+                }
+            }
+
+            let mut data = Vec::new();
+            let mut sum: u8 = 0;
+            let mut escaped = false;
+            loop {
+                let Some(byte) = self.read_byte()? else { return Ok(None) };
+                if byte == b'#' {
+                    break;
+                }
+                sum = sum.wrapping_add(byte);
+                if escaped {
+                    data.push(byte ^ 0x20);
+                    escaped = false;
+                } else if byte == b'}' {
+                    escaped = true;
+                } else {
+                    data.push(byte);
+                }
+            }
+
+            let mut checksum = [0; 2];
+            self.reader.read_exact(&mut checksum)?;
+            if parse_hex(&checksum) == Some(sum as u32) {
+                self.writer.write_all(b"+")?;
+                return Ok(Some(data));
+            }
+            warn!("Dropping GDB packet with bad checksum");
+            self.writer.write_all(b"-")?;
+        }
+    }
+
+    /// Send a packet, escaping reserved characters, and wait for GDB to acknowledge it
+    fn send_packet(&mut self, data: &str) -> io::Result<()> {
+        debug!("GDB -> {}", data);
+        let mut frame = vec![b'$'];
+        let mut sum: u8 = 0;
+        for &byte in data.as_bytes() {
+            let encoded: &[u8] = match byte {
+                b'$' | b'#' | b'}' | b'*' => &[b'}', byte ^ 0x20],
+                _ => &[byte],
+            };
+            for &b in encoded {
+                sum = sum.wrapping_add(b);
+                frame.push(b);
+            }
+        }
+        frame.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+
+        loop {
+            self.writer.write_all(&frame)?;
+            match self.read_byte()? {
+                Some(b'-') => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Stop reply for a halted core: exit status, or the signal matching the exception
+fn halt_reply(core: &ScalarFrontend) -> Option<String> {
+    let reply = match core.halted()? {
+        Halt::Exit(code) => format!("W{:02x}", code & 0xFF),
+        Halt::Trap(Trap { cause: CAUSE_ILLEGAL_INSTRUCTION, .. }) => "S04".to_string(),
+        Halt::Trap(Trap { cause: CAUSE_LOAD_ACCESS_FAULT | CAUSE_STORE_ACCESS_FAULT, .. }) => "S0b".to_string(),
+        Halt::Trap(_) | Halt::Breakpoint => "S05".to_string(),
+    };
+    Some(reply)
+}
+
+/// Target description advertising the RV32 integer register set
+fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\n\
+         <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+         <target version=\"1.0\">\n\
+         <architecture>riscv:rv32</architecture>\n\
+         <feature name=\"org.gnu.gdb.riscv.cpu\">\n",
+    );
+    for (i, name) in ABI_NAMES.iter().enumerate() {
+        let typ = match *name {
+            "ra" => "code_ptr",
+            "sp" | "gp" | "tp" => "data_ptr",
+            _ => "int",
+        };
+        let _ = writeln!(xml, "<reg name=\"{}\" bitsize=\"32\" type=\"{}\" regnum=\"{}\"/>", name, typ, i);
+    }
+    let _ = writeln!(xml, "<reg name=\"pc\" bitsize=\"32\" type=\"code_ptr\" regnum=\"{}\"/>", PC_REGNUM);
+    xml.push_str("</feature>\n</target>\n");
+    xml
+}
+
+/// Write register `n` of the GDB register layout
+fn write_reg(core: &mut ScalarFrontend, n: usize, value: u32) {
+    if n == PC_REGNUM {
+        core.redirect(value);
+    } else {
+        core.regs.write(n as u8, value);
+    }
+}
+
+/// Read `len` bytes of memory as hex, stopping early at the first unmapped byte.
+/// Device registers are not read, since reads may have side effects.
+fn read_memory(core: &mut ScalarFrontend, addr: u32, len: u32) -> String {
+    let bus = core.data_bus();
+    let mut out = String::new();
+    for i in 0..len {
+        match bus.peek(addr.wrapping_add(i), 1) {
+            Some(byte) => {
+                let _ = write!(out, "{:02x}", byte);
+            }
+            None => break,
+        }
+    }
+    if out.is_empty() && len > 0 { "E01".to_string() } else { out }
+}
+
+/// Write bytes to memory and refetch, since the write may have modified code.
+/// Writes touching device registers are refused so they cannot start device actions.
+fn write_memory(core: &mut ScalarFrontend, addr: u32, bytes: &[u8]) -> String {
+    let mut bus = core.data_bus();
+    let len = bytes.len() as u32;
+    if (0..len).any(|i| bus.peek(addr.wrapping_add(i), 1).is_none()) {
+        return "E01".to_string();
+    }
+    for (i, byte) in bytes.iter().enumerate() {
+        bus.store(addr.wrapping_add(i as u32), 1, *byte as u32);
+    }
+    let pc = core.pc();
+    core.redirect(pc);
+    "OK".to_string()
+}
+
+/// Append a register value as little-endian hex
+fn push_reg(out: &mut String, value: u32) {
+    for byte in value.to_le_bytes() {
+        let _ = write!(out, "{:02x}", byte);
+    }
+}
+
+/// Parse a register value sent as little-endian hex
+fn parse_reg(hex: &[u8]) -> Option<u32> {
+    let bytes = decode_hex(hex)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Parse a big-endian hex number
+fn parse_hex(hex: &[u8]) -> Option<u32> {
+    u32::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+}
+
+/// Decode a hex string into bytes
+fn decode_hex(hex: &[u8]) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    hex.chunks(2).map(|pair| parse_hex(pair).map(|b| b as u8)).collect()
+}
+
+/// Split at the first occurrence of `sep`, the separator is dropped
+fn split_at_byte(bytes: &[u8], sep: u8) -> (&[u8], &[u8]) {
+    match bytes.iter().position(|b| *b == sep) {
+        Some(i) => (&bytes[..i], &bytes[i + 1..]),
+        None => (bytes, &[]),
+    }
+}
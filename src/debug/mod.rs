@@ -0,0 +1,2 @@
+pub mod gdb;
+pub mod repl;
@@ -0,0 +1,380 @@
+use std::io::{self, BufRead, Write};
+use crate::scalar::banks::Requestor;
+use crate::scalar::core::ScalarFrontend;
+use crate::scalar::dispatch::Halt;
+use crate::scalar::regfile::ABI_NAMES;
+use crate::vector::regfile::NUM_VREGS;
+
+/// Cycles `run` simulates before giving up when nothing stops it
+const DEFAULT_RUN_CYCLES: u64 = 100_000;
+
+const HELP: &str = "\
+commands:
+  tick [n]            advance n cycles (default 1)
+  step [n]            advance until n more instructions retire (default 1)
+  run [max]           advance until a breakpoint, event or program exit
+  break <pc>          stop before the instruction at pc issues
+  delete <pc>         remove a pc breakpoint
+  stall <n>           stop when dispatch stalls for more than n cycles (0 disables)
+  info                list breakpoints and events
+  print <what>        show pc|regs|vector|matrix|dtcm|axi|fetch|icache|dcache|buffer|decode|queue|scoreboard|units|all
+  mem <addr> [len]    dump memory bytes (default 64)
+  help                show this message
+  quit                leave the console
+an empty line repeats the previous command";
+
+/// Why a run command stopped before its cycle limit
+enum Stop {
+    Breakpoint(u32),
+    Stall(u64),
+    Halt(Halt),
+}
+
+/// Interactive console for cycle-level inspection of the scalar pipeline
+pub struct Repl {
+    /// Stop when dispatch stalls for more than this many consecutive cycles
+    stall_limit: Option<u64>,
+    /// Command repeated on an empty line
+    last_command: String,
+}
+
+impl Repl {
+    /// Create a console with no event breakpoints
+    pub fn new() -> Self {
+        Self {
+            stall_limit: None,
+            last_command: String::new(),
+        }
+    }
+
+    /// Read commands from `input` until `quit` or end of input
+    pub fn run(&mut self, core: &mut ScalarFrontend, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        writeln!(output, "coral-npu-sim console, type 'help' for commands")?;
+        write!(output, "(sim) ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let command = if line.trim().is_empty() { self.last_command.clone() } else { line.trim().to_string() };
+            if command == "quit" || command == "q" {
+                break;
+            }
+            if !command.is_empty() {
+                self.execute(&command, core, &mut output)?;
+                self.last_command = command;
+            }
+            write!(output, "(sim) ")?;
+            output.flush()?;
+        }
+        writeln!(output)?;
+        Ok(())
+    }
+
+    /// Execute a single console command
+    fn execute(&mut self, command: &str, core: &mut ScalarFrontend, out: &mut impl Write) -> io::Result<()> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let arg = |i: usize| words.get(i).and_then(|w| parse_number(w));
+        match words[0] {
+            "tick" | "t" => {
+                let cycles = arg(1).unwrap_or(1) as u64;
+                self.advance(core, out, cycles, |_| false)?;
+            }
+            "step" | "s" => {
+                let target = core.dispatch.retired + arg(1).unwrap_or(1) as u64;
+                self.advance(core, out, DEFAULT_RUN_CYCLES, |core| core.dispatch.retired >= target)?;
+            }
+            "run" | "r" => {
+                let cycles = arg(1).map_or(DEFAULT_RUN_CYCLES, |n| n as u64);
+                self.advance(core, out, cycles, |_| false)?;
+            }
+            "break" | "b" => match arg(1) {
+                Some(pc) => {
+                    core.dispatch.control.breakpoints.insert(pc);
+                    writeln!(out, "breakpoint at 0x{:08x}", pc)?;
+                }
+                None => writeln!(out, "usage: break <pc>")?,
+            },
+            "delete" | "d" => match arg(1) {
+                Some(pc) if core.dispatch.control.breakpoints.remove(&pc) => {
+                    writeln!(out, "deleted breakpoint at 0x{:08x}", pc)?
+                }
+                _ => writeln!(out, "no such breakpoint")?,
+            },
+            "stall" => match arg(1) {
+                Some(0) => self.stall_limit = None,
+                Some(n) => self.stall_limit = Some(n as u64),
+                None => writeln!(out, "usage: stall <cycles>")?,
+            },
+            "info" | "i" => {
+                for pc in &core.dispatch.control.breakpoints {
+                    writeln!(out, "breakpoint 0x{:08x}", pc)?;
+                }
+                if let Some(limit) = self.stall_limit {
+                    writeln!(out, "event: dispatch stall > {} cycles", limit)?;
+                }
+            }
+            "print" | "p" => print_state(core, words.get(1).copied().unwrap_or("all"), out)?,
+            "mem" | "x" => match arg(1) {
+                Some(addr) => dump_memory(core, addr, arg(2).unwrap_or(64), out)?,
+                None => writeln!(out, "usage: mem <addr> [len]")?,
+            },
+            "help" | "h" => writeln!(out, "{}", HELP)?,
+            other => writeln!(out, "unknown command '{}', type 'help'", other)?,
+        }
+        Ok(())
+    }
+
+    /// Tick up to `cycles` times, stopping early on breakpoints, events or `done`
+    fn advance(
+        &mut self,
+        core: &mut ScalarFrontend,
+        out: &mut impl Write,
+        cycles: u64,
+        done: impl Fn(&ScalarFrontend) -> bool,
+    ) -> io::Result<()> {
+        // Step over the breakpoint we are currently stopped at
+        if let Some(pc) = core.dispatch.control.hit.take() {
+            core.dispatch.control.resume_pc = Some(pc);
+        }
+        core.resume();
+
+        let mut stop = None;
+        for _ in 0..cycles {
+            core.tick();
+            if let Some(halt) = core.halted() {
+                stop = Some(Stop::Halt(halt));
+            } else if let Some(pc) = core.dispatch.control.hit {
+                stop = Some(Stop::Breakpoint(pc));
+            } else if let Some(limit) = self.stall_limit && core.dispatch.stall_cycles == limit + 1 {
+                stop = Some(Stop::Stall(core.dispatch.stall_cycles));
+            }
+            if stop.is_some() || done(core) {
+                break;
+            }
+        }
+
+        match stop {
+            Some(Stop::Breakpoint(pc)) => writeln!(out, "breakpoint hit at 0x{:08x}", pc)?,
+            Some(Stop::Stall(cycles)) => writeln!(out, "dispatch stalled for {} cycles", cycles)?,
+            Some(Stop::Halt(Halt::Exit(code))) => writeln!(out, "program exited with code {}", code)?,
+            Some(Stop::Halt(halt)) => writeln!(out, "program halted: {:?}", halt)?,
+            None => {}
+        }
+        writeln!(out, "cycle {}, pc 0x{:08x}, retired {}", core.cycle, core.pc(), core.dispatch.retired)
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Print a named part of the pipeline state
+fn print_state(core: &ScalarFrontend, what: &str, out: &mut impl Write) -> io::Result<()> {
+    let all = what == "all";
+    if all || what == "pc" {
+        writeln!(out, "pc 0x{:08x}  cycle {}  retired {}", core.pc(), core.cycle, core.dispatch.retired)?;
+    }
+    if all || what == "regs" {
+        for (i, name) in ABI_NAMES.iter().enumerate() {
+            let end = if i % 4 == 3 { "\n" } else { "  " };
+            write!(out, "x{:<2} {:>4} 0x{:08x}{}", i, name, core.regs.read(i as u8), end)?;
+        }
+    }
+    if all || what == "vector" {
+        let vector = &core.csrs.vector;
+        let vtype = match vector.vtype() {
+            Some(vtype) => vtype.to_string(),
+            None => "vill".to_string(),
+        };
+        writeln!(out, "vtype {}  vl {}  vstart {}  vlen {}", vtype, vector.vl, vector.vstart, vector.vlen)?;
+        for i in 0..NUM_VREGS as u8 {
+            // Most significant byte first, like a register value
+            let bytes: String = core.dispatch.vector.regs.reg(i).iter().rev().map(|b| format!("{:02x}", b)).collect();
+            writeln!(out, "v{:<2} 0x{}", i, bytes)?;
+        }
+    }
+    if all || what == "matrix" {
+        let matrix = &core.dispatch.matrix;
+        let acc = &matrix.acc;
+        let peak = core.cycle * (acc.rows * acc.cols) as u64;
+        let utilization = if peak == 0 { 0.0 } else { 100.0 * matrix.macs as f64 / peak as f64 };
+        writeln!(out, "array {}x{}  macs {}  utilization {:.1}%", acc.rows, acc.cols, matrix.macs, utilization)?;
+        for tile in 0..acc.tiles() as u8 {
+            writeln!(out, "acc{}:", tile)?;
+            for row in acc.tile(tile).chunks(acc.cols) {
+                let values: Vec<String> = row.iter().map(|v| format!("{:>11}", v)).collect();
+                writeln!(out, " {}", values.join(""))?;
+            }
+        }
+    }
+    if all || what == "dtcm" {
+        let arbiter = &core.dtcm.arbiter;
+        let config = &arbiter.config;
+        writeln!(
+            out,
+            "dtcm: {} banks x {} bytes, {} port(s), {:?}",
+            config.banks, config.width, config.ports, config.arbitration
+        )?;
+        for requestor in Requestor::ALL {
+            let index = requestor as usize;
+            writeln!(
+                out,
+                "  {:<5} beats {}  conflict stalls {}  pending {}",
+                requestor.name(), arbiter.stats.beats[index], arbiter.stats.conflict_cycles[index], arbiter.pending(requestor)
+            )?;
+        }
+        let conflicts: Vec<String> = arbiter.stats.bank_conflicts.iter().map(|c| c.to_string()).collect();
+        writeln!(out, "  bank conflicts [{}]", conflicts.join(", "))?;
+    }
+    if all || what == "axi" {
+        let stats = &core.dram.stats;
+        writeln!(
+            out,
+            "axi: read bursts {} ({} bytes)  write bursts {} ({} bytes)  stalled {} cycles",
+            stats.read_bursts, stats.bytes_read, stats.write_bursts, stats.bytes_written, stats.stall_cycles
+        )?;
+    }
+    if all || what == "fetch" {
+        let fetch = &core.fetch;
+        let itcm = &core.itcm;
+        writeln!(
+            out,
+            "fetch: next 0x{:08x}  blocks in flight {} ({} queued)  fetched {}  starved {} cycles",
+            fetch.pc, fetch.pending_reads.len(), itcm.queued(), fetch.fetched.len(), fetch.starved_cycles
+        )?;
+        writeln!(
+            out,
+            "itcm: {} port(s), {}-byte blocks  blocks read {}  port stalls {} cycles",
+            itcm.ports, itcm.block_bytes, itcm.stats.blocks, itcm.stats.port_stall_cycles
+        )?;
+    }
+    if all || what == "icache" {
+        let icache = &core.icache;
+        let config = &icache.config;
+        let stats = &icache.stats;
+        let lookups = stats.hits + stats.misses;
+        let hit_rate = if lookups == 0 { 0.0 } else { 100.0 * stats.hits as f64 / lookups as f64 };
+        writeln!(
+            out,
+            "icache: {} bytes, {}-byte lines, {} way(s), {} sets, {:?}, prefetch {}",
+            config.size, config.line_bytes, config.ways, icache.num_sets(), config.replacement, if config.prefetch { "on" } else { "off" }
+        )?;
+        writeln!(
+            out,
+            "  hits {}  misses {}  hit rate {:.1}%  evictions {}  prefetches {} ({} used)",
+            stats.hits, stats.misses, hit_rate, stats.evictions, stats.prefetches, stats.useful_prefetches
+        )?;
+    }
+    if all || what == "dcache" {
+        match &core.dispatch.lsu.dcache {
+            Some(dcache) => {
+                let config = &dcache.config;
+                let stats = &dcache.stats;
+                writeln!(
+                    out,
+                    "dcache: {} bytes, {}-byte lines, {} way(s), {} sets, {:?}, {:?}, write-allocate {}, {} MSHRs ({} busy)",
+                    config.size, config.line_bytes, config.ways, dcache.num_sets(), config.replacement, config.write_policy,
+                    if config.write_allocate { "on" } else { "off" }, config.mshrs, dcache.outstanding()
+                )?;
+                writeln!(
+                    out,
+                    "  loads {} hit / {} miss  stores {} hit / {} miss  merged {}  evictions {}  write-backs {}  MSHR stalls {} cycles",
+                    stats.load_hits, stats.load_misses, stats.store_hits, stats.store_misses, stats.merged_misses,
+                    stats.evictions, stats.writebacks, stats.mshr_stall_cycles
+                )?;
+            }
+            None => writeln!(out, "dcache: disabled")?,
+        }
+    }
+    if all || what == "buffer" {
+        writeln!(out, "instruction buffer ({}/{}):", core.instr_buffer.queue.len(), core.instr_buffer.capacity)?;
+        for raw in &core.instr_buffer.queue {
+            writeln!(out, "  0x{:08x}: {:08x}", raw.pc, raw.data)?;
+        }
+    }
+    if all || what == "decode" {
+        writeln!(out, "decode lanes:")?;
+        for (lane, raw) in core.decode.lanes.iter().enumerate() {
+            match raw {
+                Some(raw) => writeln!(out, "  [{}] 0x{:08x}: {:08x}", lane, raw.pc, raw.data)?,
+                None => writeln!(out, "  [{}] -", lane)?,
+            }
+        }
+    }
+    if all || what == "queue" {
+        let queue = &core.dispatch.queue;
+        writeln!(out, "dispatch queue ({}/{}), stalled {} cycles:", queue.inner.len(), queue.capacity, core.dispatch.stall_cycles)?;
+        for instr in &queue.inner {
+            writeln!(out, "  0x{:08x}: {}", instr.pc, instr)?;
+        }
+        let vector = &core.dispatch.vector.queue;
+        writeln!(out, "vector queue ({}/{}):", vector.inner.len(), vector.capacity)?;
+        for entry in &vector.inner {
+            writeln!(out, "  0x{:08x}: {}  rs1 0x{:08x} rs2 0x{:08x}", entry.instr.pc, entry.instr, entry.rs1, entry.rs2)?;
+        }
+    }
+    if all || what == "scoreboard" {
+        let scoreboard = &core.dispatch.scoreboard;
+        let busy: Vec<String> = (0..32)
+            .filter(|r| scoreboard.reg_busy[*r])
+            .map(|r| format!("x{}", r))
+            .collect();
+        writeln!(out, "scoreboard: busy regs [{}]", busy.join(", "))?;
+        writeln!(
+            out,
+            "  alu {:?}  bru {:?}  lsu {}  csr {}  ext {:?}",
+            scoreboard.alu_busy, scoreboard.bru_busy, scoreboard.lsu_busy, scoreboard.csr_busy, scoreboard.extension_busy
+        )?;
+    }
+    if all || what == "units" {
+        let dispatch = &core.dispatch;
+        let units = dispatch.alus.iter().enumerate().map(|(i, u)| (format!("alu{}", i), u.busy, u.remaining as u32, u.current))
+            .chain(dispatch.brus.iter().enumerate().map(|(i, u)| (format!("bru{}", i), u.busy, u.remaining as u32, u.current)))
+            .chain([
+                ("lsu".to_string(), dispatch.lsu.busy, dispatch.lsu.remaining as u32, dispatch.lsu.current),
+                ("csr".to_string(), dispatch.csr.busy, dispatch.csr.remaining as u32, dispatch.csr.current),
+                ("valu".to_string(), dispatch.vector.alu.busy, dispatch.vector.alu.remaining, dispatch.vector.alu.current),
+                ("vlsu".to_string(), dispatch.vector.lsu.busy, dispatch.vector.lsu.remaining, dispatch.vector.lsu.current),
+                ("vperm".to_string(), dispatch.vector.permute.busy, dispatch.vector.permute.remaining, dispatch.vector.permute.current),
+                ("vfpu".to_string(), dispatch.vector.fpu.busy, dispatch.vector.fpu.remaining, dispatch.vector.fpu.current),
+                // Oldest instruction in the matrix engine
+                ("mtx".to_string(), !dispatch.matrix.is_idle(), dispatch.matrix.in_flight.front().map_or(0, |e| e.remaining), dispatch.matrix.in_flight.front().map(|e| e.instr)),
+            ])
+            .chain(dispatch.custom.iter().map(|slot| ("ext".to_string(), true, slot.remaining as u32, Some(slot.instr))));
+        for (name, busy, remaining, current) in units {
+            match current {
+                Some(instr) if busy => {
+                    writeln!(out, "  {:<5} 0x{:08x}: {} ({} cycles left)", name, instr.pc, instr, remaining)?
+                }
+                _ => writeln!(out, "  {:<5} idle", name)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Hex dump `len` bytes of memory starting at `addr`, device registers show as `??`
+fn dump_memory(core: &mut ScalarFrontend, addr: u32, len: u32, out: &mut impl Write) -> io::Result<()> {
+    let bus = core.data_bus();
+    for row in (0..len).step_by(16) {
+        let base = addr.wrapping_add(row);
+        write!(out, "0x{:08x}:", base)?;
+        for i in 0..16.min(len - row) {
+            match bus.peek(base.wrapping_add(i), 1) {
+                Some(byte) => write!(out, " {:02x}", byte)?,
+                None => write!(out, " ??")?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Parse a decimal or `0x`-prefixed hexadecimal number
+fn parse_number(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
@@ -0,0 +1,78 @@
+use crate::devices::mmio::{Device, DeviceMemory};
+use crate::scalar::csr::{MIP_MSIP, MIP_MTIP};
+
+/// Base address of the CLINT registers
+pub const CLINT_BASE: u32 = 0x0200_0000;
+/// Size of the CLINT register window in bytes
+pub const CLINT_SIZE: u32 = 0x1_0000;
+
+/// Software interrupt pending, bit 0 raises `mip.MSIP`
+const MSIP: u32 = 0x0000;
+/// 64-bit timer compare value, the timer interrupt is pending while `mtime >= mtimecmp`
+const MTIMECMP: u32 = 0x4000;
+/// 64-bit free-running timer
+const MTIME: u32 = 0xBFF8;
+
+/// Core-local interruptor with a single hart's software and timer interrupts.
+/// `mtime` advances once every `divider` core cycles.
+pub struct Clint {
+    msip: bool,
+    mtimecmp: u64,
+    mtime: u64,
+    divider: u32,
+    /// Cycles since `mtime` last advanced
+    prescale: u32,
+}
+
+impl Clint {
+    /// Create a CLINT whose timer advances every `divider` cycles
+    pub fn new(divider: u32) -> Self {
+        Self { msip: false, mtimecmp: u64::MAX, mtime: 0, divider: divider.max(1), prescale: 0 }
+    }
+}
+
+impl Default for Clint {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+/// Replace the 32-bit half of `reg` selected by `offset`
+fn write_half(reg: &mut u64, offset: u32, value: u32) {
+    let shift = 8 * (offset & 4);
+    *reg = (*reg & !(0xFFFF_FFFF << shift)) | (value as u64) << shift;
+}
+
+impl Device for Clint {
+    fn load(&mut self, offset: u32, _size: u32) -> u32 {
+        match offset & !3 {
+            MSIP => self.msip as u32,
+            MTIMECMP | 0x4004 => (self.mtimecmp >> (8 * (offset & 4))) as u32,
+            MTIME | 0xBFFC => (self.mtime >> (8 * (offset & 4))) as u32,
+            _ => 0,
+        }
+    }
+
+    fn store(&mut self, offset: u32, _size: u32, value: u32) {
+        match offset & !3 {
+            MSIP => self.msip = value & 1 != 0,
+            MTIMECMP | 0x4004 => write_half(&mut self.mtimecmp, offset, value),
+            MTIME | 0xBFFC => write_half(&mut self.mtime, offset, value),
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, _memory: &mut DeviceMemory) {
+        self.prescale += 1;
+        if self.prescale == self.divider {
+            self.prescale = 0;
+            self.mtime = self.mtime.wrapping_add(1);
+        }
+    }
+
+    fn interrupts(&self) -> u32 {
+        let software = if self.msip { MIP_MSIP } else { 0 };
+        let timer = if self.mtime >= self.mtimecmp { MIP_MTIP } else { 0 };
+        software | timer
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::VecDeque;
+use tracing::debug;
+use crate::common::axi::{AxiRead, AxiWrite};
+use crate::common::io::{Future, Poll};
+use crate::devices::mmio::{Device, DeviceMemory};
+use crate::scalar::banks::Requestor;
+use crate::scalar::csr::MIP_MEIP;
+
+/// Base address of the DMA registers
+pub const DMA_BASE: u32 = 0x0003_0000;
+/// Size of the DMA register window in bytes
+pub const DMA_SIZE: u32 = 0x100;
+
+/// Default number of chunks the engine keeps in flight
+pub const DEFAULT_DMA_CHUNKS: usize = 4;
+
+/// Source address of the first row
+const SRC: u32 = 0x00;
+/// Destination address of the first row
+const DST: u32 = 0x04;
+/// Bytes per row
+const LEN: u32 = 0x08;
+/// Number of rows, zero is treated as one
+const ROWS: u32 = 0x0C;
+/// Bytes between the starts of consecutive source rows
+const SRC_STRIDE: u32 = 0x10;
+/// Bytes between the starts of consecutive destination rows
+const DST_STRIDE: u32 = 0x14;
+/// Control flags
+const CTRL: u32 = 0x18;
+/// Status flags, writing a one clears `STATUS_DONE` and `STATUS_ERROR`
+const STATUS: u32 = 0x1C;
+
+/// `CTRL` flag: start a transfer, ignored while one is running
+const CTRL_START: u32 = 1 << 0;
+/// `CTRL` flag: raise the external interrupt while `STATUS_DONE` or `STATUS_ERROR` is set
+const CTRL_IRQ_ENABLE: u32 = 1 << 1;
+
+/// `STATUS` flag: a transfer is running
+const STATUS_BUSY: u32 = 1 << 0;
+/// `STATUS` flag: the last transfer completed
+const STATUS_DONE: u32 = 1 << 1;
+/// `STATUS` flag: the last transfer stopped at an address outside DTCM and DRAM
+const STATUS_ERROR: u32 = 1 << 2;
+
+/// DMA engine copying 2D blocks between external DRAM and DTCM.
+///
+/// A transfer copies `ROWS` rows of `LEN` bytes, stepping the source and destination
+/// by their strides after each row. Rows are split into chunks of at most one AXI
+/// burst, and the engine starts one chunk per cycle while fewer than `max_chunks` are
+/// in flight, so DRAM latency overlaps with the core and with other chunks. DRAM
+/// sides go through the AXI port, DTCM sides compete for the bank ports with the
+/// load/store units a beat of the AXI data width at a time.
+pub struct Dma {
+    src: u32,
+    dst: u32,
+    len: u32,
+    rows: u32,
+    src_stride: u32,
+    dst_stride: u32,
+    ctrl: u32,
+    status: u32,
+    /// Row and byte within the row of the next chunk to start
+    row: u32,
+    offset: u32,
+    /// Chunks started but not yet written, oldest first
+    in_flight: VecDeque<Chunk>,
+    max_chunks: usize,
+}
+
+/// Part of a row on its way from source to destination
+enum Chunk {
+    /// Waiting for the DRAM read, then written to `dst`
+    Reading { read: AxiRead, dst: u32 },
+    /// Waiting for the DTCM read grant, then `data` is written to `dst`
+    Loading { ticket: u64, data: Vec<u8>, dst: u32 },
+    /// Waiting for the DRAM write response
+    Writing(AxiWrite),
+    /// Waiting for the DTCM write grant
+    Storing(u64),
+}
+
+impl Dma {
+    /// Create a DMA engine keeping up to `max_chunks` chunks in flight
+    pub fn new(max_chunks: usize) -> Self {
+        Self {
+            src: 0,
+            dst: 0,
+            len: 0,
+            rows: 0,
+            src_stride: 0,
+            dst_stride: 0,
+            ctrl: 0,
+            status: 0,
+            row: 0,
+            offset: 0,
+            in_flight: VecDeque::new(),
+            max_chunks: max_chunks.max(1),
+        }
+    }
+
+    /// Stop the running transfer with `flag` set in the status
+    fn finish(&mut self, flag: u32) {
+        debug!("DMA transfer finished with status 0x{:x}", flag);
+        self.in_flight.clear();
+        self.status = (self.status & !STATUS_BUSY) | flag;
+    }
+
+    /// Start the next chunk, returning false if it touches an address outside DTCM and DRAM
+    fn start_chunk(&mut self, memory: &mut DeviceMemory) -> bool {
+        let len = (self.len - self.offset).min(memory.dram.burst_bytes());
+        let src = self.src.wrapping_add(self.row.wrapping_mul(self.src_stride)).wrapping_add(self.offset);
+        let dst = self.dst.wrapping_add(self.row.wrapping_mul(self.dst_stride)).wrapping_add(self.offset);
+        let (Some(from_dram), Some(_)) = (in_dram(memory, src, len), in_dram(memory, dst, len)) else {
+            return false;
+        };
+
+        let chunk = if from_dram {
+            Chunk::Reading { read: memory.dram.read(src, len), dst }
+        } else {
+            let data = (0..len).filter_map(|i| memory.load(src.wrapping_add(i), 1)).map(|b| b as u8).collect();
+            Chunk::Loading { ticket: request_dtcm(memory, src, len), data, dst }
+        };
+        self.in_flight.push_back(chunk);
+
+        self.offset += len;
+        if self.offset == self.len {
+            self.offset = 0;
+            self.row += 1;
+        }
+        true
+    }
+}
+
+/// Whether `len` bytes at `addr` lie in DRAM, `Some(false)` if they lie in DTCM and
+/// `None` if they lie in neither
+fn in_dram(memory: &DeviceMemory, addr: u32, len: u32) -> Option<bool> {
+    let end = addr.wrapping_add(len - 1);
+    if memory.dtcm.contains(addr) && memory.dtcm.contains(end) {
+        Some(false)
+    } else if memory.dram.contains(addr) && memory.dram.contains(end) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Queue the DTCM beats for `len` bytes at `addr`, returning the ticket of the last
+fn request_dtcm(memory: &mut DeviceMemory, addr: u32, len: u32) -> u64 {
+    let bytes: Vec<(u32, u32)> = (0..len).map(|i| (addr.wrapping_add(i), 1)).collect();
+    let beats = memory.dtcm.beats(&bytes, memory.dram.config.bytes_per_beat.max(1));
+    memory.dtcm.arbiter.request(Requestor::Dma, beats)
+}
+
+/// Write a chunk's data to `dst`, returning the chunk waiting for the write to finish
+fn deliver(memory: &mut DeviceMemory, dst: u32, data: Vec<u8>) -> Chunk {
+    if memory.dram.contains(dst) {
+        return Chunk::Writing(memory.dram.write(dst, data));
+    }
+    let len = data.len() as u32;
+    for (i, byte) in data.into_iter().enumerate() {
+        memory.store(dst.wrapping_add(i as u32), 1, byte as u32);
+    }
+    Chunk::Storing(request_dtcm(memory, dst, len))
+}
+
+impl Default for Dma {
+    fn default() -> Self {
+        Self::new(DEFAULT_DMA_CHUNKS)
+    }
+}
+
+impl Device for Dma {
+    fn load(&mut self, offset: u32, _size: u32) -> u32 {
+        match offset {
+            SRC => self.src,
+            DST => self.dst,
+            LEN => self.len,
+            ROWS => self.rows,
+            SRC_STRIDE => self.src_stride,
+            DST_STRIDE => self.dst_stride,
+            CTRL => self.ctrl,
+            STATUS => self.status,
+            _ => 0,
+        }
+    }
+
+    fn store(&mut self, offset: u32, _size: u32, value: u32) {
+        let busy = self.status & STATUS_BUSY != 0;
+        match offset {
+            // The transfer registers are latched while a transfer runs
+            _ if busy && offset < CTRL => {}
+            SRC => self.src = value,
+            DST => self.dst = value,
+            LEN => self.len = value,
+            ROWS => self.rows = value,
+            SRC_STRIDE => self.src_stride = value,
+            DST_STRIDE => self.dst_stride = value,
+            CTRL => {
+                self.ctrl = value & CTRL_IRQ_ENABLE;
+                if value & CTRL_START != 0 && !busy {
+                    debug!("DMA transfer 0x{:08x} -> 0x{:08x}, {} x {} bytes", self.src, self.dst, self.rows.max(1), self.len);
+                    self.status = STATUS_BUSY;
+                    self.row = 0;
+                    self.offset = 0;
+                }
+            }
+            STATUS => self.status &= !(value & (STATUS_DONE | STATUS_ERROR)),
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, memory: &mut DeviceMemory) {
+        if self.status & STATUS_BUSY == 0 {
+            return;
+        }
+
+        // Complete chunks in order as their DRAM accesses finish
+        while let Some(chunk) = self.in_flight.front_mut() {
+            match chunk {
+                Chunk::Reading { read, dst } => match read.poll(memory.dram) {
+                    Poll::Pending => break,
+                    Poll::Ready(data) => *chunk = deliver(memory, *dst, data),
+                },
+                Chunk::Loading { ticket, data, dst } => {
+                    if !memory.dtcm.arbiter.is_granted(Requestor::Dma, *ticket) {
+                        break;
+                    }
+                    *chunk = deliver(memory, *dst, std::mem::take(data));
+                }
+                Chunk::Writing(write) => match write.poll(memory.dram) {
+                    Poll::Pending => break,
+                    Poll::Ready(()) => {
+                        self.in_flight.pop_front();
+                    }
+                },
+                Chunk::Storing(ticket) => {
+                    if !memory.dtcm.arbiter.is_granted(Requestor::Dma, *ticket) {
+                        break;
+                    }
+                    self.in_flight.pop_front();
+                }
+            }
+        }
+
+        let started = self.row >= self.rows.max(1) || self.len == 0;
+        if !started && self.in_flight.len() < self.max_chunks && !self.start_chunk(memory) {
+            self.finish(STATUS_ERROR);
+        } else if started && self.in_flight.is_empty() {
+            self.finish(STATUS_DONE);
+        }
+    }
+
+    fn interrupts(&self) -> u32 {
+        let pending = self.status & (STATUS_DONE | STATUS_ERROR) != 0;
+        if pending && self.ctrl & CTRL_IRQ_ENABLE != 0 { MIP_MEIP } else { 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::axi::ExternalMemory;
+    use crate::scalar::memory::{Dtcm, DRAM_BASE, DTCM_BASE, DTCM_SIZE};
+
+    /// DTCM and DRAM the engine copies between, with the first DRAM bytes holding their offset
+    fn memories() -> (Dtcm, ExternalMemory) {
+        let mut dram = ExternalMemory::default();
+        for i in 0..256 {
+            dram.store(DRAM_BASE + i, 1, i);
+        }
+        (Dtcm::new(1), dram)
+    }
+
+    /// Program a transfer of `rows` rows of `len` bytes and start it
+    #[allow(clippy::too_many_arguments)]
+    fn start(dma: &mut Dma, src: u32, dst: u32, len: u32, rows: u32, src_stride: u32, dst_stride: u32, ctrl: u32) {
+        for (offset, value) in [(SRC, src), (DST, dst), (LEN, len), (ROWS, rows), (SRC_STRIDE, src_stride), (DST_STRIDE, dst_stride)] {
+            dma.store(offset, 4, value);
+        }
+        dma.store(CTRL, 4, ctrl | CTRL_START);
+    }
+
+    /// Tick until the transfer stops, returning its status
+    fn finish(dma: &mut Dma, dtcm: &mut Dtcm, dram: &mut ExternalMemory) -> u32 {
+        for _ in 0..1_000 {
+            dma.tick(&mut DeviceMemory { dtcm, dram });
+            dtcm.arbiter.tick();
+            dram.tick();
+            if dma.load(STATUS, 4) & STATUS_BUSY == 0 {
+                return dma.load(STATUS, 4);
+            }
+        }
+        panic!("transfer never finished");
+    }
+
+    #[test]
+    fn copies_2d_block_from_dram_to_dtcm() {
+        let (mut dtcm, mut dram) = memories();
+        let mut dma = Dma::default();
+        // Rows narrower than the destination stride leave gaps the copy must not touch
+        let (dst, len, rows, src_stride, dst_stride) = (DTCM_BASE + 0x100, 20, 3, 64, 24);
+        start(&mut dma, DRAM_BASE, dst, len, rows, src_stride, dst_stride, 0);
+        assert_eq!(dma.load(STATUS, 4), STATUS_BUSY);
+        assert_eq!(finish(&mut dma, &mut dtcm, &mut dram), STATUS_DONE);
+
+        for row in 0..rows {
+            for byte in 0..dst_stride {
+                let expected = if byte < len { row * src_stride + byte } else { 0 };
+                assert_eq!(dtcm.load(dst + row * dst_stride + byte, 1), expected, "row {} byte {}", row, byte);
+            }
+        }
+        assert_eq!(dma.interrupts(), 0, "no interrupt unless enabled");
+        assert!(dtcm.arbiter.stats.beats[Requestor::Dma as usize] > 0, "stores went through the bank ports");
+    }
+
+    #[test]
+    fn chunk_outside_dtcm_and_dram_stops_with_error() {
+        let (mut dtcm, mut dram) = memories();
+        let mut dma = Dma::default();
+        // The first row fits at the end of the DTCM, the second runs past it
+        start(&mut dma, DRAM_BASE, DTCM_BASE + DTCM_SIZE - 8, 8, 2, 8, 8, 0);
+        assert_eq!(finish(&mut dma, &mut dtcm, &mut dram), STATUS_ERROR);
+        // A source outside both memories fails too
+        start(&mut dma, 0x4000_0000, DTCM_BASE, 8, 1, 0, 0, 0);
+        assert_eq!(finish(&mut dma, &mut dtcm, &mut dram), STATUS_ERROR);
+    }
+
+    #[test]
+    fn interrupt_follows_irq_enable_and_status() {
+        for (ctrl, irq) in [(0, 0), (CTRL_IRQ_ENABLE, MIP_MEIP)] {
+            let (mut dtcm, mut dram) = memories();
+            let mut dma = Dma::default();
+            start(&mut dma, DRAM_BASE, DTCM_BASE, 16, 1, 0, 0, ctrl);
+            assert_eq!(dma.interrupts(), 0, "ctrl 0x{:x} while busy", ctrl);
+            assert_eq!(finish(&mut dma, &mut dtcm, &mut dram), STATUS_DONE);
+            assert_eq!(dma.interrupts(), irq, "ctrl 0x{:x} when done", ctrl);
+            dma.store(STATUS, 4, STATUS_DONE);
+            assert_eq!(dma.interrupts(), 0, "ctrl 0x{:x} after clearing the status", ctrl);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::common::axi::ExternalMemory;
+use crate::scalar::memory::Dtcm;
+
+/// Memories a bus-mastering device such as the DMA engine can access
+pub struct DeviceMemory<'a> {
+    pub dtcm: &'a mut Dtcm,
+    pub dram: &'a mut ExternalMemory,
+}
+
+impl DeviceMemory<'_> {
+    /// Load `size` bytes from the given address, or `None` if it is not in DTCM or DRAM
+    pub fn load(&mut self, addr: u32, size: u32) -> Option<u32> {
+        let end = addr.wrapping_add(size - 1);
+        if self.dtcm.contains(addr) && self.dtcm.contains(end) {
+            Some(self.dtcm.load(addr, size))
+        } else if self.dram.contains(addr) && self.dram.contains(end) {
+            Some(self.dram.load(addr, size))
+        } else {
+            None
+        }
+    }
+
+    /// Store `size` bytes to the given address, returns false if it is not in DTCM or DRAM
+    pub fn store(&mut self, addr: u32, size: u32, value: u32) -> bool {
+        let end = addr.wrapping_add(size - 1);
+        if self.dtcm.contains(addr) && self.dtcm.contains(end) {
+            self.dtcm.store(addr, size, value);
+        } else if self.dram.contains(addr) && self.dram.contains(end) {
+            self.dram.store(addr, size, value);
+        } else {
+            return false;
+        }
+        true
+    }
+}
+
+/// A device with registers mapped into the data address space
+pub trait Device {
+    /// Read `size` bytes (1, 2 or 4) at `offset` into the device's window
+    fn load(&mut self, offset: u32, size: u32) -> u32;
+
+    /// Write the low `size` bytes (1, 2 or 4) of `value` at `offset` into the device's window
+    fn store(&mut self, offset: u32, size: u32, value: u32);
+
+    /// Advance the device by one core clock cycle. Devices that master the bus
+    /// access `memory` directly.
+    fn tick(&mut self, _memory: &mut DeviceMemory) {}
+
+    /// Interrupt lines the device is asserting, as `mip` bits
+    fn interrupts(&self) -> u32 {
+        0
+    }
+}
+
+/// An address window routed to a device
+struct Region {
+    base: u32,
+    size: u32,
+    device: Box<dyn Device>,
+}
+
+impl Region {
+    /// Whether the whole access lies inside the window
+    fn covers(&self, addr: u32, size: u32) -> bool {
+        let offset = addr.wrapping_sub(self.base);
+        offset < self.size && self.size - offset >= size
+    }
+}
+
+/// Memory-mapped IO devices attached to the data bus
+#[derive(Default)]
+pub struct Mmio {
+    regions: Vec<Region>,
+}
+
+impl Mmio {
+    /// Map `device` at `base`, replacing any device already mapped there
+    pub fn attach(&mut self, base: u32, size: u32, device: Box<dyn Device>) {
+        self.regions.retain(|r| r.base != base);
+        self.regions.push(Region { base, size, device });
+    }
+
+    /// Advance every device by one core clock cycle
+    pub fn tick(&mut self, memory: &mut DeviceMemory) {
+        for region in &mut self.regions {
+            region.device.tick(memory);
+        }
+    }
+
+    /// Interrupt lines asserted by any device, as `mip` bits
+    pub fn interrupts(&self) -> u32 {
+        self.regions.iter().fold(0, |lines, r| lines | r.device.interrupts())
+    }
+
+    /// Load from a device register, or `None` if no device is mapped at the address
+    pub fn load(&mut self, addr: u32, size: u32) -> Option<u32> {
+        let region = self.regions.iter_mut().find(|r| r.covers(addr, size))?;
+        Some(region.device.load(addr - region.base, size))
+    }
+
+    /// Store to a device register, returns false if no device is mapped at the address
+    pub fn store(&mut self, addr: u32, size: u32, value: u32) -> bool {
+        match self.regions.iter_mut().find(|r| r.covers(addr, size)) {
+            Some(region) => {
+                region.device.store(addr - region.base, size, value);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod mmio;
+pub mod uart;
+pub mod clint;
+pub mod dma;
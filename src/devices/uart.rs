@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use tracing::warn;
+use crate::devices::mmio::Device;
+
+/// Base address of the UART registers
+pub const UART_BASE: u32 = 0x0002_0000;
+/// Size of the UART register window in bytes
+pub const UART_SIZE: u32 = 0x100;
+
+/// Write a byte to transmit it
+const TXDATA: u32 = 0x0;
+/// Read the next received byte, zero when none is waiting
+const RXDATA: u32 = 0x4;
+/// Read-only status flags
+const STATUS: u32 = 0x8;
+
+/// `STATUS` flag: the transmitter accepts a byte
+const STATUS_TX_READY: u32 = 1 << 0;
+/// `STATUS` flag: a received byte is waiting in `RXDATA`
+const STATUS_RX_VALID: u32 = 1 << 1;
+
+/// Simple UART console. Transmitted bytes go to `output` straight away and
+/// received bytes are taken in order from a preloaded input buffer.
+pub struct Uart {
+    output: Box<dyn Write>,
+    input: VecDeque<u8>,
+}
+
+impl Uart {
+    /// Create a UART writing to `output` that will receive `input`
+    pub fn new(output: Box<dyn Write>, input: Vec<u8>) -> Self {
+        Self { output, input: input.into() }
+    }
+}
+
+impl Default for Uart {
+    /// UART writing to stdout with nothing to receive
+    fn default() -> Self {
+        Self::new(Box::new(std::io::stdout()), Vec::new())
+    }
+}
+
+impl Device for Uart {
+    fn load(&mut self, offset: u32, _size: u32) -> u32 {
+        match offset {
+            RXDATA => self.input.pop_front().unwrap_or(0) as u32,
+            STATUS => STATUS_TX_READY | if self.input.is_empty() { 0 } else { STATUS_RX_VALID },
+            _ => 0,
+        }
+    }
+
+    fn store(&mut self, offset: u32, _size: u32, value: u32) {
+        if offset == TXDATA {
+            let written = self.output.write_all(&[value as u8]).and_then(|_| self.output.flush());
+            if let Err(e) = written {
+                warn!("UART output failed: {}", e);
+            }
+        }
+    }
+}
@@ -1,15 +1,222 @@
+use std::fs::File;
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use tracing::error;
+use crate::compliance::ComplianceRunner;
+use crate::common::elf::{Elf, Segment, SymbolTable};
+use crate::debug::gdb::GdbStub;
+use crate::debug::repl::Repl;
+use crate::devices::uart::{Uart, UART_BASE, UART_SIZE};
+use crate::scalar::asm::assemble;
 use crate::scalar::core::ScalarFrontend;
+use crate::scalar::dcache::DCache;
+use crate::scalar::disasm::dump_image;
+use crate::scalar::dispatch::Halt;
+use crate::scalar::memory::ITCM_BASE;
 
 pub mod scalar;
 pub mod vector;
 pub mod matrix;
 pub mod common;
+pub mod debug;
+pub mod devices;
+pub mod compliance;
+
+/// Program loaded when no image is given
+const DEMO_PROGRAM: &str = "
+    add  x5, x1, x2
+    add  x6, x5, x3
+    add  x7, x6, x4
+    sw   x7, 0(x0)
+    lw   x8, 0(x0)
+    add  x9, x8, x5
+    add  x10, x9, x9
+    add  x11, x10, x10
+    add  x12, x11, x11
+    add  x13, x12, x12
+    add  x14, x13, x13
+    add  x15, x14, x14
+    sw   x15, 8(x14)
+    lw   x17, 8(x14)
+    add  x17, x17, x17
+    li   a0, 0
+    ecall
+";
+
+/// Cycle limit for a run when none is given
+const DEFAULT_MAX_CYCLES: u64 = 10_000_000;
+
+/// Cycle limit for each compliance test when none is given
+const DEFAULT_COMPLIANCE_CYCLES: u64 = 1_000_000;
+
+const USAGE: &str = "usage: coral-npu-sim [IMAGE] [--max-cycles N] [--semihost-root DIR]
+                     [--uart-out FILE] [--uart-in FILE] [--dcache] [--gdb PORT | --repl]
+       coral-npu-sim disasm IMAGE [BASE]
+       coral-npu-sim compliance DIR [MAX_CYCLES]";
 
 fn main() {
     tracing_subscriber::fmt::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if let [command, path, rest @ ..] = args.as_slice() && command == "disasm" {
+        let base = rest.first().and_then(|b| u32::from_str_radix(b.trim_start_matches("0x"), 16).ok());
+        let result = std::fs::read(path)
+            .and_then(|image| dump_image(&image, base.unwrap_or(ITCM_BASE), &mut std::io::stdout().lock()));
+        if let Err(e) = result {
+            error!("Cannot disassemble {}: {}", path, e);
+        }
+        return;
+    }
+
+    if let [command, dir, rest @ ..] = args.as_slice() && command == "compliance" {
+        let max_cycles = rest.first().and_then(|n| n.parse().ok()).unwrap_or(DEFAULT_COMPLIANCE_CYCLES);
+        let runner = ComplianceRunner::new(max_cycles);
+        match runner.run_dir(Path::new(dir), &mut std::io::stdout().lock()) {
+            Ok(0) => return,
+            Ok(_) => std::process::exit(1),
+            Err(e) => {
+                error!("Cannot run compliance tests in {}: {}", dir, e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let mut image = None;
+    let mut gdb_port = None;
+    let mut repl = false;
+    let mut max_cycles = DEFAULT_MAX_CYCLES;
+    let mut semihost_root = None;
+    let mut uart_out = None;
+    let mut uart_in = None;
+    let mut dcache = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--gdb" => match args.next().and_then(|p| p.parse().ok()) {
+                Some(port) => gdb_port = Some(port),
+                None => {
+                    error!("Invalid GDB port\n{}", USAGE);
+                    std::process::exit(2);
+                }
+            },
+            "--repl" => repl = true,
+            "--dcache" => dcache = true,
+            "--max-cycles" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => max_cycles = n,
+                None => {
+                    error!("Invalid cycle limit\n{}", USAGE);
+                    std::process::exit(2);
+                }
+            },
+            "--semihost-root" => match args.next() {
+                Some(dir) => semihost_root = Some(PathBuf::from(dir)),
+                None => {
+                    error!("Missing semihosting directory\n{}", USAGE);
+                    std::process::exit(2);
+                }
+            },
+            "--uart-out" | "--uart-in" => match args.next() {
+                Some(path) if arg == "--uart-out" => uart_out = Some(path),
+                Some(path) => uart_in = Some(path),
+                None => {
+                    error!("Missing UART file\n{}", USAGE);
+                    std::process::exit(2);
+                }
+            },
+            path if image.is_none() && !path.starts_with("--") => image = Some(path),
+            _ => {
+                error!("Unexpected argument {}\n{}", arg, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
     let mut scalar_frontend = ScalarFrontend::new();
-    for cycle in 0..14 {
-        println!("\n===== Cycle {cycle} =====");
-        scalar_frontend.tick()
+    scalar_frontend.semihost.root = semihost_root;
+    if dcache {
+        scalar_frontend.dispatch.lsu.dcache = Some(DCache::default());
+    }
+    if uart_out.is_some() || uart_in.is_some() {
+        match open_uart(uart_out, uart_in) {
+            Ok(uart) => scalar_frontend.mmio.attach(UART_BASE, UART_SIZE, Box::new(uart)),
+            Err(e) => {
+                error!("Cannot open UART file: {}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+    let loaded = match image {
+        Some(path) => load_image(&mut scalar_frontend, path),
+        None => {
+            let demo = assemble(DEMO_PROGRAM).expect("demo program assembles");
+            scalar_frontend.load_program(&demo.segments, ITCM_BASE, &demo.symbols)
+        }
+    };
+    if let Err(e) = loaded {
+        error!("Cannot load {}: {}", image.unwrap_or("demo program"), e);
+        std::process::exit(2);
+    }
+
+    if let Some(port) = gdb_port {
+        let result = GdbStub::listen(port).and_then(|mut stub| stub.serve(&mut scalar_frontend));
+        if let Err(e) = result {
+            error!("GDB stub failed: {}", e);
+        }
+        return;
+    }
+    if repl {
+        let stdin = std::io::stdin();
+        if let Err(e) = Repl::new().run(&mut scalar_frontend, stdin.lock(), std::io::stdout()) {
+            error!("Console failed: {}", e);
+        }
+        return;
+    }
+
+    let halt = scalar_frontend.run(max_cycles);
+    let (cycles, retired) = (scalar_frontend.cycle, scalar_frontend.dispatch.retired);
+    let status = match halt {
+        Some(Halt::Exit(code)) => {
+            eprintln!("Program exited with code {} after {} cycles, {} instructions", code, cycles, retired);
+            code as i32
+        }
+        Some(halt) => {
+            eprintln!("Program halted at 0x{:08x} after {} cycles: {:?}", scalar_frontend.pc(), cycles, halt);
+            1
+        }
+        None => {
+            eprintln!("Program did not exit within {} cycles", max_cycles);
+            1
+        }
+    };
+    std::process::exit(status);
+}
+
+/// Create a UART writing to `output` or stdout and receiving the contents of `input`
+fn open_uart(output: Option<&String>, input: Option<&String>) -> io::Result<Uart> {
+    let output: Box<dyn Write> = match output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+    let input = match input {
+        Some(path) => std::fs::read(path)?,
+        None => Vec::new(),
+    };
+    Ok(Uart::new(output, input))
+}
+
+/// Load an ELF executable, assembly source (`.s`/`.S`) or raw binary placed at the ITCM base
+fn load_image(core: &mut ScalarFrontend, path: &str) -> io::Result<()> {
+    let bytes = std::fs::read(path)?;
+    if Elf::is_elf(&bytes) {
+        let elf = Elf::parse(&bytes)?;
+        return core.load_program(&elf.segments, elf.entry, &elf.symbols);
+    }
+    if path.ends_with(".s") || path.ends_with(".S") {
+        let source = String::from_utf8_lossy(&bytes);
+        let program = assemble(&source).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        let entry = program.symbols.get("_start").map_or(ITCM_BASE, |s| s.addr);
+        return core.load_program(&program.segments, entry, &program.symbols);
     }
+    let segment = Segment { addr: ITCM_BASE, mem_size: bytes.len() as u32, data: bytes, executable: true };
+    core.load_program(&[segment], ITCM_BASE, &SymbolTable::default())
 }
@@ -0,0 +1,48 @@
+/// Default number of accumulator tiles
+pub const DEFAULT_TILES: usize = 4;
+
+/// Accumulator tiles of the matrix engine, each `rows` x `cols` int32 values.
+///
+/// Element `(i, j)` of tile `t` is at `(t * rows + i) * cols + j`, row-major.
+pub struct AccumulatorFile {
+    pub rows: usize,
+    pub cols: usize,
+    data: Vec<i32>,
+}
+
+impl AccumulatorFile {
+    /// Create `tiles` zeroed tiles of `rows` x `cols` elements
+    pub fn new(tiles: usize, rows: usize, cols: usize) -> Self {
+        Self { rows, cols, data: vec![0; tiles * rows * cols] }
+    }
+
+    /// Number of tiles
+    pub fn tiles(&self) -> usize {
+        self.data.len() / (self.rows * self.cols).max(1)
+    }
+
+    /// Elements of tile `tile`, row-major
+    pub fn tile(&self, tile: u8) -> &[i32] {
+        let size = self.rows * self.cols;
+        &self.data[tile as usize * size..(tile as usize + 1) * size]
+    }
+
+    /// Mutable elements of tile `tile`, row-major
+    pub fn tile_mut(&mut self, tile: u8) -> &mut [i32] {
+        let size = self.rows * self.cols;
+        &mut self.data[tile as usize * size..(tile as usize + 1) * size]
+    }
+
+    /// Clear every element of tile `tile`
+    pub fn zero(&mut self, tile: u8) {
+        self.tile_mut(tile).fill(0);
+    }
+
+    /// Set every row of tile `tile` to `row`, one value per column
+    pub fn init(&mut self, tile: u8, row: &[i32]) {
+        let cols = self.cols;
+        for dest in self.tile_mut(tile).chunks_mut(cols) {
+            dest.copy_from_slice(row);
+        }
+    }
+}
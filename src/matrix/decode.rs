@@ -0,0 +1,92 @@
+use crate::scalar::custom::CUSTOM_0;
+use crate::scalar::instruction::Instruction;
+
+/// Matrix engine operations selected by funct3
+pub const MMAC: u8 = 0b000;
+pub const MZERO: u8 = 0b001;
+pub const MINIT: u8 = 0b010;
+pub const MREAD: u8 = 0b011;
+
+/// Matrix engine operation
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MatrixOp {
+    /// Outer-product multiply-accumulate of the int8 blocks in `vs1` and `vs2` into tile `acc`
+    Mac { acc: u8, vs1: u8, vs2: u8, signed_vs1: bool, signed_vs2: bool },
+    /// Clear every element of tile `acc`
+    Zero { acc: u8 },
+    /// Set every row of tile `acc` to the `cols` int32 values in the group starting at `vs1`
+    Init { acc: u8, vs1: u8 },
+    /// Copy tile `acc` row-major into the group starting at `vd`
+    Read { vd: u8, acc: u8, requant: Option<Requant> },
+}
+
+/// Requantization applied to accumulators on readout: a shift right by `x[rs1]`
+/// rounded by `vxrm`, then saturation to `width` bits
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Requant {
+    pub width: u32,
+    pub signed: bool,
+}
+
+impl MatrixOp {
+    /// Decode a custom-0 instruction, `None` if it is not a matrix operation.
+    /// The matrix engine owns funct3 values 0 to 3 of custom-0.
+    pub fn decode(instr: &Instruction) -> Option<Self> {
+        if instr.opcode != CUSTOM_0 {
+            return None;
+        }
+        let op = match (instr.funct3, instr.funct7) {
+            // funct7 bit 0 marks vs1 unsigned and bit 1 marks vs2 unsigned
+            (MMAC, 0b0000000..=0b0000011) => MatrixOp::Mac {
+                acc: instr.rd,
+                vs1: instr.rs1,
+                vs2: instr.rs2,
+                signed_vs1: instr.funct7 & 0b01 == 0,
+                signed_vs2: instr.funct7 & 0b10 == 0,
+            },
+            (MZERO, 0) if instr.rs1 == 0 && instr.rs2 == 0 => MatrixOp::Zero { acc: instr.rd },
+            (MINIT, 0) if instr.rs2 == 0 => MatrixOp::Init { acc: instr.rd, vs1: instr.rs1 },
+            (MREAD, 0) if instr.rs1 == 0 => MatrixOp::Read { vd: instr.rd, acc: instr.rs2, requant: None },
+            (MREAD, 0b0000001..=0b0000100) => {
+                let (width, signed) = match instr.funct7 {
+                    0b0000001 => (16, true),
+                    0b0000010 => (16, false),
+                    0b0000011 => (8, true),
+                    _ => (8, false),
+                };
+                MatrixOp::Read { vd: instr.rd, acc: instr.rs2, requant: Some(Requant { width, signed }) }
+            }
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Accumulator tile the operation uses
+    pub fn tile(&self) -> u8 {
+        match *self {
+            MatrixOp::Mac { acc, .. } | MatrixOp::Zero { acc } | MatrixOp::Init { acc, .. } | MatrixOp::Read { acc, .. } => acc,
+        }
+    }
+}
+
+/// Mnemonic of a matrix engine instruction
+pub fn mnemonic(instr: &Instruction) -> Option<&'static str> {
+    let name = match MatrixOp::decode(instr)? {
+        MatrixOp::Mac { signed_vs1, signed_vs2, .. } => match (signed_vs1, signed_vs2) {
+            (true, true) => "mmac",
+            (false, true) => "mmacus",
+            (true, false) => "mmacsu",
+            (false, false) => "mmacu",
+        },
+        MatrixOp::Zero { .. } => "mzero",
+        MatrixOp::Init { .. } => "minit",
+        MatrixOp::Read { requant: None, .. } => "mread.w",
+        MatrixOp::Read { requant: Some(Requant { width, signed }), .. } => match (width, signed) {
+            (16, true) => "mread.h",
+            (16, false) => "mread.hu",
+            (8, true) => "mread.b",
+            _ => "mread.bu",
+        },
+    };
+    Some(name)
+}
@@ -0,0 +1,209 @@
+use std::collections::VecDeque;
+use crate::matrix::accumulator::{AccumulatorFile, DEFAULT_TILES};
+use crate::matrix::decode::{MatrixOp, Requant};
+use crate::scalar::csr::{Trap, CAUSE_ILLEGAL_INSTRUCTION};
+use crate::scalar::instruction::Instruction;
+use crate::vector::alu::round_shift;
+use crate::vector::config::VectorConfig;
+use crate::vector::regfile::{group_mask, VectorRegisterFile, NUM_VREGS};
+
+/// Default rows and columns of the MAC array
+pub const DEFAULT_ARRAY_DIM: usize = 8;
+/// Default cycles from the last array step until the sums are in the tile
+pub const DEFAULT_MAC_LATENCY: u32 = 3;
+
+/// A matrix instruction in the engine
+#[derive(Copy, Clone, Debug)]
+pub struct InFlight {
+    pub instr: Instruction,
+    /// Cycles left until the instruction completes
+    pub remaining: u32,
+    /// Accumulator tile the instruction uses
+    pub tile: u8,
+    /// Vector registers the instruction reads or writes
+    pub regs: u32,
+}
+
+/// Outer-product int8 MAC array accumulating into int32 tiles.
+///
+/// `mmac acc, vs1, vs2` treats `vs1` as `depth` columns of `rows` int8 values and
+/// `vs2` as `depth` rows of `cols` int8 values, byte `k * rows + i` and `k * cols + j`,
+/// and adds `vs1[k][i] * vs2[k][j]` over `k` to element `(i, j)` of the tile. The
+/// depth is as many columns as fit in one vector register.
+///
+/// The array performs one rank-1 update of `rows * cols` MACs per cycle, so it accepts
+/// a new instruction every `depth` cycles, and the sums land in the tile `latency`
+/// cycles after the last step. Back-to-back accumulations into a tile chain without
+/// waiting, while initializing or reading a tile waits for its pending sums. Tile
+/// moves take a cycle per vector register, readout requantization adds the pipeline
+/// latency. Instructions execute functionally at issue, and the vector registers an
+/// executing instruction uses are reported to the vector backend as busy.
+pub struct MatrixEngine {
+    pub acc: AccumulatorFile,
+    /// Cycles from the last array step until the sums are in the tile
+    pub latency: u32,
+    /// Cycles before the array can start another multiply-accumulate
+    pub occupied: u32,
+    /// Issued instructions, oldest first
+    pub in_flight: VecDeque<InFlight>,
+    /// Multiply-accumulates performed
+    pub macs: u64,
+}
+
+impl MatrixEngine {
+    /// Create an engine with a `rows` x `cols` array, `tiles` accumulator tiles and a
+    /// pipeline of `latency` cycles
+    pub fn new(rows: usize, cols: usize, tiles: usize, latency: u32) -> Self {
+        Self {
+            acc: AccumulatorFile::new(tiles, rows, cols),
+            latency,
+            occupied: 0,
+            in_flight: VecDeque::new(),
+            macs: 0,
+        }
+    }
+
+    /// Rank-1 updates per instruction with registers of `vlenb` bytes, zero if an
+    /// operand does not fit in a register
+    pub fn depth(&self, vlenb: usize) -> usize {
+        vlenb / self.acc.rows.max(self.acc.cols).max(1)
+    }
+
+    /// Vector registers an instruction would use with registers of `vlenb` bytes,
+    /// `None` if it is illegal
+    pub fn registers_of(&self, instr: &Instruction, vlenb: usize) -> Option<u32> {
+        let op = MatrixOp::decode(instr)?;
+        if op.tile() as usize >= self.acc.tiles() {
+            return None;
+        }
+        // A group of the registers holding `bytes`, which must end by v31
+        let group = |base: u8, bytes: usize| {
+            let count = bytes.div_ceil(vlenb);
+            (base as usize + count <= NUM_VREGS).then(|| group_mask(base, count as u8))
+        };
+        let (rows, cols) = (self.acc.rows, self.acc.cols);
+        match op {
+            MatrixOp::Mac { vs1, vs2, .. } if self.depth(vlenb) > 0 => Some(group_mask(vs1, 1) | group_mask(vs2, 1)),
+            MatrixOp::Mac { .. } => None,
+            MatrixOp::Zero { .. } => Some(0),
+            MatrixOp::Init { vs1, .. } => group(vs1, cols * 4),
+            MatrixOp::Read { vd, requant, .. } => {
+                let width = requant.map_or(32, |r| r.width) as usize;
+                group(vd, rows * cols * width / 8)
+            }
+        }
+    }
+
+    /// Whether `instr` can start this cycle: a multiply-accumulate needs the array,
+    /// other operations wait for the pending sums of their tile
+    pub fn can_issue(&self, instr: &Instruction) -> bool {
+        match MatrixOp::decode(instr) {
+            Some(MatrixOp::Mac { .. }) => self.occupied == 0,
+            Some(op) => self.in_flight.iter().all(|entry| entry.tile != op.tile()),
+            None => true,
+        }
+    }
+
+    /// Vector registers used by executing instructions
+    pub fn busy_regs(&self) -> u32 {
+        self.in_flight.iter().fold(0, |regs, entry| regs | entry.regs)
+    }
+
+    /// Issue a matrix instruction with `rs1` holding the readout shift. A tile out of
+    /// range, a register group past `v31` or an array wider than a vector register
+    /// raises illegal instruction. Saturating readouts set `vxsat`.
+    pub fn issue(
+        &mut self,
+        instr: Instruction,
+        rs1: u32,
+        config: &mut VectorConfig,
+        vregs: &mut VectorRegisterFile,
+    ) -> Result<(), Trap> {
+        let illegal = Trap { cause: CAUSE_ILLEGAL_INSTRUCTION, tval: instr.raw };
+        let (Some(op), Some(regs)) = (MatrixOp::decode(&instr), self.registers_of(&instr, vregs.vlenb())) else {
+            return Err(illegal);
+        };
+        let (rows, cols) = (self.acc.rows, self.acc.cols);
+
+        let cycles = match op {
+            MatrixOp::Mac { acc, vs1, vs2, signed_vs1, signed_vs2 } => {
+                let depth = self.depth(vregs.vlenb());
+                let element = |bytes: &[u8], index: usize, signed: bool| {
+                    if signed { bytes[index] as i8 as i32 } else { bytes[index] as i32 }
+                };
+                let (a, b) = (vregs.reg(vs1), vregs.reg(vs2));
+                let tile = self.acc.tile_mut(acc);
+                for k in 0..depth {
+                    for i in 0..rows {
+                        let x = element(a, k * rows + i, signed_vs1);
+                        for j in 0..cols {
+                            let product = x * element(b, k * cols + j, signed_vs2);
+                            tile[i * cols + j] = tile[i * cols + j].wrapping_add(product);
+                        }
+                    }
+                }
+                self.macs += (depth * rows * cols) as u64;
+                self.occupied = depth as u32;
+                depth as u32 + self.latency
+            }
+            MatrixOp::Zero { acc } => {
+                self.acc.zero(acc);
+                1
+            }
+            MatrixOp::Init { acc, vs1 } => {
+                let row: Vec<i32> = (0..cols as u32).map(|j| vregs.read(vs1, j, 32) as i32).collect();
+                self.acc.init(acc, &row);
+                regs.count_ones()
+            }
+            MatrixOp::Read { vd, acc, requant: None } => {
+                for (index, &value) in self.acc.tile(acc).iter().enumerate() {
+                    vregs.write(vd, index as u32, 32, value as u32);
+                }
+                regs.count_ones()
+            }
+            MatrixOp::Read { vd, acc, requant: Some(Requant { width, signed }) } => {
+                let (min, max) = if signed {
+                    (-(1i128 << (width - 1)), (1i128 << (width - 1)) - 1)
+                } else {
+                    (0, (1i128 << width) - 1)
+                };
+                for (index, &value) in self.acc.tile(acc).iter().enumerate() {
+                    let shifted = round_shift(value as i128, rs1 & 31, config.vxrm);
+                    let clipped = shifted.clamp(min, max);
+                    config.vxsat |= clipped != shifted;
+                    vregs.write(vd, index as u32, width, clipped as u32);
+                }
+                regs.count_ones() + self.latency
+            }
+        };
+
+        self.in_flight.push_back(InFlight { instr, remaining: cycles, tile: op.tile(), regs });
+        Ok(())
+    }
+
+    /// Advance the engine by one cycle, returning the instructions that completed
+    pub fn tick(&mut self) -> Vec<(Instruction, u32)> {
+        self.occupied = self.occupied.saturating_sub(1);
+        let mut completed = Vec::new();
+        self.in_flight.retain_mut(|entry| {
+            if entry.remaining == 0 {
+                completed.push((entry.instr, 0));
+                return false;
+            }
+            entry.remaining -= 1;
+            true
+        });
+        completed
+    }
+
+    /// Whether no matrix instruction is executing
+    pub fn is_idle(&self) -> bool {
+        self.in_flight.is_empty()
+    }
+}
+
+impl Default for MatrixEngine {
+    fn default() -> Self {
+        Self::new(DEFAULT_ARRAY_DIM, DEFAULT_ARRAY_DIM, DEFAULT_TILES, DEFAULT_MAC_LATENCY)
+    }
+}
@@ -0,0 +1,3 @@
+pub mod accumulator;
+pub mod decode;
+pub mod engine;
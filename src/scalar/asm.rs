@@ -0,0 +1,950 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use crate::common::elf::{Segment, Symbol, SymbolTable};
+use crate::scalar::disasm::CSR_NAMES;
+use crate::scalar::memory::{DTCM_BASE, ITCM_BASE};
+use crate::scalar::regfile::ABI_NAMES;
+
+/// Output of the assembler: memory image segments and label addresses
+pub struct Assembly {
+    pub segments: Vec<Segment>,
+    pub symbols: SymbolTable,
+}
+
+/// Assembly error with the 1-based source line it occurred on
+#[derive(Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Assemble RV32 source text into ITCM (`.text`) and DTCM (`.data`) images.
+///
+/// Supports labels, `.org`, `.align`, `.word`/`.half`/`.byte`, `.space`/`.zero`,
+/// `.ascii`/`.asciz`/`.string`, `.equ`/`.set`, `%hi`/`%lo` and the common pseudo-instructions.
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    let statements = parse(source)?;
+    let mut assembler = Assembler::default();
+    assembler.run(&statements, false)?;
+    assembler.run(&statements, true)?;
+
+    let symbols = assembler
+        .labels
+        .iter()
+        .map(|(name, addr)| Symbol { name: name.clone(), addr: *addr, size: 0 })
+        .collect();
+    let segments = assembler
+        .chunks
+        .into_iter()
+        .filter(|chunk| !chunk.data.is_empty())
+        .map(|chunk| Segment {
+            addr: chunk.addr,
+            mem_size: chunk.data.len() as u32,
+            data: chunk.data,
+            executable: chunk.section == Section::Text,
+        })
+        .collect();
+    Ok(Assembly { segments, symbols: SymbolTable::new(symbols) })
+}
+
+/// A source line split into labels, an operation and its operands
+struct Statement {
+    line: usize,
+    labels: Vec<String>,
+    op: String,
+    args: Vec<String>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Section {
+    Text,
+    Data,
+}
+
+/// Contiguous bytes emitted at a fixed address
+struct Chunk {
+    section: Section,
+    addr: u32,
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Assembler {
+    /// Label and `.equ` values, complete after the first pass
+    labels: HashMap<String, u32>,
+    /// Location counters of `.text` and `.data`
+    text_pc: u32,
+    data_pc: u32,
+    section: Option<Section>,
+    /// `.equ` symbols whose value depended on a later label in the first pass
+    deferred: HashSet<String>,
+    /// Sizes chosen for variable-length pseudo-instructions in the first pass
+    sizes: Vec<u32>,
+    chunks: Vec<Chunk>,
+    /// Second pass: emit bytes and require every symbol to resolve
+    emit: bool,
+}
+
+impl Assembler {
+    /// Lay out (first pass) or encode (second pass) every statement
+    fn run(&mut self, statements: &[Statement], emit: bool) -> Result<(), AsmError> {
+        self.emit = emit;
+        self.text_pc = ITCM_BASE;
+        self.data_pc = DTCM_BASE;
+        self.section = None;
+        self.chunks.clear();
+        self.switch(Section::Text);
+
+        for (index, statement) in statements.iter().enumerate() {
+            let error = |message: String| AsmError { line: statement.line, message };
+            for label in &statement.labels {
+                if !emit && self.labels.insert(label.clone(), self.pc()).is_some() {
+                    return Err(error(format!("duplicate label '{}'", label)));
+                }
+            }
+            if statement.op.is_empty() {
+                continue;
+            }
+            if statement.op.starts_with('.') {
+                self.directive(statement).map_err(error)?;
+            } else {
+                let words = self.instruction(statement, index).map_err(error)?;
+                for word in words {
+                    self.push(&word.to_le_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Current location counter
+    fn pc(&self) -> u32 {
+        match self.section {
+            Some(Section::Data) => self.data_pc,
+            _ => self.text_pc,
+        }
+    }
+
+    /// Move the location counter of the current section, starting a new chunk
+    fn set_pc(&mut self, pc: u32) {
+        let section = self.section.unwrap_or(Section::Text);
+        match section {
+            Section::Text => self.text_pc = pc,
+            Section::Data => self.data_pc = pc,
+        }
+        self.chunks.push(Chunk { section, addr: pc, data: Vec::new() });
+    }
+
+    /// Switch to a section, continuing at its location counter
+    fn switch(&mut self, section: Section) {
+        if self.section != Some(section) {
+            self.section = Some(section);
+            let pc = self.pc();
+            self.set_pc(pc);
+        }
+    }
+
+    /// Append bytes at the location counter
+    fn push(&mut self, bytes: &[u8]) {
+        if self.emit {
+            self.chunks.last_mut().unwrap().data.extend_from_slice(bytes);
+        }
+        let pc = self.pc().wrapping_add(bytes.len() as u32);
+        match self.section {
+            Some(Section::Data) => self.data_pc = pc,
+            _ => self.text_pc = pc,
+        }
+    }
+
+    /// Assemble a directive
+    fn directive(&mut self, statement: &Statement) -> Result<(), String> {
+        let args = &statement.args;
+        match statement.op.as_str() {
+            ".text" => self.switch(Section::Text),
+            ".data" | ".rodata" | ".bss" => self.switch(Section::Data),
+            ".section" => match args.first().map(String::as_str) {
+                Some(name) if name.starts_with(".text") => self.switch(Section::Text),
+                Some(_) => self.switch(Section::Data),
+                None => return Err(".section needs a name".to_string()),
+            },
+            ".globl" | ".global" | ".type" | ".size" | ".option" | ".file" => {}
+            ".org" => {
+                let addr = self.expr(arg(args, 0)?)?;
+                if addr < self.pc() && self.chunks.last().is_some_and(|c| !c.data.is_empty()) {
+                    return Err(format!(".org 0x{:x} moves backwards", addr));
+                }
+                self.set_pc(addr);
+            }
+            ".align" | ".p2align" | ".balign" => {
+                let n = self.expr(arg(args, 0)?)?;
+                let align = match statement.op.as_str() {
+                    ".balign" => n.max(1),
+                    _ if n < 32 => 1 << n,
+                    op => return Err(format!("{} {} out of range", op, n)),
+                };
+                let padding = self.pc().wrapping_neg() % align;
+                self.push(&vec![0; padding as usize]);
+            }
+            ".space" | ".zero" | ".skip" => {
+                let n = self.expr(arg(args, 0)?)?;
+                self.push(&vec![0; n as usize]);
+            }
+            ".word" | ".4byte" | ".half" | ".2byte" | ".short" | ".byte" => {
+                let size = match statement.op.as_str() {
+                    ".word" | ".4byte" => 4,
+                    ".half" | ".2byte" | ".short" => 2,
+                    _ => 1,
+                };
+                for value in args {
+                    let value = self.expr(value)?;
+                    self.push(&value.to_le_bytes()[..size]);
+                }
+            }
+            ".ascii" | ".asciz" | ".string" => {
+                for text in args {
+                    let mut bytes = parse_string(text)?;
+                    if statement.op != ".ascii" {
+                        bytes.push(0);
+                    }
+                    self.push(&bytes);
+                }
+            }
+            ".equ" | ".set" => {
+                let value = self.expr(arg(args, 1)?)?;
+                let name = arg(args, 0)?.to_string();
+                if !self.emit && !self.resolves(arg(args, 1)?) {
+                    self.deferred.insert(name.clone());
+                }
+                self.labels.insert(name, value);
+            }
+            other => return Err(format!("unknown directive '{}'", other)),
+        }
+        Ok(())
+    }
+
+    /// Assemble an instruction or pseudo-instruction into machine words
+    fn instruction(&mut self, statement: &Statement, index: usize) -> Result<Vec<u32>, String> {
+        let args = &statement.args;
+        let a = |i: usize| arg(args, i);
+        let pc = self.pc();
+        let op = statement.op.as_str();
+        let expect = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err(format!("'{}' takes {} operands, found {}", op, n, args.len()))
+            }
+        };
+
+        if let Some((funct3, funct7)) = r_type(op) {
+            expect(3)?;
+            return Ok(vec![encode_r(0b0110011, funct3, funct7, reg(a(0)?)?, reg(a(1)?)?, reg(a(2)?)?)]);
+        }
+        if let Some(funct3) = i_type(op) {
+            expect(3)?;
+            let imm = self.imm(a(2)?, 12)?;
+            return Ok(vec![encode_i(0b0010011, funct3, reg(a(0)?)?, reg(a(1)?)?, imm)]);
+        }
+        if let Some((funct3, funct7)) = shift_type(op) {
+            expect(3)?;
+            let shamt = self.expr(a(2)?)?;
+            if shamt > 31 {
+                return Err(format!("shift amount {} out of range", shamt));
+            }
+            return Ok(vec![encode_i(0b0010011, funct3, reg(a(0)?)?, reg(a(1)?)?, (funct7 << 5) | shamt as i32)]);
+        }
+        if let Some(funct3) = load_type(op) {
+            expect(2)?;
+            let (offset, base) = self.mem_operand(a(1)?)?;
+            return Ok(vec![encode_i(0b0000011, funct3, reg(a(0)?)?, base, offset)]);
+        }
+        if let Some(funct3) = store_type(op) {
+            expect(2)?;
+            let (offset, base) = self.mem_operand(a(1)?)?;
+            return Ok(vec![encode_s(funct3, base, reg(a(0)?)?, offset)]);
+        }
+        if let Some((funct3, swap)) = branch_type(op) {
+            expect(3)?;
+            let (rs1, rs2) = if swap { (reg(a(1)?)?, reg(a(0)?)?) } else { (reg(a(0)?)?, reg(a(1)?)?) };
+            return Ok(vec![encode_b(funct3, rs1, rs2, self.offset(a(2)?, 13)?)]);
+        }
+        if let Some((funct3, rs2_zero)) = branch_zero_type(op) {
+            expect(2)?;
+            let offset = self.offset(a(1)?, 13)?;
+            let rs = reg(a(0)?)?;
+            let (rs1, rs2) = if rs2_zero { (rs, 0) } else { (0, rs) };
+            return Ok(vec![encode_b(funct3, rs1, rs2, offset)]);
+        }
+        if let Some(funct3) = csr_type(op) {
+            expect(3)?;
+            let csr = csr(a(1)?)?;
+            let source = if funct3 & 0b100 != 0 { self.uimm(a(2)?)? } else { reg(a(2)?)? };
+            return Ok(vec![encode_i(0b1110011, funct3, reg(a(0)?)?, source, csr)]);
+        }
+        if let Some(funct3) = csr_pseudo_type(op) {
+            expect(2)?;
+            let csr = csr(a(0)?)?;
+            let source = if funct3 & 0b100 != 0 { self.uimm(a(1)?)? } else { reg(a(1)?)? };
+            return Ok(vec![encode_i(0b1110011, funct3, 0, source, csr)]);
+        }
+
+        let words = match op {
+            "lui" | "auipc" => {
+                expect(2)?;
+                let opcode = if op == "lui" { 0b0110111 } else { 0b0010111 };
+                let imm = self.expr(a(1)?)?;
+                if imm > 0xFFFFF {
+                    return Err(format!("upper immediate 0x{:x} out of range", imm));
+                }
+                vec![opcode | ((reg(a(0)?)? as u32) << 7) | (imm << 12)]
+            }
+            "jal" => match args.len() {
+                1 => vec![encode_j(1, self.offset(a(0)?, 21)?)],
+                _ => {
+                    expect(2)?;
+                    vec![encode_j(reg(a(0)?)?, self.offset(a(1)?, 21)?)]
+                }
+            },
+            "j" => {
+                expect(1)?;
+                vec![encode_j(0, self.offset(a(0)?, 21)?)]
+            }
+            "jalr" => match args.len() {
+                1 => vec![encode_i(0b1100111, 0, 1, reg(a(0)?)?, 0)],
+                2 => {
+                    let (offset, base) = self.mem_operand(a(1)?)?;
+                    vec![encode_i(0b1100111, 0, reg(a(0)?)?, base, offset)]
+                }
+                _ => {
+                    expect(3)?;
+                    vec![encode_i(0b1100111, 0, reg(a(0)?)?, reg(a(1)?)?, self.imm(a(2)?, 12)?)]
+                }
+            },
+            "jr" => {
+                expect(1)?;
+                vec![encode_i(0b1100111, 0, 0, reg(a(0)?)?, 0)]
+            }
+            "ret" => vec![encode_i(0b1100111, 0, 0, 1, 0)],
+            "call" | "tail" => {
+                expect(1)?;
+                let rd = if op == "call" { 1 } else { 6 };
+                let (hi, lo) = split_offset(self.expr(a(0)?)?.wrapping_sub(pc));
+                vec![0b0010111 | (rd << 7) | hi, encode_i(0b1100111, 0, if op == "call" { 1 } else { 0 }, rd as u8, lo)]
+            }
+            "nop" => vec![encode_i(0b0010011, 0, 0, 0, 0)],
+            "mv" => {
+                expect(2)?;
+                vec![encode_i(0b0010011, 0, reg(a(0)?)?, reg(a(1)?)?, 0)]
+            }
+            "not" => {
+                expect(2)?;
+                vec![encode_i(0b0010011, 0b100, reg(a(0)?)?, reg(a(1)?)?, -1)]
+            }
+            "neg" => {
+                expect(2)?;
+                vec![encode_r(0b0110011, 0b000, 0b0100000, reg(a(0)?)?, 0, reg(a(1)?)?)]
+            }
+            "seqz" => {
+                expect(2)?;
+                vec![encode_i(0b0010011, 0b011, reg(a(0)?)?, reg(a(1)?)?, 1)]
+            }
+            "snez" => {
+                expect(2)?;
+                vec![encode_r(0b0110011, 0b011, 0, reg(a(0)?)?, 0, reg(a(1)?)?)]
+            }
+            "sltz" => {
+                expect(2)?;
+                vec![encode_r(0b0110011, 0b010, 0, reg(a(0)?)?, reg(a(1)?)?, 0)]
+            }
+            "sgtz" => {
+                expect(2)?;
+                vec![encode_r(0b0110011, 0b010, 0, reg(a(0)?)?, 0, reg(a(1)?)?)]
+            }
+            "li" => {
+                expect(2)?;
+                let rd = reg(a(0)?)?;
+                let value = self.expr(a(1)?)?;
+                // The first pass picks the size, the second must keep it even if the value changed
+                if !self.emit {
+                    let fits = self.resolves(a(1)?) && fits_signed(value as i32, 12);
+                    self.sizes.resize(index + 1, 0);
+                    self.sizes[index] = if fits { 4 } else { 8 };
+                }
+                if self.sizes[index] == 4 {
+                    // A symbol defined from a later label read as zero when the size was chosen
+                    if !fits_signed(value as i32, 12) {
+                        return Err(format!("value 0x{:x} of '{}' changed after layout and no longer fits 'li'", value, a(1)?));
+                    }
+                    vec![encode_i(0b0010011, 0, rd, 0, value as i32)]
+                } else {
+                    let (hi, lo) = split_offset(value);
+                    vec![0b0110111 | ((rd as u32) << 7) | hi, encode_i(0b0010011, 0, rd, rd, lo)]
+                }
+            }
+            "la" => {
+                expect(2)?;
+                let rd = reg(a(0)?)?;
+                let (hi, lo) = split_offset(self.expr(a(1)?)?.wrapping_sub(pc));
+                vec![0b0010111 | ((rd as u32) << 7) | hi, encode_i(0b0010011, 0, rd, rd, lo)]
+            }
+            "fence" => vec![0x0FF0000F],
+            "fence.i" => vec![0x0000100F],
+            "ecall" => vec![0x00000073],
+            "ebreak" => vec![0x00100073],
+            "mret" => vec![0x30200073],
+            "wfi" => vec![0x10500073],
+            "csrr" => {
+                expect(2)?;
+                vec![encode_i(0b1110011, 0b010, reg(a(0)?)?, 0, csr(a(1)?)?)]
+            }
+            _ => return Err(format!("unknown instruction '{}'", op)),
+        };
+        Ok(words)
+    }
+
+    /// Evaluate an expression of numbers, symbols, `.`, `%hi()`/`%lo()` joined by `+` and `-`.
+    /// Unknown symbols evaluate to zero in the first pass.
+    fn expr(&self, text: &str) -> Result<u32, String> {
+        let text = text.trim();
+        let mut total: u32 = 0;
+        let mut rest = text;
+        let mut negate = false;
+        loop {
+            rest = rest.trim_start();
+            if let Some(tail) = rest.strip_prefix('-') {
+                negate = !negate;
+                rest = tail;
+                continue;
+            }
+            if let Some(tail) = rest.strip_prefix('+') {
+                rest = tail;
+                continue;
+            }
+
+            let end = term_end(rest);
+            if end == 0 {
+                return Err(format!("invalid expression '{}'", text));
+            }
+            let value = self.term(rest[..end].trim())?;
+            total = if negate { total.wrapping_sub(value) } else { total.wrapping_add(value) };
+            negate = false;
+            rest = &rest[end..];
+            if rest.trim().is_empty() {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Evaluate a single expression term
+    fn term(&self, term: &str) -> Result<u32, String> {
+        if let Some(inner) = term.strip_prefix("%hi(").and_then(|t| t.strip_suffix(')')) {
+            return Ok(split_offset(self.expr(inner)?).0 >> 12);
+        }
+        if let Some(inner) = term.strip_prefix("%lo(").and_then(|t| t.strip_suffix(')')) {
+            return Ok(split_offset(self.expr(inner)?).1 as u32);
+        }
+        if term == "." {
+            return Ok(self.pc());
+        }
+        if let Some(value) = parse_number(term) {
+            return Ok(value);
+        }
+        if term.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.') {
+            return match self.labels.get(term) {
+                Some(value) => Ok(*value),
+                None if !self.emit => Ok(0),
+                None => Err(format!("undefined symbol '{}'", term)),
+            };
+        }
+        Err(format!("invalid term '{}'", term))
+    }
+
+    /// Whether every symbol in an expression is already known with its final value
+    fn resolves(&self, text: &str) -> bool {
+        let mut labels = self.labels.clone();
+        labels.retain(|name, _| !self.deferred.contains(name));
+        let strict = Assembler { labels, emit: true, ..Default::default() };
+        strict.expr(text).is_ok()
+    }
+
+    /// Signed immediate that must fit in `bits`
+    fn imm(&self, text: &str, bits: u32) -> Result<i32, String> {
+        let value = self.expr(text)? as i32;
+        if !fits_signed(value, bits) {
+            return Err(format!("immediate {} does not fit in {} bits", value, bits));
+        }
+        Ok(value)
+    }
+
+    /// 5-bit unsigned immediate used by the CSR immediate forms
+    fn uimm(&self, text: &str) -> Result<u8, String> {
+        let value = self.expr(text)?;
+        if value > 31 {
+            return Err(format!("immediate {} does not fit in 5 bits", value));
+        }
+        Ok(value as u8)
+    }
+
+    /// PC-relative offset to a target, checked against the encodable range
+    fn offset(&self, target: &str, bits: u32) -> Result<i32, String> {
+        let offset = self.expr(target)?.wrapping_sub(self.pc()) as i32;
+        if self.emit && (!fits_signed(offset, bits) || offset & 1 != 0) {
+            return Err(format!("target '{}' out of range", target));
+        }
+        Ok(offset)
+    }
+
+    /// Parse an `offset(base)` memory operand
+    fn mem_operand(&self, text: &str) -> Result<(i32, u8), String> {
+        let open = text.rfind('(').ok_or_else(|| format!("expected offset(base), found '{}'", text))?;
+        let base = text[open + 1..].strip_suffix(')').ok_or_else(|| format!("missing ')' in '{}'", text))?;
+        let offset = text[..open].trim();
+        let offset = if offset.is_empty() { 0 } else { self.imm(offset, 12)? };
+        Ok((offset, reg(base)?))
+    }
+}
+
+/// Split source into statements, stripping comments and collecting labels
+fn parse(source: &str) -> Result<Vec<Statement>, AsmError> {
+    let mut statements = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let mut text = strip_comment(line).trim();
+        let mut labels = Vec::new();
+        while let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+                break;
+            }
+            labels.push(label.to_string());
+            text = text[colon + 1..].trim();
+        }
+
+        let (op, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        statements.push(Statement {
+            line: i + 1,
+            labels,
+            op: op.to_lowercase(),
+            args: split_operands(rest),
+        });
+    }
+    Ok(statements)
+}
+
+/// Remove a `#` or `//` comment, ignoring markers inside string literals
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let bytes = line.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'"' if i == 0 || bytes[i - 1] != b'\\' => in_string = !in_string,
+            b'#' if !in_string => return &line[..i],
+            b'/' if !in_string && bytes.get(i + 1) == Some(&b'/') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Split operands on commas outside parentheses and string literals
+fn split_operands(text: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    for c in text.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => depth -= 1,
+            ',' if !in_string && depth == 0 => {
+                operands.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        operands.push(current.trim().to_string());
+    }
+    operands
+}
+
+/// Length of the expression term at the start of `text`
+fn term_end(text: &str) -> usize {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '+' | '-' if depth == 0 && i > 0 => return i,
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+fn arg(args: &[String], i: usize) -> Result<&str, String> {
+    args.get(i).map(String::as_str).ok_or_else(|| format!("missing operand {}", i + 1))
+}
+
+/// Parse a register as `xN` or by ABI name
+fn reg(text: &str) -> Result<u8, String> {
+    let text = text.trim();
+    if let Some(n) = text.strip_prefix('x').and_then(|n| n.parse::<u8>().ok()) && n < 32 {
+        return Ok(n);
+    }
+    if text == "fp" {
+        return Ok(8);
+    }
+    ABI_NAMES
+        .iter()
+        .position(|name| *name == text)
+        .map(|n| n as u8)
+        .ok_or_else(|| format!("invalid register '{}'", text))
+}
+
+/// Parse a CSR by name or number
+fn csr(text: &str) -> Result<i32, String> {
+    if let Some((number, _)) = CSR_NAMES.iter().find(|(_, name)| *name == text) {
+        return Ok(*number as i32);
+    }
+    match parse_number(text) {
+        Some(n) if n <= 0xFFF => Ok(n as i32),
+        _ => Err(format!("invalid CSR '{}'", text)),
+    }
+}
+
+/// Parse a decimal, `0x` hex, `0b` binary or `'c'` character literal
+fn parse_number(text: &str) -> Option<u32> {
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = text.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2).ok()
+    } else if let Some(c) = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        let bytes = parse_string(&format!("\"{}\"", c)).ok()?;
+        (bytes.len() == 1).then(|| bytes[0] as u32)
+    } else {
+        text.parse::<u32>().ok()
+    }
+}
+
+/// Parse a double-quoted string literal with C escapes
+fn parse_string(text: &str) -> Result<Vec<u8>, String> {
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .ok_or_else(|| format!("expected string literal, found '{}'", text))?;
+    let mut bytes = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next() {
+            Some('n') => b'\n',
+            Some('t') => b'\t',
+            Some('r') => b'\r',
+            Some('0') => 0,
+            Some(c @ ('\\' | '"' | '\'')) => c as u8,
+            other => return Err(format!("invalid escape '\\{}'", other.unwrap_or(' '))),
+        });
+    }
+    Ok(bytes)
+}
+
+fn fits_signed(value: i32, bits: u32) -> bool {
+    let limit = 1i64 << (bits - 1);
+    (-limit..limit).contains(&(value as i64))
+}
+
+/// Split a value into a `lui`/`auipc` upper field (already shifted) and a signed 12-bit low part
+fn split_offset(value: u32) -> (u32, i32) {
+    let hi = value.wrapping_add(0x800) & 0xFFFFF000;
+    let lo = value.wrapping_sub(hi) as i32;
+    (hi, lo)
+}
+
+fn r_type(op: &str) -> Option<(u32, u32)> {
+    Some(match op {
+        "add" => (0b000, 0),
+        "sub" => (0b000, 0b0100000),
+        "sll" => (0b001, 0),
+        "slt" => (0b010, 0),
+        "sltu" => (0b011, 0),
+        "xor" => (0b100, 0),
+        "srl" => (0b101, 0),
+        "sra" => (0b101, 0b0100000),
+        "or" => (0b110, 0),
+        "and" => (0b111, 0),
+        "mul" => (0b000, 0b0000001),
+        "mulh" => (0b001, 0b0000001),
+        "mulhsu" => (0b010, 0b0000001),
+        "mulhu" => (0b011, 0b0000001),
+        "div" => (0b100, 0b0000001),
+        "divu" => (0b101, 0b0000001),
+        "rem" => (0b110, 0b0000001),
+        "remu" => (0b111, 0b0000001),
+        _ => return None,
+    })
+}
+
+fn i_type(op: &str) -> Option<u32> {
+    Some(match op {
+        "addi" => 0b000,
+        "slti" => 0b010,
+        "sltiu" => 0b011,
+        "xori" => 0b100,
+        "ori" => 0b110,
+        "andi" => 0b111,
+        _ => return None,
+    })
+}
+
+fn shift_type(op: &str) -> Option<(u32, i32)> {
+    Some(match op {
+        "slli" => (0b001, 0),
+        "srli" => (0b101, 0),
+        "srai" => (0b101, 0b0100000),
+        _ => return None,
+    })
+}
+
+fn load_type(op: &str) -> Option<u32> {
+    Some(match op {
+        "lb" => 0b000,
+        "lh" => 0b001,
+        "lw" => 0b010,
+        "lbu" => 0b100,
+        "lhu" => 0b101,
+        _ => return None,
+    })
+}
+
+fn store_type(op: &str) -> Option<u32> {
+    Some(match op {
+        "sb" => 0b000,
+        "sh" => 0b001,
+        "sw" => 0b010,
+        _ => return None,
+    })
+}
+
+/// Branch funct3, and whether the operands are swapped (`bgt`, `ble`, ...)
+fn branch_type(op: &str) -> Option<(u32, bool)> {
+    Some(match op {
+        "beq" => (0b000, false),
+        "bne" => (0b001, false),
+        "blt" => (0b100, false),
+        "bge" => (0b101, false),
+        "bltu" => (0b110, false),
+        "bgeu" => (0b111, false),
+        "bgt" => (0b100, true),
+        "ble" => (0b101, true),
+        "bgtu" => (0b110, true),
+        "bleu" => (0b111, true),
+        _ => return None,
+    })
+}
+
+/// Branch-against-zero funct3, and whether zero is the second operand
+fn branch_zero_type(op: &str) -> Option<(u32, bool)> {
+    Some(match op {
+        "beqz" => (0b000, true),
+        "bnez" => (0b001, true),
+        "bltz" => (0b100, true),
+        "bgez" => (0b101, true),
+        "bgtz" => (0b100, false),
+        "blez" => (0b101, false),
+        _ => return None,
+    })
+}
+
+fn csr_type(op: &str) -> Option<u32> {
+    Some(match op {
+        "csrrw" => 0b001,
+        "csrrs" => 0b010,
+        "csrrc" => 0b011,
+        "csrrwi" => 0b101,
+        "csrrsi" => 0b110,
+        "csrrci" => 0b111,
+        _ => return None,
+    })
+}
+
+/// CSR pseudo-instructions that discard the old value
+fn csr_pseudo_type(op: &str) -> Option<u32> {
+    Some(match op {
+        "csrw" => 0b001,
+        "csrs" => 0b010,
+        "csrc" => 0b011,
+        "csrwi" => 0b101,
+        "csrsi" => 0b110,
+        "csrci" => 0b111,
+        _ => return None,
+    })
+}
+
+fn encode_r(opcode: u32, funct3: u32, funct7: u32, rd: u8, rs1: u8, rs2: u8) -> u32 {
+    opcode | ((rd as u32) << 7) | (funct3 << 12) | ((rs1 as u32) << 15) | ((rs2 as u32) << 20) | (funct7 << 25)
+}
+
+fn encode_i(opcode: u32, funct3: u32, rd: u8, rs1: u8, imm: i32) -> u32 {
+    opcode | ((rd as u32) << 7) | (funct3 << 12) | ((rs1 as u32) << 15) | (((imm as u32) & 0xFFF) << 20)
+}
+
+fn encode_s(funct3: u32, rs1: u8, rs2: u8, imm: i32) -> u32 {
+    let imm = imm as u32;
+    0b0100011 | ((imm & 0x1F) << 7) | (funct3 << 12) | ((rs1 as u32) << 15) | ((rs2 as u32) << 20) | (((imm >> 5) & 0x7F) << 25)
+}
+
+fn encode_b(funct3: u32, rs1: u8, rs2: u8, offset: i32) -> u32 {
+    let imm = offset as u32;
+    0b1100011
+        | (((imm >> 11) & 0x1) << 7)
+        | (((imm >> 1) & 0xF) << 8)
+        | (funct3 << 12)
+        | ((rs1 as u32) << 15)
+        | ((rs2 as u32) << 20)
+        | (((imm >> 5) & 0x3F) << 25)
+        | (((imm >> 12) & 0x1) << 31)
+}
+
+fn encode_j(rd: u8, offset: i32) -> u32 {
+    let imm = offset as u32;
+    0b1101111
+        | ((rd as u32) << 7)
+        | (((imm >> 12) & 0xFF) << 12)
+        | (((imm >> 11) & 0x1) << 20)
+        | (((imm >> 1) & 0x3FF) << 21)
+        | (((imm >> 20) & 0x1) << 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Words of the segment starting at `addr`
+    fn words_at(program: &Assembly, addr: u32) -> Vec<u32> {
+        let segment = program.segments.iter().find(|s| s.addr == addr).expect("segment at address");
+        segment.data.chunks(4).map(|w| u32::from_le_bytes(w.try_into().unwrap())).collect()
+    }
+
+    fn text(source: &str) -> Vec<u32> {
+        words_at(&assemble(source).unwrap(), ITCM_BASE)
+    }
+
+    #[test]
+    fn encodes_each_format() {
+        let cases = [
+            ("add x5, x1, x2", 0x002082b3),
+            ("sub a0, a1, a2", 0x40c58533),
+            ("mul a0, a1, a2", 0x02c58533),
+            ("addi a0, a0, -1", 0xfff50513),
+            ("slli a0, a0, 3", 0x00351513),
+            ("srai a0, a0, 3", 0x40355513),
+            ("lw a0, 4(sp)", 0x00412503),
+            ("lbu t0, -1(a0)", 0xfff54283),
+            ("sw a0, 8(sp)", 0x00a12423),
+            ("sb t0, -1(a0)", 0xfe550fa3),
+            ("lui a0, 0x12345", 0x12345537),
+            ("auipc t0, 1", 0x00001297),
+            ("jalr ra, 4(t0)", 0x004280e7),
+            ("csrrw t0, mscratch, t1", 0x340312f3),
+            ("csrrsi x0, mstatus, 8", 0x30046073),
+            ("csrr a0, mcause", 0x34202573),
+            ("ecall", 0x00000073),
+            ("ebreak", 0x00100073),
+            ("mret", 0x30200073),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(text(source), [expected], "{}", source);
+        }
+    }
+
+    #[test]
+    fn resolves_backward_and_forward_labels() {
+        let words = text(
+            "loop: addi a0, a0, -1
+                   bnez a0, loop
+                   beqz a1, done
+                   jal  ra, done
+             done: j    loop",
+        );
+        assert_eq!(words, [0xfff50513, 0xfe051ee3, 0x00058463, 0x004000ef, 0xff1ff06f]);
+    }
+
+    #[test]
+    fn places_org_word_and_equ() {
+        let program = assemble(
+            "       .equ   COUNT, 3
+                    .org   0x100
+             start: li     a0, COUNT
+                    .data
+             table: .word  0x11223344, COUNT + 1, table
+                    .byte  1, 2",
+        )
+        .unwrap();
+        assert_eq!(words_at(&program, 0x100), [0x00300513]);
+        let data = &program.segments.iter().find(|s| s.addr == DTCM_BASE).unwrap().data;
+        assert_eq!(data[..12], [0x44, 0x33, 0x22, 0x11, 4, 0, 0, 0, 0x00, 0x00, 0x01, 0x00]);
+        assert_eq!(data[12..], [1, 2]);
+        assert_eq!(program.symbols.get("start").unwrap().addr, 0x100);
+        assert_eq!(program.symbols.get("table").unwrap().addr, DTCM_BASE);
+    }
+
+    #[test]
+    fn expands_li() {
+        assert_eq!(text("li a0, -5"), [0xffb00513]);
+        assert_eq!(text("li a0, 0x12345678"), [0x12345537, 0x67850513]);
+        // Low part sign-extends, so the upper part rounds up
+        assert_eq!(text("li a0, 0x12345fff"), [0x12346537, 0xfff50513]);
+    }
+
+    #[test]
+    fn li_of_symbol_defined_from_later_label_keeps_full_value() {
+        let words = text(
+            "      .equ  END, end
+                   li    a0, END
+                   .org  0x1000
+             end:  nop",
+        );
+        assert_eq!(words[..2], [0x00001537, 0x00050513]);
+    }
+
+    #[test]
+    fn expands_la_call_and_ret() {
+        let words = text(
+            "      la   a0, value
+                   call func
+                   ret
+             func: ret
+                   .data
+             value: .word 0",
+        );
+        // `value` is at DTCM_BASE, 0x10000 above `la`
+        assert_eq!(words[..2], [0x00010517, 0x00050513]);
+        // `call` at 8 reaches `func` at 20
+        assert_eq!(words[2..4], [0x00000097, 0x00c080e7]);
+        assert_eq!(words[4..], [0x00008067, 0x00008067]);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(assemble(".p2align 32").is_err());
+        assert!(assemble("addi a0, a0, 4096").is_err());
+        assert!(assemble("j nowhere").is_err());
+        assert_eq!(assemble("nop\nfoo a0").err().map(|e| e.line), Some(2));
+    }
+}
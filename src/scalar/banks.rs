@@ -0,0 +1,235 @@
+use std::collections::VecDeque;
+
+/// Number of agents contending for the DTCM banks
+pub const REQUESTORS: usize = 3;
+
+/// Agent accessing the DTCM
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Requestor {
+    ScalarLsu,
+    VectorLsu,
+    Dma,
+}
+
+impl Requestor {
+    /// All requestors, in their default priority order
+    pub const ALL: [Requestor; REQUESTORS] = [Requestor::ScalarLsu, Requestor::VectorLsu, Requestor::Dma];
+
+    /// Short name used in statistics
+    pub fn name(&self) -> &'static str {
+        match self {
+            Requestor::ScalarLsu => "lsu",
+            Requestor::VectorLsu => "vlsu",
+            Requestor::Dma => "dma",
+        }
+    }
+}
+
+/// How the arbiter orders requestors competing for a bank port
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Arbitration {
+    /// The requestor after the one granted first in the previous contended cycle goes first
+    RoundRobin,
+    /// Requestors go in the given order, highest priority first
+    Priority([Requestor; REQUESTORS]),
+}
+
+/// Geometry and arbitration of the DTCM banks
+#[derive(Copy, Clone, Debug)]
+pub struct BankConfig {
+    /// Number of word-interleaved banks, at most 64
+    pub banks: u32,
+    /// Bytes per bank word
+    pub width: u32,
+    /// Accesses each bank serves per cycle, 1 for single-port and 2 for dual-port banks
+    pub ports: u8,
+    pub arbitration: Arbitration,
+}
+
+impl Default for BankConfig {
+    fn default() -> Self {
+        Self { banks: 8, width: 4, ports: 1, arbitration: Arbitration::RoundRobin }
+    }
+}
+
+impl BankConfig {
+    /// Bank holding the word at `addr`
+    pub fn bank(&self, addr: u32) -> usize {
+        ((addr / self.width) % self.banks) as usize
+    }
+
+    /// Row of its bank holding the word at `addr`
+    pub fn row(&self, addr: u32) -> u32 {
+        addr / (self.width * self.banks)
+    }
+}
+
+/// Bank access counts, indexed by requestor and by bank
+#[derive(Clone, Debug, Default)]
+pub struct BankStats {
+    /// Beats granted per requestor
+    pub beats: [u64; REQUESTORS],
+    /// Cycles a requestor's next beat lost arbitration for a bank port
+    pub conflict_cycles: [u64; REQUESTORS],
+    /// Cycles a beat waited for each bank
+    pub bank_conflicts: Vec<u64>,
+}
+
+/// Per-cycle arbiter for the DTCM bank ports.
+///
+/// Each requestor has one port into the DTCM and queues its accesses as beats, the
+/// set of banks a beat reads or writes. Once per cycle, the arbiter walks the
+/// requestors in arbitration order and grants each its oldest beat if every bank of
+/// the beat has a port left this cycle. A beat that loses waits for the next cycle,
+/// holding back the requestor's younger beats. Requestors execute accesses
+/// functionally when they queue them and use tickets to wait for the grants.
+pub struct BankArbiter {
+    pub config: BankConfig,
+    pub stats: BankStats,
+    /// Beats waiting for a grant per requestor, oldest first, as bank masks
+    queues: [VecDeque<u64>; REQUESTORS],
+    /// Beats requested and granted so far per requestor
+    requested: [u64; REQUESTORS],
+    granted: [u64; REQUESTORS],
+    /// Requestor first in round-robin order next cycle
+    next: usize,
+}
+
+impl BankArbiter {
+    pub fn new(config: BankConfig) -> Self {
+        Self {
+            config,
+            stats: BankStats::default(),
+            queues: Default::default(),
+            requested: [0; REQUESTORS],
+            granted: [0; REQUESTORS],
+            next: 0,
+        }
+    }
+
+    /// Queue beats for `requestor`, returning the ticket that is granted with the last of them
+    pub fn request(&mut self, requestor: Requestor, beats: Vec<u64>) -> u64 {
+        let index = requestor as usize;
+        self.requested[index] += beats.len() as u64;
+        self.queues[index].extend(beats);
+        self.requested[index]
+    }
+
+    /// Whether every beat up to `ticket` has been granted
+    pub fn is_granted(&self, requestor: Requestor, ticket: u64) -> bool {
+        self.granted[requestor as usize] >= ticket
+    }
+
+    /// Beats `requestor` has waiting
+    pub fn pending(&self, requestor: Requestor) -> usize {
+        self.queues[requestor as usize].len()
+    }
+
+    /// Arbitrate the bank ports for one cycle
+    pub fn tick(&mut self) {
+        let banks = self.config.banks as usize;
+        self.stats.bank_conflicts.resize(banks, 0);
+        let order = match self.config.arbitration {
+            Arbitration::RoundRobin => std::array::from_fn(|i| Requestor::ALL[(self.next + i) % REQUESTORS]),
+            Arbitration::Priority(order) => order,
+        };
+
+        let mut used = vec![0u8; banks];
+        let mut first_granted = None;
+        let mut contended = false;
+        for requestor in order {
+            let index = requestor as usize;
+            let Some(&beat) = self.queues[index].front() else {
+                continue;
+            };
+            let busy = (0..banks).filter(|&bank| beat & (1 << bank) != 0 && used[bank] >= self.config.ports);
+            let mut lost = false;
+            for bank in busy {
+                self.stats.bank_conflicts[bank] += 1;
+                lost = true;
+            }
+            if lost {
+                self.stats.conflict_cycles[index] += 1;
+                contended = true;
+                continue;
+            }
+            for (bank, count) in used.iter_mut().enumerate() {
+                *count += (beat >> bank & 1) as u8;
+            }
+            self.queues[index].pop_front();
+            self.granted[index] += 1;
+            self.stats.beats[index] += 1;
+            first_granted.get_or_insert(index);
+        }
+
+        if contended && let Some(index) = first_granted {
+            self.next = (index + 1) % REQUESTORS;
+        }
+    }
+}
+
+impl Default for BankArbiter {
+    fn default() -> Self {
+        Self::new(BankConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Requestor::{Dma, ScalarLsu, VectorLsu};
+
+    /// Queue the beats and tick until all are granted, returning who was granted each cycle
+    fn arbitrate(config: BankConfig, requests: &[(Requestor, &[u64])]) -> (Vec<Vec<Requestor>>, BankStats) {
+        let mut arbiter = BankArbiter::new(config);
+        for &(requestor, beats) in requests {
+            arbiter.request(requestor, beats.to_vec());
+        }
+        let mut cycles = Vec::new();
+        while Requestor::ALL.iter().any(|&r| arbiter.pending(r) > 0) {
+            assert!(cycles.len() < 100, "beats never granted");
+            let before = arbiter.stats.beats;
+            arbiter.tick();
+            cycles.push(Requestor::ALL.into_iter().filter(|&r| arbiter.stats.beats[r as usize] > before[r as usize]).collect());
+        }
+        (cycles, arbiter.stats)
+    }
+
+    #[test]
+    fn same_bank_grants_follow_the_arbitration_mode() {
+        let round_robin = BankConfig::default();
+        let priority = BankConfig { arbitration: Arbitration::Priority([VectorLsu, ScalarLsu, Dma]), ..BankConfig::default() };
+        let cases = [
+            // After a contended cycle the requestor after the first granted goes first
+            (round_robin, vec![vec![ScalarLsu], vec![VectorLsu], vec![ScalarLsu], vec![VectorLsu]], [1, 2, 0]),
+            (priority, vec![vec![VectorLsu], vec![VectorLsu], vec![ScalarLsu], vec![ScalarLsu]], [2, 0, 0]),
+        ];
+        for (config, grants, conflicts) in cases {
+            let (cycles, stats) = arbitrate(config, &[(ScalarLsu, &[1, 1]), (VectorLsu, &[1, 1])]);
+            assert_eq!(cycles, grants, "{:?}", config.arbitration);
+            assert_eq!(stats.conflict_cycles, conflicts, "{:?}", config.arbitration);
+            assert_eq!(stats.bank_conflicts[0], conflicts.iter().sum::<u64>(), "{:?}", config.arbitration);
+            assert_eq!(stats.beats, [2, 2, 0], "{:?}", config.arbitration);
+        }
+    }
+
+    #[test]
+    fn different_banks_never_conflict() {
+        let priority = BankConfig { arbitration: Arbitration::Priority([Dma, VectorLsu, ScalarLsu]), ..BankConfig::default() };
+        for config in [BankConfig::default(), priority] {
+            let (cycles, stats) = arbitrate(config, &[(ScalarLsu, &[0b01, 0b01]), (VectorLsu, &[0b10, 0b10])]);
+            assert_eq!(cycles, vec![vec![ScalarLsu, VectorLsu]; 2], "{:?}", config.arbitration);
+            assert_eq!(stats.conflict_cycles, [0; REQUESTORS], "{:?}", config.arbitration);
+            assert!(stats.bank_conflicts.iter().all(|&count| count == 0), "{:?}", config.arbitration);
+        }
+    }
+
+    #[test]
+    fn dual_port_banks_serve_two_requestors_per_cycle() {
+        let config = BankConfig { ports: 2, arbitration: Arbitration::Priority(Requestor::ALL), ..BankConfig::default() };
+        let (cycles, stats) = arbitrate(config, &[(ScalarLsu, &[1]), (VectorLsu, &[0b11]), (Dma, &[1])]);
+        assert_eq!(cycles, vec![vec![ScalarLsu, VectorLsu], vec![Dma]]);
+        assert_eq!(stats.conflict_cycles, [0, 0, 1]);
+        assert_eq!(stats.bank_conflicts[..2], [1, 0]);
+    }
+}
@@ -1,8 +1,22 @@
+use std::io::{self, ErrorKind};
+use tracing::debug;
+use crate::common::axi::ExternalMemory;
+use crate::common::elf::{Segment, SymbolTable};
+use crate::devices::clint::{Clint, CLINT_BASE, CLINT_SIZE};
+use crate::devices::dma::{Dma, DMA_BASE, DMA_SIZE};
+use crate::devices::mmio::{DeviceMemory, Mmio};
+use crate::devices::uart::{Uart, UART_BASE, UART_SIZE};
+use crate::scalar::csr::CsrFile;
+use crate::scalar::custom::Extensions;
 use crate::scalar::decode::DecodeStage;
-use crate::scalar::dispatch::DispatchStage;
+use crate::scalar::dispatch::{DispatchStage, Halt};
 use crate::scalar::fetch::FetchStage;
+use crate::scalar::htif::Htif;
+use crate::scalar::icache::ICache;
 use crate::scalar::instruction::InstructionBuffer;
-use crate::scalar::memory::Itcm;
+use crate::scalar::memory::{DataBus, Dtcm, Itcm};
+use crate::scalar::regfile::RegisterFile;
+use crate::scalar::semihost::Semihost;
 
 /// The ScalarFrontend struct encapsulates the fetch, decode, and dispatch stages
 pub struct ScalarFrontend {
@@ -10,7 +24,23 @@ pub struct ScalarFrontend {
     pub decode: DecodeStage,
     pub dispatch: DispatchStage,
     pub instr_buffer: InstructionBuffer,
-    pub itcm: Itcm
+    pub itcm: Itcm,
+    /// Instruction cache for code fetched from the DRAM
+    pub icache: ICache,
+    pub dtcm: Dtcm,
+    /// External DRAM behind an AXI port, reached by loads and stores and by the DMA engine
+    pub dram: ExternalMemory,
+    /// Memory-mapped devices, a UART on stdout, a CLINT and a DMA engine by default
+    pub mmio: Mmio,
+    pub regs: RegisterFile,
+    pub csrs: CsrFile,
+    /// Host interface the program signals exit through, if it defines `tohost`
+    pub htif: Option<Htif>,
+    pub semihost: Semihost,
+    /// Extensions decoding and executing custom-opcode instructions
+    pub extensions: Extensions,
+    /// Number of elapsed cycles
+    pub cycle: u64,
 }
 
 impl ScalarFrontend {
@@ -18,6 +48,11 @@ impl ScalarFrontend {
     pub fn new() -> Self {
         let instr_buffer = InstructionBuffer::new(4);
         let itcm = Itcm::new(1);
+        let dtcm = Dtcm::new(1);
+        let mut mmio = Mmio::default();
+        mmio.attach(UART_BASE, UART_SIZE, Box::new(Uart::default()));
+        mmio.attach(CLINT_BASE, CLINT_SIZE, Box::new(Clint::default()));
+        mmio.attach(DMA_BASE, DMA_SIZE, Box::new(Dma::default()));
         let fetch = FetchStage::new();
         let decode = DecodeStage::new();
         let dispatch = DispatchStage::new();
@@ -27,19 +62,201 @@ impl ScalarFrontend {
             dispatch,
             instr_buffer,
             itcm,
+            icache: ICache::default(),
+            dtcm,
+            dram: ExternalMemory::default(),
+            mmio,
+            regs: RegisterFile::default(),
+            csrs: CsrFile::default(),
+            htif: None,
+            semihost: Semihost::default(),
+            extensions: Extensions::default(),
+            cycle: 0,
         }
     }
 
     /// Advances the frontend by one tick, processing fetch, decode, and dispatch stages
     pub fn tick(&mut self) {
-        self.fetch.tick(&mut self.instr_buffer, &mut self.itcm);
+        debug!("===== Cycle {} =====", self.cycle);
+        self.fetch.tick(&mut self.instr_buffer, &mut self.itcm, &mut self.icache);
+        self.decode.tick(&mut self.instr_buffer, &mut self.dispatch.queue, &self.extensions);
 
-        let batch = self.instr_buffer.pop_batch(4);
-        if !batch.is_empty() {
-            self.decode.accept_batch(batch);
+        let mut bus = DataBus::new(&mut self.itcm, &mut self.dtcm, &mut self.dram, &mut self.mmio);
+        if let Some(target) = self.dispatch.tick(&mut self.regs, &mut self.csrs, &mut bus, &mut self.semihost, &mut self.extensions) {
+            self.fetch.redirect(target, bus.itcm);
+            self.decode.flush();
+            self.instr_buffer.flush();
         }
+        if std::mem::take(&mut self.dispatch.fence_i) {
+            self.icache.invalidate();
+        }
+        if let Some(htif) = &self.htif
+            && self.dispatch.halt.is_none()
+            && let Some(code) = htif.poll(&mut bus)
+        {
+            debug!("Program signalled exit through tohost with code {}", code);
+            self.dispatch.halt = Some(Halt::Exit(code));
+            self.dispatch.queue.inner.clear();
+        }
+        self.mmio.tick(&mut DeviceMemory { dtcm: &mut self.dtcm, dram: &mut self.dram });
+        self.itcm.tick();
+        self.dtcm.arbiter.tick();
+        self.icache.tick(&mut self.dram);
+        self.dram.tick();
+        self.csrs.mip = self.mmio.interrupts();
+        self.cycle += 1;
+        self.csrs.cycle += 1;
+    }
+
+    /// Why the core stopped, if it has
+    pub fn halted(&self) -> Option<Halt> {
+        self.dispatch.halt
+    }
+
+    /// Clear a breakpoint or exception halt so execution can continue.
+    /// A program that has exited stays halted.
+    pub fn resume(&mut self) {
+        if !matches!(self.dispatch.halt, Some(Halt::Exit(_))) {
+            self.dispatch.halt = None;
+        }
+    }
+
+    /// Tick until the program halts or `max_cycles` elapse, then drain in-flight instructions.
+    /// Returns the halt reason, or `None` on timeout.
+    pub fn run(&mut self, max_cycles: u64) -> Option<Halt> {
+        let start = self.cycle;
+        while self.dispatch.halt.is_none() && self.cycle - start < max_cycles {
+            self.tick();
+        }
+        self.drain();
+        self.dispatch.halt
+    }
+
+    /// PC of the next instruction to execute
+    pub fn pc(&self) -> u32 {
+        self.dispatch.pc
+    }
+
+    /// Discard all in-flight frontend state and continue execution at `pc`
+    pub fn redirect(&mut self, pc: u32) {
+        self.fetch.redirect(pc, &mut self.itcm);
+        self.decode.flush();
+        self.instr_buffer.flush();
+        self.dispatch.queue.inner.clear();
+        self.dispatch.pc = pc;
+    }
+
+    /// Copy image segments into memory, zero-filling each up to its memory size
+    pub fn load(&mut self, segments: &[Segment]) -> io::Result<()> {
+        let mut bus = self.data_bus();
+        for segment in segments {
+            for offset in 0..segment.mem_size {
+                let addr = segment.addr.wrapping_add(offset);
+                let byte = segment.data.get(offset as usize).copied().unwrap_or(0);
+                let in_memory = bus.itcm.contains(addr) || bus.dtcm.contains(addr) || bus.dram.contains(addr);
+                if !in_memory || !bus.store(addr, 1, byte as u32) {
+                    let message = format!("segment byte at 0x{:08x} is outside ITCM, DTCM and DRAM", addr);
+                    return Err(io::Error::new(ErrorKind::InvalidInput, message));
+                }
+            }
+        }
+        // Drop instructions fetched before the image was written
+        let pc = self.pc();
+        self.redirect(pc);
+        Ok(())
+    }
+
+    /// Load a program image, start execution at `entry` and attach the host
+    /// interface if the program defines a `tohost` symbol
+    pub fn load_program(&mut self, segments: &[Segment], entry: u32, symbols: &SymbolTable) -> io::Result<()> {
+        self.load(segments)?;
+        self.redirect(entry);
+        self.htif = Htif::from_symbols(symbols);
+        Ok(())
+    }
 
-        self.decode.tick(&mut self.instr_buffer, &mut self.dispatch.queue);
-        self.dispatch.tick();
+    /// Data-side view of memory for debug accesses
+    pub fn data_bus(&mut self) -> DataBus<'_> {
+        DataBus::new(&mut self.itcm, &mut self.dtcm, &mut self.dram, &mut self.mmio)
     }
-}
\ No newline at end of file
+
+    /// Stop issuing and tick until every executing instruction has written back
+    pub fn drain(&mut self) {
+        let budget = self.dispatch.control.budget.replace(0);
+        while !self.dispatch.is_idle() {
+            self.tick();
+        }
+        self.dispatch.control.budget = budget;
+    }
+
+    /// Execute exactly one instruction, giving up after `max_cycles` if it cannot issue.
+    /// Returns true if the instruction retired.
+    pub fn step_instruction(&mut self, max_cycles: u64) -> bool {
+        self.dispatch.control.budget = Some(1);
+        self.dispatch.control.resume_pc = Some(self.pc());
+        let start = self.cycle;
+        while self.dispatch.control.budget != Some(0) && self.cycle - start < max_cycles {
+            self.tick();
+        }
+        let stepped = self.dispatch.control.budget == Some(0);
+        self.drain();
+        self.dispatch.control.budget = None;
+        self.dispatch.control.resume_pc = None;
+        stepped
+    }
+}
+
+impl Default for ScalarFrontend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::asm::assemble;
+    use crate::scalar::memory::ITCM_BASE;
+
+    /// Assemble and run a program, returning its exit code
+    fn run(source: &str) -> Option<u32> {
+        let program = assemble(source).expect("program assembles");
+        let mut core = ScalarFrontend::new();
+        core.load_program(&program.segments, ITCM_BASE, &program.symbols).unwrap();
+        match core.run(10_000) {
+            Some(Halt::Exit(code)) => Some(code),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn vector_load_sees_older_uncached_scalar_store() {
+        let code = run(
+            "li    a0, 0x80000000
+             li    t0, 1
+             .word 0xc100f057 # vsetivli zero, 1, e32, m1, tu, mu
+             sw    t0, 0(a0)
+             .word 0x02056087 # vle32.v v1, (a0)
+             .word 0x42102357 # vmv.x.s t1, v1
+             mv    a0, t1
+             ecall",
+        );
+        assert_eq!(code, Some(1));
+    }
+
+    #[test]
+    fn vector_store_lands_after_older_uncached_scalar_store() {
+        let code = run(
+            "li    a0, 0x80000000
+             li    t0, 1
+             li    t2, 2
+             .word 0xc100f057 # vsetivli zero, 1, e32, m1, tu, mu
+             .word 0x5e03c0d7 # vmv.v.x v1, t2
+             sw    t0, 0(a0)
+             .word 0x020560a7 # vse32.v v1, (a0)
+             lw    a0, 0(a0)
+             ecall",
+        );
+        assert_eq!(code, Some(2));
+    }
+}
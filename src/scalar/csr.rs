@@ -0,0 +1,179 @@
+use crate::vector::config::VectorConfig;
+
+/// Exception cause codes written to `mcause`
+pub const CAUSE_MISALIGNED_FETCH: u32 = 0;
+pub const CAUSE_ILLEGAL_INSTRUCTION: u32 = 2;
+pub const CAUSE_BREAKPOINT: u32 = 3;
+pub const CAUSE_LOAD_ACCESS_FAULT: u32 = 5;
+pub const CAUSE_STORE_ACCESS_FAULT: u32 = 7;
+pub const CAUSE_ECALL_M: u32 = 11;
+/// `mcause` bit set for interrupts
+pub const CAUSE_INTERRUPT: u32 = 1 << 31;
+
+/// Machine interrupt numbers, in decreasing priority
+pub const IRQ_M_EXTERNAL: u32 = 11;
+pub const IRQ_M_SOFT: u32 = 3;
+pub const IRQ_M_TIMER: u32 = 7;
+
+/// `mip`/`mie` bits of the machine interrupts
+pub const MIP_MSIP: u32 = 1 << IRQ_M_SOFT;
+pub const MIP_MTIP: u32 = 1 << IRQ_M_TIMER;
+pub const MIP_MEIP: u32 = 1 << IRQ_M_EXTERNAL;
+
+/// `mstatus` machine interrupt enable
+pub const MSTATUS_MIE: u32 = 1 << 3;
+/// `mstatus` previous machine interrupt enable
+pub const MSTATUS_MPIE: u32 = 1 << 7;
+/// `mstatus` previous privilege, hardwired to machine mode
+const MSTATUS_MPP: u32 = 0b11 << 11;
+/// `mstatus` vector context status, hardwired to initial so vector instructions are always enabled
+const MSTATUS_VS: u32 = 0b01 << 9;
+/// `mstatus` floating-point context status, hardwired to initial so `fcsr` is always accessible
+const MSTATUS_FS: u32 = 0b01 << 13;
+
+/// `misa` value: RV32IMV
+const MISA: u32 = (1 << 30) | (1 << 21) | (1 << 12) | (1 << 8);
+
+/// A synchronous exception raised by an instruction
+#[derive(Copy, Clone, Debug)]
+pub struct Trap {
+    pub cause: u32,
+    pub tval: u32,
+}
+
+/// Floating-point rounding mode and accrued exception flags, shared by every
+/// floating-point unit
+#[derive(Copy, Clone, Default)]
+pub struct FloatCsr {
+    /// Dynamic rounding mode
+    pub frm: u32,
+    /// Accrued exception flags
+    pub fflags: u32,
+}
+
+/// Machine-mode control and status registers
+#[derive(Default)]
+pub struct CsrFile {
+    pub mstatus: u32,
+    pub mie: u32,
+    pub mip: u32,
+    pub mtvec: u32,
+    pub mscratch: u32,
+    pub mepc: u32,
+    pub mcause: u32,
+    pub mtval: u32,
+    /// Elapsed cycles, backing `mcycle`/`cycle`
+    pub cycle: u64,
+    /// Retired instructions, backing `minstret`/`instret`
+    pub instret: u64,
+    /// Vector configuration and fixed-point state
+    pub vector: VectorConfig,
+    /// Backing `fflags`, `frm` and `fcsr`
+    pub fcsr: FloatCsr,
+}
+
+impl CsrFile {
+    /// Read a CSR, or `None` if it does not exist
+    pub fn read(&self, csr: u16) -> Option<u32> {
+        let value = match csr {
+            0x001 => self.fcsr.fflags,
+            0x002 => self.fcsr.frm,
+            0x003 => (self.fcsr.frm << 5) | self.fcsr.fflags,
+            0x008 => self.vector.vstart,
+            0x009 => self.vector.vxsat as u32,
+            0x00A => self.vector.vxrm,
+            0x00F => (self.vector.vxrm << 1) | self.vector.vxsat as u32,
+            0x300 => self.mstatus | MSTATUS_FS | MSTATUS_VS | MSTATUS_MPP,
+            0x301 => MISA,
+            0x304 => self.mie,
+            0x305 => self.mtvec,
+            0x340 => self.mscratch,
+            0x341 => self.mepc,
+            0x342 => self.mcause,
+            0x343 => self.mtval,
+            0x344 => self.mip,
+            0xB00 | 0xC00 | 0xC01 => self.cycle as u32,
+            0xB02 | 0xC02 => self.instret as u32,
+            0xB80 | 0xC80 | 0xC81 => (self.cycle >> 32) as u32,
+            0xB82 | 0xC82 => (self.instret >> 32) as u32,
+            0xC20 => self.vector.vl,
+            0xC21 => self.vector.vtype,
+            0xC22 => self.vector.vlenb(),
+            0xF11..=0xF14 => 0,
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Write a CSR, returns false if it does not exist or is read-only
+    pub fn write(&mut self, csr: u16, value: u32) -> bool {
+        match csr {
+            0x001 => self.fcsr.fflags = value & 0x1F,
+            0x002 => self.fcsr.frm = value & 0b111,
+            0x003 => {
+                self.fcsr.frm = (value >> 5) & 0b111;
+                self.fcsr.fflags = value & 0x1F;
+            }
+            0x008 => self.vector.vstart = value & (self.vector.vlen - 1),
+            0x009 => self.vector.vxsat = value & 1 != 0,
+            0x00A => self.vector.vxrm = value & 0b11,
+            0x00F => {
+                self.vector.vxrm = (value >> 1) & 0b11;
+                self.vector.vxsat = value & 1 != 0;
+            }
+            0x300 => self.mstatus = value & (MSTATUS_MIE | MSTATUS_MPIE),
+            0x301 => {} // WARL, writes ignored
+            0x304 => self.mie = value & (MIP_MSIP | MIP_MTIP | MIP_MEIP),
+            0x305 => self.mtvec = value & !0b10, // direct or vectored mode
+            0x340 => self.mscratch = value,
+            0x341 => self.mepc = value & !3, // IALIGN is 32 without the C extension
+            0x342 => self.mcause = value,
+            0x343 => self.mtval = value,
+            0x344 => {} // Pending bits follow the interrupt lines
+            0xB00 => self.cycle = (self.cycle & !0xFFFF_FFFF) | value as u64,
+            0xB02 => self.instret = (self.instret & !0xFFFF_FFFF) | value as u64,
+            0xB80 => self.cycle = (self.cycle & 0xFFFF_FFFF) | (value as u64) << 32,
+            0xB82 => self.instret = (self.instret & 0xFFFF_FFFF) | (value as u64) << 32,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Whether a trap handler has been installed. `mtvec` resets to zero, which is
+    /// the reset vector, so a zero `mtvec` means the program has no handler.
+    pub fn has_trap_handler(&self) -> bool {
+        self.mtvec != 0
+    }
+
+    /// Highest-priority interrupt that is pending, enabled in `mie` and globally enabled
+    pub fn pending_interrupt(&self) -> Option<u32> {
+        if self.mstatus & MSTATUS_MIE == 0 {
+            return None;
+        }
+        let pending = self.mip & self.mie;
+        [IRQ_M_EXTERNAL, IRQ_M_SOFT, IRQ_M_TIMER].into_iter().find(|irq| pending & (1 << irq) != 0)
+    }
+
+    /// Enter a machine-mode trap taken at `pc`, returning the handler address.
+    /// Interrupts jump to `base + 4 * cause` when `mtvec` selects vectored mode.
+    pub fn enter_trap(&mut self, trap: Trap, pc: u32) -> u32 {
+        self.mepc = pc;
+        self.mcause = trap.cause;
+        self.mtval = trap.tval;
+        let mpie = if self.mstatus & MSTATUS_MIE != 0 { MSTATUS_MPIE } else { 0 };
+        self.mstatus = (self.mstatus & !(MSTATUS_MIE | MSTATUS_MPIE)) | mpie;
+        let base = self.mtvec & !0b11;
+        if self.mtvec & 0b11 == 1 && trap.cause & CAUSE_INTERRUPT != 0 {
+            base.wrapping_add(4 * (trap.cause & !CAUSE_INTERRUPT))
+        } else {
+            base
+        }
+    }
+
+    /// Return from a machine-mode trap, returning the resume address
+    pub fn mret(&mut self) -> u32 {
+        let mie = if self.mstatus & MSTATUS_MPIE != 0 { MSTATUS_MIE } else { 0 };
+        self.mstatus = (self.mstatus & !MSTATUS_MIE) | mie | MSTATUS_MPIE;
+        self.mepc
+    }
+}
@@ -0,0 +1,211 @@
+use crate::scalar::csr::{CsrFile, Trap};
+use crate::scalar::instruction::{Instruction, RawInstruction};
+use crate::scalar::memory::DataBus;
+use crate::vector::regfile::VectorRegisterFile;
+
+/// Major opcodes reserved for custom extensions
+pub const CUSTOM_0: u8 = 0b0001011;
+pub const CUSTOM_1: u8 = 0b0101011;
+pub const CUSTOM_2: u8 = 0b1011011;
+pub const CUSTOM_3: u8 = 0b1111011;
+
+/// Scalar unit a custom instruction occupies while it executes
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CustomUnit {
+    /// One of the scalar ALUs
+    Alu,
+    /// The scalar LSU, ordered with scalar and vector loads and stores
+    Lsu,
+    /// A unit of the extension's own that executes one instruction at a time
+    Dedicated,
+}
+
+/// How the pipeline schedules a custom instruction, cached in the decoded instruction
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CustomInfo {
+    pub mnemonic: &'static str,
+    pub unit: CustomUnit,
+    /// Cycles from issue until the result is written back
+    pub latency: u8,
+    pub reads_rs1: bool,
+    pub reads_rs2: bool,
+    pub writes_rd: bool,
+    /// Whether it accesses the vector registers, which makes it wait for the vector
+    /// and matrix units to drain
+    pub uses_vregs: bool,
+    /// Index of the extension that decoded the instruction, set on registration
+    pub extension: u8,
+}
+
+impl CustomInfo {
+    /// Info for a single-cycle instruction in `unit` that uses no scalar registers
+    pub fn new(mnemonic: &'static str, unit: CustomUnit) -> Self {
+        Self { mnemonic, unit, latency: 1, reads_rs1: false, reads_rs2: false, writes_rd: false, uses_vregs: false, extension: 0 }
+    }
+}
+
+/// A custom instruction decoded by an extension into its own operation type
+pub struct Decoded<Op> {
+    pub op: Op,
+    pub info: CustomInfo,
+}
+
+/// State a custom instruction can use when it executes
+pub struct CustomContext<'a, 'b> {
+    pub instr: &'a Instruction,
+    /// `x[rs1]` and `x[rs2]`, read at issue
+    pub rs1: u32,
+    pub rs2: u32,
+    pub csrs: &'a mut CsrFile,
+    pub bus: &'a mut DataBus<'b>,
+    pub vregs: &'a mut VectorRegisterFile,
+}
+
+/// An ISA extension in the custom-0 to custom-3 opcode spaces.
+///
+/// `decode` must be a pure function of the instruction bits: it runs when the
+/// instruction is decoded and again when it issues, to hand `execute` the typed
+/// operation. `execute` runs at issue, like the built-in units, and returns the value
+/// written to `rd` when the instruction completes `latency` cycles later. A trap it
+/// returns is raised precisely by the instruction.
+pub trait Extension {
+    /// Decoded form of the extension's instructions
+    type Op;
+
+    /// Decode an instruction, `None` if it does not belong to the extension
+    fn decode(&self, instr: &Instruction) -> Option<Decoded<Self::Op>>;
+
+    /// Execute a decoded operation
+    fn execute(&mut self, op: Self::Op, ctx: &mut CustomContext) -> Result<u32, Trap>;
+}
+
+/// Object-safe view of an `Extension` with its operation type erased
+trait AnyExtension {
+    fn info(&self, instr: &Instruction) -> Option<CustomInfo>;
+    fn execute(&mut self, ctx: &mut CustomContext) -> Option<Result<u32, Trap>>;
+}
+
+impl<E: Extension> AnyExtension for E {
+    fn info(&self, instr: &Instruction) -> Option<CustomInfo> {
+        self.decode(instr).map(|decoded| decoded.info)
+    }
+
+    fn execute(&mut self, ctx: &mut CustomContext) -> Option<Result<u32, Trap>> {
+        let decoded = self.decode(ctx.instr)?;
+        Some(Extension::execute(self, decoded.op, ctx))
+    }
+}
+
+/// Registered custom extensions, consulted in registration order. Encodings the
+/// built-in matrix engine implements are never offered to them.
+#[derive(Default)]
+pub struct Extensions {
+    list: Vec<Box<dyn AnyExtension>>,
+}
+
+impl Extensions {
+    /// Register an extension, returning its index
+    pub fn register(&mut self, extension: impl Extension + 'static) -> u8 {
+        self.list.push(Box::new(extension));
+        (self.list.len() - 1) as u8
+    }
+
+    /// Decode an instruction, attaching the scheduling info of the first extension that claims it
+    pub fn decode(&self, raw: RawInstruction) -> Instruction {
+        let mut instr = Instruction::from(raw);
+        if matches!(instr.opcode, CUSTOM_0 | CUSTOM_1 | CUSTOM_2 | CUSTOM_3) && instr.mnemonic() == "unknown" {
+            instr.custom = self.list.iter().enumerate().find_map(|(index, extension)| {
+                extension.info(&instr).map(|info| CustomInfo { extension: index as u8, ..info })
+            });
+        }
+        instr
+    }
+
+    /// Execute a custom instruction through the extension that decoded it.
+    /// Returns `None` if no registered extension claims it.
+    pub fn execute(&mut self, ctx: &mut CustomContext) -> Option<Result<u32, Trap>> {
+        let index = ctx.instr.custom?.extension;
+        self.list.get_mut(index as usize)?.execute(ctx)
+    }
+}
+
+/// A custom instruction waiting out its latency with the result it computed at issue
+#[derive(Copy, Clone, Debug)]
+pub struct CustomSlot {
+    pub instr: Instruction,
+    pub remaining: u8,
+    pub result: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::asm::assemble;
+    use crate::scalar::core::ScalarFrontend;
+    use crate::scalar::csr::CAUSE_ILLEGAL_INSTRUCTION;
+    use crate::scalar::dispatch::Halt;
+    use crate::scalar::memory::ITCM_BASE;
+
+    const LATENCY: u8 = 4;
+
+    /// Add-immediate in custom-1 with funct3 0, taking `LATENCY` cycles in an ALU
+    struct AddImm;
+
+    impl Extension for AddImm {
+        type Op = i32;
+
+        fn decode(&self, instr: &Instruction) -> Option<Decoded<i32>> {
+            (instr.opcode == CUSTOM_1 && instr.funct3 == 0).then(|| Decoded {
+                op: instr.raw as i32 >> 20,
+                info: CustomInfo { latency: LATENCY, reads_rs1: true, writes_rd: true, ..CustomInfo::new("c.addi", CustomUnit::Alu) },
+            })
+        }
+
+        fn execute(&mut self, imm: i32, ctx: &mut CustomContext) -> Result<u32, Trap> {
+            Ok(ctx.rs1.wrapping_add(imm as u32))
+        }
+    }
+
+    fn core(source: &str) -> ScalarFrontend {
+        let program = assemble(source).expect("program assembles");
+        let mut core = ScalarFrontend::new();
+        core.extensions.register(AddImm);
+        core.load_program(&program.segments, ITCM_BASE, &program.symbols).unwrap();
+        core
+    }
+
+    #[test]
+    fn custom_alu_instruction_writes_rd_after_its_latency() {
+        let mut core = core(
+            "li    a0, 5
+             .word 0x003505ab # c.addi a1, a0, 3
+             add   a2, a1, a1
+             mv    a0, a2
+             ecall",
+        );
+        while core.dispatch.custom.is_empty() {
+            assert!(core.cycle < 100, "custom instruction never issued");
+            core.tick();
+        }
+        let issued = core.cycle;
+        while core.regs.read(11) != 8 {
+            assert!(core.cycle < issued + 100, "custom result never written");
+            assert_eq!(core.regs.read(12), 0, "dependent add issued before its source was written");
+            core.tick();
+        }
+        assert_eq!(core.cycle - issued, LATENCY as u64, "writeback cycle");
+        assert_eq!(core.regs.read(12), 0, "dependent add issued in the writeback cycle");
+        assert!(matches!(core.run(1_000), Some(Halt::Exit(16))), "program exits with a2");
+    }
+
+    #[test]
+    fn unclaimed_custom_encoding_is_illegal() {
+        let mut core = core(".word 0x003515ab # custom-1, funct3 1");
+        let halt = core.run(1_000);
+        assert!(
+            matches!(halt, Some(Halt::Trap(Trap { cause: CAUSE_ILLEGAL_INSTRUCTION, tval: 0x003515ab }))),
+            "halted with {:?}",
+            halt
+        );
+    }
+}
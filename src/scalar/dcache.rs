@@ -0,0 +1,367 @@
+use tracing::debug;
+use crate::common::axi::{AxiRead, ExternalMemory};
+use crate::common::io::{Future, Poll};
+use crate::scalar::icache::Replacement;
+use crate::scalar::instruction::Instruction;
+
+/// When stores reach external memory
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WritePolicy {
+    /// Stores mark the line dirty, which is written back when it is evicted
+    WriteBack,
+    /// Every store is also posted to external memory
+    WriteThrough,
+}
+
+/// Geometry and policies of the data cache
+#[derive(Copy, Clone, Debug)]
+pub struct DCacheConfig {
+    /// Capacity in bytes
+    pub size: u32,
+    /// Bytes per line, a power of two of at least 4
+    pub line_bytes: u32,
+    pub ways: u32,
+    pub replacement: Replacement,
+    pub write_policy: WritePolicy,
+    /// Whether a store miss fills the line, otherwise the store goes around the cache
+    pub write_allocate: bool,
+    /// Line fills that may be outstanding at once
+    pub mshrs: usize,
+    /// Cycles from a lookup until a hit completes
+    pub hit_latency: u8,
+}
+
+impl Default for DCacheConfig {
+    fn default() -> Self {
+        Self {
+            size: 8 * 1024,
+            line_bytes: 32,
+            ways: 2,
+            replacement: Replacement::Lru,
+            write_policy: WritePolicy::WriteBack,
+            write_allocate: true,
+            mshrs: 4,
+            hit_latency: 1,
+        }
+    }
+}
+
+/// Lookup, fill and write counts of the data cache
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DCacheStats {
+    pub load_hits: u64,
+    pub load_misses: u64,
+    pub store_hits: u64,
+    pub store_misses: u64,
+    /// Misses to a line whose fill was already outstanding
+    pub merged_misses: u64,
+    /// Valid lines replaced by fills
+    pub evictions: u64,
+    /// Dirty lines written back on eviction
+    pub writebacks: u64,
+    /// Cycles a miss waited because every MSHR was taken
+    pub mshr_stall_cycles: u64,
+}
+
+/// Outcome of a lookup
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Access {
+    /// The access completes `hit_latency` cycles after the lookup
+    Hit,
+    /// The access waits in an MSHR for its line
+    Miss,
+    /// No MSHR is free, the lookup must be retried
+    Blocked,
+}
+
+/// A valid cache line
+struct Line {
+    /// Address of the first byte
+    addr: u32,
+    dirty: bool,
+    /// Cycles of the last lookup hitting the line and of its fill
+    last_used: u64,
+    filled: u64,
+}
+
+/// Miss status holding register: an outstanding line fill and the accesses waiting for it
+struct Mshr {
+    addr: u32,
+    read: AxiRead,
+    /// Waiting accesses with the values loads return
+    targets: Vec<(Instruction, u32)>,
+    /// Whether a store is waiting, so the line arrives dirty under write-back
+    dirty: bool,
+}
+
+/// Non-blocking set-associative data cache between the scalar LSU and external memory.
+///
+/// The cache models tags, dirty state and traffic only. Loads and stores access
+/// memory functionally at issue like the other units, so the DRAM always holds
+/// current data and the DMA and debug accesses see it. Misses allocate an MSHR
+/// and request the line over AXI, and later misses to the same line merge into it,
+/// so hits and further misses proceed while fills are outstanding. Write-back
+/// evictions and write-through stores are posted to the AXI port without stalling.
+pub struct DCache {
+    pub config: DCacheConfig,
+    pub stats: DCacheStats,
+    /// Valid lines of each set, at most `ways` per set
+    sets: Vec<Vec<Line>>,
+    mshrs: Vec<Mshr>,
+    /// Current cycle
+    now: u64,
+    /// State of the xorshift generator picking random victims
+    seed: u32,
+}
+
+impl DCache {
+    pub fn new(config: DCacheConfig) -> Self {
+        Self { config, stats: DCacheStats::default(), sets: Vec::new(), mshrs: Vec::new(), now: 0, seed: 0x2545_f491 }
+    }
+
+    /// Number of sets
+    pub fn num_sets(&self) -> usize {
+        (self.config.size / (self.config.line_bytes * self.config.ways.max(1))).max(1) as usize
+    }
+
+    /// Outstanding line fills
+    pub fn outstanding(&self) -> usize {
+        self.mshrs.len()
+    }
+
+    /// Address of the line holding `addr`
+    fn line_of(&self, addr: u32) -> u32 {
+        addr & !(self.config.line_bytes - 1)
+    }
+
+    /// Set of the line at `addr`
+    fn set_of(&self, addr: u32) -> usize {
+        (addr / self.config.line_bytes) as usize % self.num_sets()
+    }
+
+    /// Look up a load or store of `size` bytes at `addr` by `instr`, with `value` the
+    /// result a load returns once it completes
+    pub fn access(&mut self, instr: Instruction, addr: u32, size: u32, store: bool, value: u32, dram: &mut ExternalMemory) -> Access {
+        let line = self.line_of(addr);
+        let set = self.set_of(line);
+        let write_through = self.config.write_policy == WritePolicy::WriteThrough;
+
+        if let Some(entry) = self.sets.get_mut(set).and_then(|lines| lines.iter_mut().find(|entry| entry.addr == line)) {
+            entry.last_used = self.now;
+            if store {
+                self.stats.store_hits += 1;
+                entry.dirty |= !write_through;
+                if write_through {
+                    dram.post_write(addr, size);
+                }
+            } else {
+                self.stats.load_hits += 1;
+            }
+            return Access::Hit;
+        }
+
+        if store && !self.config.write_allocate {
+            self.stats.store_misses += 1;
+            dram.post_write(addr, size);
+            return Access::Hit;
+        }
+
+        let pending = self.mshrs.iter().position(|mshr| mshr.addr == line);
+        if pending.is_none() && self.mshrs.len() >= self.config.mshrs.max(1) {
+            self.stats.mshr_stall_cycles += 1;
+            return Access::Blocked;
+        }
+        if store {
+            self.stats.store_misses += 1;
+            if write_through {
+                dram.post_write(addr, size);
+            }
+        } else {
+            self.stats.load_misses += 1;
+        }
+        let index = match pending {
+            Some(index) => {
+                self.stats.merged_misses += 1;
+                index
+            }
+            None => {
+                debug!("D-cache miss addr=0x{:08x}", addr);
+                let read = dram.read(line, self.config.line_bytes);
+                self.mshrs.push(Mshr { addr: line, read, targets: Vec::new(), dirty: false });
+                self.mshrs.len() - 1
+            }
+        };
+        let mshr = &mut self.mshrs[index];
+        mshr.targets.push((instr, value));
+        mshr.dirty |= store && !write_through;
+        Access::Miss
+    }
+
+    /// Install the fills that arrived and advance to the next cycle, returning the
+    /// accesses they complete
+    pub fn tick(&mut self, dram: &mut ExternalMemory) -> Vec<(Instruction, u32)> {
+        let mut arrived = Vec::new();
+        self.mshrs.retain_mut(|mshr| match mshr.read.poll(dram) {
+            Poll::Ready(_) => {
+                arrived.push((mshr.addr, mshr.dirty, std::mem::take(&mut mshr.targets)));
+                false
+            }
+            Poll::Pending => true,
+        });
+
+        let mut completed = Vec::new();
+        for (addr, dirty, targets) in arrived {
+            self.install(addr, dirty, dram);
+            completed.extend(targets);
+        }
+        self.now += 1;
+        completed
+    }
+
+    /// Place a filled line in its set, evicting a victim and writing it back if it is dirty
+    fn install(&mut self, addr: u32, dirty: bool, dram: &mut ExternalMemory) {
+        let sets = self.num_sets();
+        self.sets.resize_with(sets, Vec::new);
+        let index = self.set_of(addr);
+        if self.sets[index].len() >= self.config.ways.max(1) as usize {
+            let set = &self.sets[index];
+            let victim = match self.config.replacement {
+                Replacement::Lru => (0..set.len()).min_by_key(|&way| set[way].last_used).unwrap_or(0),
+                Replacement::Fifo => (0..set.len()).min_by_key(|&way| set[way].filled).unwrap_or(0),
+                Replacement::Random => {
+                    self.seed ^= self.seed << 13;
+                    self.seed ^= self.seed >> 17;
+                    self.seed ^= self.seed << 5;
+                    self.seed as usize % set.len()
+                }
+            };
+            let evicted = self.sets[index].remove(victim);
+            self.stats.evictions += 1;
+            if evicted.dirty {
+                debug!("D-cache write-back addr=0x{:08x}", evicted.addr);
+                self.stats.writebacks += 1;
+                dram.post_write(evicted.addr, self.config.line_bytes);
+            }
+        }
+        self.sets[index].push(Line { addr, dirty, last_used: self.now, filled: self.now });
+    }
+}
+
+impl Default for DCache {
+    fn default() -> Self {
+        Self::new(DCacheConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::asm::assemble;
+    use crate::scalar::core::ScalarFrontend;
+    use crate::scalar::dispatch::Halt;
+    use crate::scalar::instruction::RawInstruction;
+    use crate::scalar::memory::{DRAM_BASE, ITCM_BASE};
+
+    /// A `lw` standing in for the access that waits in an MSHR
+    fn load() -> Instruction {
+        Instruction::from(RawInstruction { pc: 0, data: 0x00052283 })
+    }
+
+    /// Tick until outstanding fills arrive, returning the accesses they complete
+    fn fill(cache: &mut DCache, dram: &mut ExternalMemory) -> Vec<(Instruction, u32)> {
+        for _ in 0..1_000 {
+            let completed = cache.tick(dram);
+            dram.tick();
+            if !completed.is_empty() {
+                return completed;
+            }
+        }
+        panic!("line fill never arrived");
+    }
+
+    #[test]
+    fn load_miss_then_hit() {
+        let mut dram = ExternalMemory::default();
+        let mut cache = DCache::default();
+        assert_eq!(cache.access(load(), DRAM_BASE, 4, false, 7, &mut dram), Access::Miss);
+        let completed = fill(&mut cache, &mut dram);
+        assert_eq!(completed.iter().map(|&(_, value)| value).collect::<Vec<_>>(), vec![7]);
+        assert_eq!(cache.access(load(), DRAM_BASE + 4, 4, false, 0, &mut dram), Access::Hit);
+        assert_eq!((cache.stats.load_misses, cache.stats.load_hits), (1, 1));
+        assert_eq!(dram.stats.read_bursts, 1, "the hit reads nothing");
+    }
+
+    #[test]
+    fn misses_to_one_line_merge_into_one_mshr() {
+        let mut dram = ExternalMemory::default();
+        let mut cache = DCache::default();
+        assert_eq!(cache.access(load(), DRAM_BASE, 4, false, 1, &mut dram), Access::Miss);
+        assert_eq!(cache.access(load(), DRAM_BASE + 8, 4, false, 2, &mut dram), Access::Miss);
+        assert_eq!(cache.outstanding(), 1);
+        assert_eq!(cache.stats.merged_misses, 1);
+        let completed = fill(&mut cache, &mut dram);
+        assert_eq!(completed.iter().map(|&(_, value)| value).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(dram.stats.read_bursts, 1);
+    }
+
+    #[test]
+    fn miss_blocks_when_every_mshr_is_taken() {
+        let mut dram = ExternalMemory::default();
+        let mut cache = DCache::new(DCacheConfig { mshrs: 2, ..DCacheConfig::default() });
+        assert_eq!(cache.access(load(), DRAM_BASE, 4, false, 0, &mut dram), Access::Miss);
+        assert_eq!(cache.access(load(), DRAM_BASE + 32, 4, false, 0, &mut dram), Access::Miss);
+        assert_eq!(cache.access(load(), DRAM_BASE + 64, 4, false, 0, &mut dram), Access::Blocked);
+        assert_eq!(cache.stats.mshr_stall_cycles, 1);
+        // A miss to a line already being filled needs no new MSHR
+        assert_eq!(cache.access(load(), DRAM_BASE + 4, 4, false, 0, &mut dram), Access::Miss);
+        while cache.outstanding() > 0 {
+            fill(&mut cache, &mut dram);
+        }
+        assert_eq!(cache.access(load(), DRAM_BASE + 64, 4, false, 0, &mut dram), Access::Miss);
+    }
+
+    #[test]
+    fn dirty_lines_are_written_back_on_eviction_or_posted_under_write_through() {
+        for (policy, writebacks, bytes_written) in [(WritePolicy::WriteBack, 1, 32), (WritePolicy::WriteThrough, 0, 4)] {
+            let mut dram = ExternalMemory::default();
+            // A single one-line set, so every fill evicts the previous line
+            let config = DCacheConfig { size: 32, line_bytes: 32, ways: 1, write_policy: policy, ..DCacheConfig::default() };
+            let mut cache = DCache::new(config);
+            assert_eq!(cache.access(load(), DRAM_BASE, 4, true, 0, &mut dram), Access::Miss);
+            fill(&mut cache, &mut dram);
+            assert_eq!(cache.access(load(), DRAM_BASE + 32, 4, false, 0, &mut dram), Access::Miss);
+            fill(&mut cache, &mut dram);
+            assert_eq!(cache.stats.evictions, 1, "{:?} evictions", policy);
+            assert_eq!(cache.stats.writebacks, writebacks, "{:?} write-backs", policy);
+            assert_eq!(dram.stats.bytes_written, bytes_written, "{:?} bytes written", policy);
+        }
+    }
+
+    #[test]
+    fn load_miss_frees_the_lsu_and_writes_rd_when_the_fill_arrives() {
+        let program = assemble(
+            "li    a0, 0x80000000
+             lw    a1, 0(a0)
+             add   a0, a1, a1
+             ecall",
+        )
+        .expect("program assembles");
+        let mut core = ScalarFrontend::new();
+        core.dispatch.lsu.dcache = Some(DCache::default());
+        core.load_program(&program.segments, ITCM_BASE, &program.symbols).unwrap();
+        core.dram.store(DRAM_BASE, 4, 21);
+
+        let outstanding = |core: &ScalarFrontend| core.dispatch.lsu.dcache.as_ref().unwrap().outstanding();
+        while outstanding(&core) == 0 {
+            assert!(core.cycle < 100, "load never missed");
+            core.tick();
+        }
+        assert!(!core.dispatch.lsu.busy, "the miss waits in an MSHR, not in the unit");
+        while outstanding(&core) > 0 {
+            assert_eq!(core.regs.read(11), 0, "rd written before the fill arrived");
+            core.tick();
+        }
+        assert_eq!(core.regs.read(11), 21);
+        assert!(matches!(core.run(1_000), Some(Halt::Exit(42))));
+    }
+}
@@ -1,5 +1,6 @@
+use crate::scalar::custom::Extensions;
 use crate::scalar::dispatch::DispatchQueue;
-use crate::scalar::instruction::{Instruction, InstructionBuffer, RawInstruction};
+use crate::scalar::instruction::{InstructionBuffer, RawInstruction};
 
 /// The DecodeStage struct represents the decode stage of the scalar pipeline
 pub struct DecodeStage {
@@ -23,18 +24,27 @@ impl DecodeStage {
         }
     }
 
-    /// Advances the decode stage by one tick, decoding instructions and pushing them to the dispatch queue
-    pub fn tick(&mut self, instr_buffer: &mut InstructionBuffer, dispatch_q: &mut DispatchQueue) {
-        let batch = instr_buffer.pop_batch(4);
-        self.accept_batch(batch);
-        
+    /// Advances the decode stage by one tick, decoding instructions and pushing them to the dispatch queue.
+    /// Custom opcodes are decoded by the registered `extensions`.
+    pub fn tick(&mut self, instr_buffer: &mut InstructionBuffer, dispatch_q: &mut DispatchQueue, extensions: &Extensions) {
+        if self.lanes.iter().all(Option::is_none) {
+            let batch = instr_buffer.pop_batch(4);
+            self.accept_batch(batch);
+        }
+
         for lane in 0..4 {
-            if let Some(raw) = self.lanes[lane].take() {
-                let decoded = Instruction::from(raw);
+            if let Some(raw) = self.lanes[lane] {
+                let decoded = extensions.decode(raw);
                 if !dispatch_q.push(decoded) {
                     break;
                 }
+                self.lanes[lane] = None;
             }
         }
     }
+
+    /// Drop every instruction waiting in the decode lanes
+    pub fn flush(&mut self) {
+        self.lanes = [None; 4];
+    }
 }
@@ -0,0 +1,267 @@
+use std::io::{self, Write};
+use crate::common::elf::{Elf, SymbolTable};
+use crate::scalar::custom::CUSTOM_0;
+use crate::scalar::instruction::{sign_extend, Instruction, RawInstruction};
+use crate::scalar::regfile::ABI_NAMES;
+use crate::vector::config::Vtype;
+use crate::vector::decode::{LOAD_FP, OPFVF, OPIVI, OPIVX, OPMVX, STORE_FP};
+
+/// Disassemble an instruction with ABI register names and pseudo-instructions.
+/// Branch and jump targets are absolute and annotated from `symbols` when given.
+pub fn disassemble(instr: &Instruction, symbols: Option<&SymbolTable>) -> String {
+    let reg = |r: u8| ABI_NAMES[r as usize];
+    let (rd, rs1, rs2) = (reg(instr.rd), reg(instr.rs1), reg(instr.rs2));
+    let imm = instr.imm;
+    let target = || format_addr(instr.pc.wrapping_add(imm as u32), symbols);
+    let name = instr.mnemonic();
+    let unknown = || format!(".word 0x{:08x}", instr.raw);
+
+    match instr.opcode {
+        _ if instr.custom.is_some() => disassemble_custom(instr, name),
+        0b0110111 | 0b0010111 => format!("{} {}, 0x{:x}", name, rd, (imm as u32) >> 12),
+        0b1101111 => match instr.rd {
+            0 => format!("j {}", target()),
+            1 => format!("jal {}", target()),
+            _ => format!("jal {}, {}", rd, target()),
+        },
+        0b1100111 => match (instr.rd, instr.rs1, imm) {
+            (0, 1, 0) => "ret".to_string(),
+            (0, _, 0) => format!("jr {}", rs1),
+            (1, _, 0) => format!("jalr {}", rs1),
+            _ => format!("jalr {}, {}({})", rd, imm, rs1),
+        },
+        0b1100011 if name != "unknown" => match (name, instr.rs1, instr.rs2) {
+            ("beq", _, 0) => format!("beqz {}, {}", rs1, target()),
+            ("bne", _, 0) => format!("bnez {}, {}", rs1, target()),
+            ("blt", _, 0) => format!("bltz {}, {}", rs1, target()),
+            ("bge", _, 0) => format!("bgez {}, {}", rs1, target()),
+            ("blt", 0, _) => format!("bgtz {}, {}", rs2, target()),
+            ("bge", 0, _) => format!("blez {}, {}", rs2, target()),
+            _ => format!("{} {}, {}, {}", name, rs1, rs2, target()),
+        },
+        0b0000011 if name != "unknown" => format!("{} {}, {}({})", name, rd, imm, rs1),
+        0b0100011 if name != "unknown" => format!("{} {}, {}({})", name, rs2, imm, rs1),
+        0b0010011 if name != "unknown" => match (name, instr.rd, instr.rs1, imm) {
+            ("addi", 0, 0, 0) => "nop".to_string(),
+            ("addi", _, 0, _) => format!("li {}, {}", rd, imm),
+            ("addi", _, _, 0) => format!("mv {}, {}", rd, rs1),
+            ("xori", _, _, -1) => format!("not {}, {}", rd, rs1),
+            ("sltiu", _, _, 1) => format!("seqz {}, {}", rd, rs1),
+            ("slli" | "srli" | "srai", ..) => format!("{} {}, {}, {}", name, rd, rs1, imm & 0x1F),
+            _ => format!("{} {}, {}, {}", name, rd, rs1, imm),
+        },
+        0b0110011 if name != "unknown" => match (name, instr.rs1, instr.rs2) {
+            ("sub", 0, _) => format!("neg {}, {}", rd, rs2),
+            ("sltu", 0, _) => format!("snez {}, {}", rd, rs2),
+            ("slt", _, 0) => format!("sltz {}, {}", rd, rs1),
+            ("slt", 0, _) => format!("sgtz {}, {}", rd, rs2),
+            _ => format!("{} {}, {}, {}", name, rd, rs1, rs2),
+        },
+        0b0001111 => match instr.funct3 {
+            0b000 => "fence".to_string(),
+            0b001 => "fence.i".to_string(),
+            _ => unknown(),
+        },
+        0b1110011 => disassemble_system(instr).unwrap_or_else(unknown),
+        LOAD_FP | STORE_FP if name != "unknown" => disassemble_vector_memory(instr, name),
+        0b1010111 if name != "unknown" => match name {
+            "vsetvli" => format!("vsetvli {}, {}, {}", rd, rs1, format_vtype(imm as u32)),
+            "vsetivli" => format!("vsetivli {}, {}, {}", rd, instr.rs1, format_vtype(imm as u32)),
+            "vsetvl" => format!("{} {}, {}, {}", name, rd, rs1, rs2),
+            _ => disassemble_vector(instr, name),
+        },
+        CUSTOM_0 if instr.is_matrix() => disassemble_matrix(instr, name),
+        _ => unknown(),
+    }
+}
+
+/// Disassemble SYSTEM opcode instructions, including Zicsr accesses
+fn disassemble_system(instr: &Instruction) -> Option<String> {
+    let rd = ABI_NAMES[instr.rd as usize];
+    let rs1 = ABI_NAMES[instr.rs1 as usize];
+    let csr = csr_name((instr.imm as u32 & 0xFFF) as u16);
+    let uimm = instr.rs1;
+
+    let text = match (instr.funct3, instr.imm & 0xFFF) {
+        (0b000, 0x000) => "ecall".to_string(),
+        (0b000, 0x001) => "ebreak".to_string(),
+        (0b000, 0x302) => "mret".to_string(),
+        (0b000, 0x105) => "wfi".to_string(),
+        (0b001, _) if instr.rd == 0 => format!("csrw {}, {}", csr, rs1),
+        (0b001, _) => format!("csrrw {}, {}, {}", rd, csr, rs1),
+        (0b010, _) if instr.rs1 == 0 => format!("csrr {}, {}", rd, csr),
+        (0b010, _) if instr.rd == 0 => format!("csrs {}, {}", csr, rs1),
+        (0b010, _) => format!("csrrs {}, {}, {}", rd, csr, rs1),
+        (0b011, _) if instr.rd == 0 => format!("csrc {}, {}", csr, rs1),
+        (0b011, _) => format!("csrrc {}, {}, {}", rd, csr, rs1),
+        (0b101, _) if instr.rd == 0 => format!("csrwi {}, {}", csr, uimm),
+        (0b101, _) => format!("csrrwi {}, {}, {}", rd, csr, uimm),
+        (0b110, _) if instr.rd == 0 => format!("csrsi {}, {}", csr, uimm),
+        (0b110, _) => format!("csrrsi {}, {}, {}", rd, csr, uimm),
+        (0b111, _) if instr.rd == 0 => format!("csrci {}, {}", csr, uimm),
+        (0b111, _) => format!("csrrci {}, {}, {}", rd, csr, uimm),
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// Disassemble a vector arithmetic instruction, multiply-adds list their operands as `vd, vs1, vs2`
+fn disassemble_vector(instr: &Instruction, name: &str) -> String {
+    let mask = if instr.funct7 & 1 == 0 { ", v0.t" } else { "" };
+    if matches!(name, "vmv.x.s" | "vcpop.m" | "vfirst.m") {
+        return format!("{} {}, v{}{}", name, ABI_NAMES[instr.rd as usize], instr.rs2, mask);
+    }
+    let first = match instr.funct3 {
+        OPIVX | OPMVX | OPFVF => ABI_NAMES[instr.rs1 as usize].to_string(),
+        // Gather indices and slide offsets are unsigned
+        OPIVI if name.starts_with("vrgather") || name.starts_with("vslide") => instr.rs1.to_string(),
+        OPIVI => sign_extend(instr.rs1 as i32, 5).to_string(),
+        _ => format!("v{}", instr.rs1),
+    };
+    match name {
+        "vmsbf.m" | "vmsof.m" | "vmsif.m" | "viota.m" => return format!("{} v{}, v{}{}", name, instr.rd, instr.rs2, mask),
+        "vid.v" => return format!("{} v{}{}", name, instr.rd, mask),
+        _ if name.starts_with("vfcvt") || name.starts_with("vfwcvt") || name.starts_with("vfncvt") => {
+            return format!("{} v{}, v{}{}", name, instr.rd, instr.rs2, mask)
+        }
+        "vmv.v.v" | "vmv.v.x" | "vmv.v.i" | "vmv.s.x" => return format!("{} v{}, {}", name, instr.rd, first),
+        _ if name.starts_with("vmerge") => return format!("{} v{}, v{}, {}, v0", name, instr.rd, instr.rs2, first),
+        _ if name.ends_with("r.v") => return format!("{} v{}, v{}", name, instr.rd, instr.rs2),
+        _ => {}
+    }
+    let multiply_add = ["vmacc", "vnmsac", "vmadd", "vnmsub", "vwmacc", "vfmacc", "vfwmacc"].iter().any(|op| name.starts_with(op));
+    if multiply_add {
+        format!("{} v{}, {}, v{}{}", name, instr.rd, first, instr.rs2, mask)
+    } else {
+        format!("{} v{}, v{}, {}{}", name, instr.rd, instr.rs2, first, mask)
+    }
+}
+
+/// Disassemble a matrix engine instruction, tiles are named `acc0` and up
+fn disassemble_matrix(instr: &Instruction, name: &str) -> String {
+    match name {
+        "mzero" => format!("{} acc{}", name, instr.rd),
+        "minit" => format!("{} acc{}, v{}", name, instr.rd, instr.rs1),
+        "mread.w" => format!("{} v{}, acc{}", name, instr.rd, instr.rs2),
+        _ if name.starts_with("mread") => format!("{} v{}, acc{}, {}", name, instr.rd, instr.rs2, ABI_NAMES[instr.rs1 as usize]),
+        _ => format!("{} acc{}, v{}, v{}", name, instr.rd, instr.rs1, instr.rs2),
+    }
+}
+
+/// Disassemble an instruction of a registered extension, listing the scalar registers it uses
+fn disassemble_custom(instr: &Instruction, name: &str) -> String {
+    let operands: Vec<&str> = [(instr.writes_rd(), instr.rd), (instr.reads_rs1(), instr.rs1), (instr.reads_rs2(), instr.rs2)]
+        .into_iter()
+        .filter(|(used, _)| *used)
+        .map(|(_, r)| ABI_NAMES[r as usize])
+        .collect();
+    if operands.is_empty() { name.to_string() } else { format!("{} {}", name, operands.join(", ")) }
+}
+
+/// Disassemble a vector load or store, naming segment accesses like `vlseg2e8.v`
+fn disassemble_vector_memory(instr: &Instruction, name: &str) -> String {
+    let fields = (instr.funct7 >> 4) + 1;
+    let name = match name.rfind('e') {
+        Some(at) if fields > 1 => format!("{}seg{}{}", &name[..at], fields, &name[at..]),
+        _ => name.to_string(),
+    };
+    let base = ABI_NAMES[instr.rs1 as usize];
+    let offsets = match (instr.funct7 >> 1) & 0b11 {
+        0b00 => String::new(),
+        0b10 => format!(", {}", ABI_NAMES[instr.rs2 as usize]),
+        _ => format!(", v{}", instr.rs2),
+    };
+    let mask = if instr.funct7 & 1 == 0 { ", v0.t" } else { "" };
+    format!("{} v{}, ({}){}{}", name, instr.rd, base, offsets, mask)
+}
+
+/// Format a vtype immediate as `e32, m1, ta, ma`, or as a number if it is reserved
+fn format_vtype(bits: u32) -> String {
+    match Vtype::decode(bits) {
+        Some(vtype) => vtype.to_string(),
+        None => format!("0x{:x}", bits),
+    }
+}
+
+/// Well-known CSR numbers and names
+pub const CSR_NAMES: [(u16, &str); 33] = [
+    (0x001, "fflags"),
+    (0x002, "frm"),
+    (0x003, "fcsr"),
+    (0x008, "vstart"),
+    (0x009, "vxsat"),
+    (0x00A, "vxrm"),
+    (0x00F, "vcsr"),
+    (0x300, "mstatus"),
+    (0x301, "misa"),
+    (0x304, "mie"),
+    (0x305, "mtvec"),
+    (0x340, "mscratch"),
+    (0x341, "mepc"),
+    (0x342, "mcause"),
+    (0x343, "mtval"),
+    (0x344, "mip"),
+    (0xB00, "mcycle"),
+    (0xB02, "minstret"),
+    (0xB80, "mcycleh"),
+    (0xB82, "minstreth"),
+    (0xC00, "cycle"),
+    (0xC01, "time"),
+    (0xC02, "instret"),
+    (0xC20, "vl"),
+    (0xC21, "vtype"),
+    (0xC22, "vlenb"),
+    (0xC80, "cycleh"),
+    (0xC81, "timeh"),
+    (0xC82, "instreth"),
+    (0xF11, "mvendorid"),
+    (0xF12, "marchid"),
+    (0xF13, "mimpid"),
+    (0xF14, "mhartid"),
+];
+
+/// Name of a CSR, or its number in hex if it has no well-known name
+pub fn csr_name(csr: u16) -> String {
+    match CSR_NAMES.iter().find(|(number, _)| *number == csr) {
+        Some((_, name)) => name.to_string(),
+        None => format!("0x{:03x}", csr),
+    }
+}
+
+/// Format an absolute address, with `<symbol+offset>` when one covers it
+fn format_addr(addr: u32, symbols: Option<&SymbolTable>) -> String {
+    match symbols.and_then(|s| s.lookup(addr)) {
+        Some((symbol, 0)) => format!("0x{:x} <{}>", addr, symbol.name),
+        Some((symbol, offset)) => format!("0x{:x} <{}+0x{:x}>", addr, symbol.name, offset),
+        None => format!("0x{:x}", addr),
+    }
+}
+
+/// Print an objdump-style listing of an ELF file or a raw binary image loaded at `base`
+pub fn dump_image(image: &[u8], base: u32, out: &mut impl Write) -> io::Result<()> {
+    if Elf::is_elf(image) {
+        let elf = Elf::parse(image)?;
+        for segment in elf.segments.iter().filter(|s| s.executable) {
+            dump_code(&segment.data, segment.addr, Some(&elf.symbols), out)?;
+        }
+        Ok(())
+    } else {
+        dump_code(image, base, None, out)
+    }
+}
+
+/// Disassemble a block of code word by word
+fn dump_code(code: &[u8], base: u32, symbols: Option<&SymbolTable>, out: &mut impl Write) -> io::Result<()> {
+    for (i, word) in code.chunks(4).enumerate() {
+        let pc = base.wrapping_add(4 * i as u32);
+        if let Some(symbols) = symbols {
+            for symbol in symbols.at(pc) {
+                writeln!(out, "\n{:08x} <{}>:", pc, symbol.name)?;
+            }
+        }
+        let data = word.iter().rev().fold(0, |acc, b| (acc << 8) | *b as u32);
+        let instr = Instruction::from(RawInstruction { pc, data });
+        writeln!(out, "{:8x}:\t{:08x}\t{}", pc, data, disassemble(&instr, symbols))?;
+    }
+    Ok(())
+}
@@ -1,8 +1,29 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeSet, VecDeque};
 use tracing::debug;
-use crate::scalar::instruction::{Instruction, InstructionType};
+use crate::matrix::engine::MatrixEngine;
+use crate::scalar::csr::{CsrFile, Trap, CAUSE_BREAKPOINT, CAUSE_ECALL_M, CAUSE_ILLEGAL_INSTRUCTION, CAUSE_INTERRUPT};
+use crate::scalar::custom::{CustomContext, CustomSlot, Extensions};
+use crate::scalar::instruction::Instruction;
+use crate::scalar::memory::DataBus;
+use crate::scalar::regfile::RegisterFile;
 use crate::scalar::scoreboard::Scoreboard;
-use crate::scalar::units::{AluUnit, BruUnit, LsuUnit};
+use crate::scalar::semihost::{Semihost, SemihostResult};
+use crate::scalar::units::{AluUnit, BruUnit, CsrUnit, LsuUnit};
+use crate::vector::backend::VectorBackend;
+
+/// Encoding of `wfi`
+const WFI: u32 = 0x10500073;
+
+/// Reason the core stopped issuing instructions
+#[derive(Copy, Clone, Debug)]
+pub enum Halt {
+    /// The program exited with a status code, zero meaning success
+    Exit(u32),
+    /// `ebreak` executed with no trap handler installed
+    Breakpoint,
+    /// An exception was raised with no trap handler installed
+    Trap(Trap),
+}
 
 /// Dispatch stage of the scalar pipeline
 pub struct DispatchStage {
@@ -11,7 +32,26 @@ pub struct DispatchStage {
     pub alus: Vec<AluUnit>,
     pub brus: Vec<BruUnit>,
     pub lsu: LsuUnit,
+    pub csr: CsrUnit,
+    /// Vector units fed through the vector instruction queue
+    pub vector: VectorBackend,
+    /// Outer-product MAC array with its accumulator tiles
+    pub matrix: MatrixEngine,
+    /// Custom instructions of registered extensions waiting out their latency
+    pub custom: Vec<CustomSlot>,
     pub issue_width: u8,
+    /// Debugger controls applied before each instruction issues
+    pub control: IssueControl,
+    /// PC of the next instruction to issue in program order
+    pub pc: u32,
+    /// Number of instructions that have completed execution
+    pub retired: u64,
+    /// Consecutive cycles in which queued instructions were waiting but none issued
+    pub stall_cycles: u64,
+    /// Set once the program has exited or stopped on an unhandled exception
+    pub halt: Option<Halt>,
+    /// Set when a `fence.i` issues, for the frontend to drop cached instructions
+    pub fence_i: bool,
 }
 
 impl DispatchStage {
@@ -23,14 +63,32 @@ impl DispatchStage {
             alus: (0..4).map(|_| AluUnit::new()).collect(),
             brus: (0..4).map(|_| BruUnit::new()).collect(),
             lsu: LsuUnit::new(),
-            issue_width: 4
+            csr: CsrUnit::new(),
+            vector: VectorBackend::default(),
+            matrix: MatrixEngine::default(),
+            custom: Vec::new(),
+            issue_width: 4,
+            control: IssueControl::default(),
+            pc: 0,
+            retired: 0,
+            stall_cycles: 0,
+            halt: None,
+            fence_i: false,
         }
     }
 
-    /// Tick the dispatch stage, dispatching up to 4 instructions
-    pub fn tick(&mut self) {
+    /// Tick the dispatch stage, issuing up to 4 instructions in program order.
+    /// Returns the fetch redirect target when an issued instruction changes control flow.
+    pub fn tick(
+        &mut self,
+        regs: &mut RegisterFile,
+        csrs: &mut CsrFile,
+        bus: &mut DataBus,
+        semihost: &mut Semihost,
+        extensions: &mut Extensions,
+    ) -> Option<u32> {
         let mut issued = 0;
-        let mut remaining = VecDeque::new();
+        let mut redirect = self.take_interrupt(csrs);
 
         debug!(
             "Queue size: {}, ALUs busy: {}/{}",
@@ -39,68 +97,322 @@ impl DispatchStage {
             self.alus.len()
         );
 
-        while issued < self.issue_width && let Some(instr) = self.queue.inner.pop_front() {
+        while issued < self.issue_width
+            && self.halt.is_none()
+            && redirect.is_none()
+            && let Some(&instr) = self.queue.inner.front()
+        {
+            if !self.control.allows(instr.pc) {
+                debug!("Stall: issue halted by debugger at 0x{:08x}", instr.pc);
+                break;
+            }
+
+            if instr.is_system() && !self.is_idle() {
+                debug!("Stall: {} waits for older instructions to complete", instr);
+                break;
+            }
+
+            if instr.raw == WFI && csrs.mip & csrs.mie == 0 {
+                debug!("Stall: waiting for an interrupt");
+                break;
+            }
+
             if !self.scoreboard.can_issue(&instr) {
                 debug!("Stall: data hazard detected for {}", instr);
-                self.scoreboard.predict_issue(&instr);
-                remaining.push_back(instr);
-                continue;
+                break;
+            }
+
+            if instr.is_vector() && self.vector.queue.is_full() {
+                debug!("Stall: vector queue full for {}", instr);
+                break;
+            }
+
+            if instr.custom.is_some_and(|c| c.uses_vregs) && !(self.vector.is_idle() && self.matrix.is_idle()) {
+                debug!("Stall: {} waits for vector and matrix instructions to complete", instr);
+                break;
+            }
+
+            if instr.is_matrix()
+                && let Some(vregs) = self.matrix.registers_of(&instr, self.vector.regs.vlenb())
+                && self.vector.uses(vregs)
+            {
+                debug!("Stall: {} waits for vector instructions using its registers", instr);
+                break;
+            }
+
+            if instr.is_matrix() && !self.matrix.can_issue(&instr) {
+                debug!("Stall: matrix engine busy for {}", instr);
+                break;
+            }
+
+            // A queued vector load or store may still fault, so nothing younger outside the
+            // vector queue may execute before it, or it would run again after the handler returns
+            if !instr.is_vector() && self.vector.has_pending_memory() {
+                debug!("Stall: {} waits for queued vector memory accesses", instr);
+                break;
             }
 
             if !self.scoreboard.allocate_unit(&instr) {
                 debug!("Stall: no free execution unit for {}", instr);
-                remaining.push_back(instr);
-                continue;
+                break;
             }
 
+            self.queue.inner.pop_front();
             self.scoreboard.mark_issue(&instr);
+            self.control.issued();
             debug!("Issued: {}", instr);
 
-            match instr.opcode {
-                0b0110011 | 0b0010011 => { // ALU
+            let rs1 = regs.read(instr.rs1);
+            let rs2 = regs.read(instr.rs2);
+            let executed = match instr.opcode {
+                _ if instr.is_system() => {
+                    self.fence_i |= instr.opcode == 0b0001111 && instr.funct3 == 0b001;
+                    self.csr.issue(instr, rs1, rs2, csrs)
+                }
+                _ if instr.custom.is_some() => self.issue_custom(instr, rs1, rs2, csrs, bus, extensions),
+                0b0110011 | 0b0010011 | 0b0110111 | 0b0010111 => { // ALU
                     if let Some(unit) = self.alus.iter_mut().find(|u| !u.busy) {
-                        unit.issue(instr);
+                        unit.issue(instr, rs1, rs2);
                     }
+                    Ok(None)
                 }
-                0b1100011 => { // BRANCH
-                    if let Some(unit) = self.brus.iter_mut().find(|u| !u.busy) {
-                        unit.issue(instr);
-                    }
+                0b1100011 | 0b1101111 | 0b1100111 => { // BRANCH / JUMP
+                    self.brus.iter_mut().find(|u| !u.busy).map_or(Ok(None), |unit| unit.issue(instr, rs1, rs2))
                 }
                 0b0000011 | 0b0100011 => { // LOAD/STORE
-                    if !self.lsu.busy {
-                        self.lsu.issue(instr);
-                    }
+                    self.lsu.issue(instr, rs1, rs2, bus).map(|_| None)
+                }
+                0b1010111 | 0b0000111 | 0b0100111 => { // OP-V / VECTOR LOAD/STORE
+                    self.vector.dispatch(instr, rs1, rs2, csrs).map(|_| None)
                 }
-                _ => {}
+                _ if instr.is_matrix() => {
+                    self.matrix.issue(instr, rs1, &mut csrs.vector, &mut self.vector.regs).map(|_| None)
+                }
+                _ => Ok(None),
+            };
+            redirect = match executed {
+                Ok(target) => target,
+                Err(trap) if trap.cause == CAUSE_BREAKPOINT && Semihost::is_call(bus, instr.pc) => {
+                    self.semihost_call(&instr, regs, bus, semihost);
+                    None
+                }
+                Err(trap) => self.raise(&instr, trap, regs, csrs),
+            };
+            issued += 1;
+
+            if self.halt.is_some() {
+                debug!("Halted at 0x{:08x}: {:?}", instr.pc, self.halt);
+                self.pc = instr.pc;
+                self.queue.inner.clear();
+                break;
             }
+            self.pc = redirect.unwrap_or(instr.pc.wrapping_add(4));
 
-            issued += 1;
+            if let Some(target) = redirect {
+                debug!("Redirect to 0x{:08x}, flushing {} younger instructions", target, self.queue.inner.len());
+                self.queue.inner.clear();
+                break;
+            }
         }
 
-        if !remaining.is_empty() {
-            debug!("Re-queue {} stalled instructions", remaining.len());
+        if issued == 0 && !self.queue.inner.is_empty() {
+            self.stall_cycles += 1;
+        } else {
+            self.stall_cycles = 0;
         }
-        self.queue.inner = remaining;
 
+        let mut completed = Vec::new();
         for alu in &mut self.alus {
             if let Some(done) = alu.tick() {
-                debug!("ALU complete: {}", done);
-                self.scoreboard.mark_complete(&done);
-                self.scoreboard.release_unit(&done);
+                debug!("ALU complete: {}", done.0);
+                completed.push(done);
             }
         }
         for bru in &mut self.brus {
             if let Some(done) = bru.tick() {
-                debug!("BRU complete: {}", done);
-                self.scoreboard.mark_complete(&done);
-                self.scoreboard.release_unit(&done);
+                debug!("BRU complete: {}", done.0);
+                completed.push(done);
+            }
+        }
+        let lsu = self.lsu.tick(bus);
+        if let Some(done) = lsu.completed {
+            debug!("LSU complete: {}", done.0);
+            completed.push(done);
+        }
+        if let Some(instr) = lsu.missed {
+            debug!("LSU miss waits in an MSHR: {}", instr);
+            self.scoreboard.release_unit(&instr);
+        }
+        if let Some(done) = self.csr.tick() {
+            debug!("CSR complete: {}", done.0);
+            completed.push(done);
+        }
+        let vector = self.vector.tick(csrs, bus, self.matrix.busy_regs(), !self.lsu.is_idle());
+        for done in vector.completed {
+            debug!("Vector complete: {}", done.0);
+            completed.push(done);
+        }
+        for done in self.matrix.tick() {
+            debug!("Matrix complete: {}", done.0);
+            completed.push(done);
+        }
+        self.custom.retain_mut(|slot| {
+            if slot.remaining > 0 {
+                slot.remaining -= 1;
+                return true;
+            }
+            debug!("Custom complete: {}", slot.instr);
+            completed.push((slot.instr, slot.result));
+            false
+        });
+
+        for (instr, value) in completed {
+            if instr.writes_rd() {
+                regs.write(instr.rd, value);
+            }
+            self.scoreboard.mark_complete(&instr);
+            self.scoreboard.release_unit(&instr);
+            self.retired += 1;
+            csrs.instret += 1;
+        }
+        // Line fills complete accesses that already left the LSU
+        for (instr, value) in lsu.filled {
+            debug!("LSU fill complete: {}", instr);
+            if instr.writes_rd() {
+                regs.write(instr.rd, value);
+            }
+            self.scoreboard.mark_complete(&instr);
+            self.retired += 1;
+            csrs.instret += 1;
+        }
+
+        if let Some((instr, trap)) = vector.fault
+            && self.halt.is_none()
+        {
+            debug!("Vector fault at 0x{:08x}, flushing younger vector instructions", instr.pc);
+            for flushed in self.vector.flush() {
+                self.scoreboard.mark_complete(&flushed);
             }
+            self.queue.inner.clear();
+            redirect = self.raise(&instr, trap, regs, csrs);
+            self.pc = redirect.unwrap_or(instr.pc);
+        }
+
+        redirect
+    }
+
+    /// Execute a custom instruction through the extension that decoded it, holding its
+    /// result until its latency elapses
+    fn issue_custom(
+        &mut self,
+        instr: Instruction,
+        rs1: u32,
+        rs2: u32,
+        csrs: &mut CsrFile,
+        bus: &mut DataBus,
+        extensions: &mut Extensions,
+    ) -> Result<Option<u32>, Trap> {
+        let mut ctx = CustomContext { instr: &instr, rs1, rs2, csrs, bus, vregs: &mut self.vector.regs };
+        let result = extensions
+            .execute(&mut ctx)
+            .unwrap_or(Err(Trap { cause: CAUSE_ILLEGAL_INSTRUCTION, tval: instr.raw }))?;
+        let latency = instr.custom.map_or(1, |c| c.latency);
+        self.custom.push(CustomSlot { instr, remaining: latency, result });
+        Ok(None)
+    }
+
+    /// Take the highest-priority pending interrupt before the next instruction in program order
+    /// issues, returning the handler address. Younger queued instructions are discarded.
+    fn take_interrupt(&mut self, csrs: &mut CsrFile) -> Option<u32> {
+        if self.halt.is_some() || self.control.budget == Some(0) || !csrs.has_trap_handler() {
+            return None;
+        }
+        let irq = csrs.pending_interrupt()?;
+        // A `wfi` waiting at the head of the queue completes, so the handler returns past it
+        let waiting = self.queue.inner.front().is_some_and(|i| i.raw == WFI && i.pc == self.pc);
+        let epc = if waiting { self.pc.wrapping_add(4) } else { self.pc };
+        let handler = csrs.enter_trap(Trap { cause: CAUSE_INTERRUPT | irq, tval: 0 }, epc);
+        debug!("Interrupt {} at 0x{:08x}, flushing {} queued instructions", irq, epc, self.queue.inner.len());
+        self.queue.inner.clear();
+        self.pc = handler;
+        Some(handler)
+    }
+
+    /// Service a semihosting `ebreak` in place of the breakpoint exception.
+    /// Execution continues with the marker instruction after it.
+    fn semihost_call(&mut self, instr: &Instruction, regs: &mut RegisterFile, bus: &mut DataBus, semihost: &mut Semihost) {
+        self.scoreboard.mark_complete(instr);
+        self.scoreboard.release_unit(instr);
+        match semihost.call(regs.read(10), regs.read(11), bus) {
+            SemihostResult::Return(Some(value)) => regs.write(10, value),
+            SemihostResult::Return(None) => {}
+            SemihostResult::Exit(code) => self.halt = Some(Halt::Exit(code)),
         }
-        if let Some(done) = self.lsu.tick() {
-            debug!("LSU complete: {}", done);
-            self.scoreboard.mark_complete(&done);
-            self.scoreboard.release_unit(&done);
+    }
+
+    /// Take an exception raised by `instr` at issue, returning the trap handler address.
+    /// With no handler installed, `ecall` exits with the code in `a0` and any other
+    /// exception halts the core.
+    fn raise(&mut self, instr: &Instruction, trap: Trap, regs: &RegisterFile, csrs: &mut CsrFile) -> Option<u32> {
+        // The faulting instruction never reaches a unit, so it frees its resources now
+        self.scoreboard.mark_complete(instr);
+        self.scoreboard.release_unit(instr);
+
+        if csrs.has_trap_handler() {
+            debug!("Trap cause {} at 0x{:08x}, tval 0x{:08x}", trap.cause, instr.pc, trap.tval);
+            return Some(csrs.enter_trap(trap, instr.pc));
+        }
+        self.halt = Some(match trap.cause {
+            CAUSE_ECALL_M => Halt::Exit(regs.read(10)),
+            CAUSE_BREAKPOINT => Halt::Breakpoint,
+            _ => Halt::Trap(trap),
+        });
+        None
+    }
+
+    /// Whether no instruction is executing in any unit
+    pub fn is_idle(&self) -> bool {
+        self.lsu.is_idle() && !self.csr.busy && self.vector.is_idle() && self.matrix.is_idle() && self.custom.is_empty() && self.alus.iter().all(|u| !u.busy) && self.brus.iter().all(|u| !u.busy)
+    }
+}
+
+impl Default for DispatchStage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Debugger controls applied at the issue point
+#[derive(Default)]
+pub struct IssueControl {
+    /// PCs that stop issue before the instruction executes
+    pub breakpoints: BTreeSet<u32>,
+    /// Number of instructions still allowed to issue, unlimited if `None`
+    pub budget: Option<u64>,
+    /// Breakpoint to step over when resuming from it
+    pub resume_pc: Option<u32>,
+    /// Breakpoint PC that stopped issue, if any
+    pub hit: Option<u32>,
+}
+
+impl IssueControl {
+    /// Whether the instruction at `pc` may issue, recording a breakpoint hit otherwise
+    pub fn allows(&mut self, pc: u32) -> bool {
+        if self.budget == Some(0) {
+            return false;
+        }
+        if self.breakpoints.contains(&pc) && self.resume_pc != Some(pc) {
+            self.hit = Some(pc);
+            return false;
+        }
+        true
+    }
+
+    /// Account for one issued instruction
+    pub fn issued(&mut self) {
+        self.resume_pc = None;
+        if let Some(budget) = &mut self.budget {
+            *budget -= 1;
         }
     }
 }
@@ -1,39 +1,93 @@
+use std::collections::VecDeque;
 use crate::common::io::{Future, Poll};
-use crate::scalar::instruction::InstructionBuffer;
+use crate::scalar::icache::{ICache, ICacheRead};
+use crate::scalar::instruction::{InstructionBuffer, RawInstruction};
 use crate::scalar::memory::{Itcm, ItcmRead};
 
-/// The FetchStage struct represents the fetch stage of the scalar pipeline
+/// Default number of fetch blocks requested ahead of the instruction buffer
+pub const DEFAULT_FETCH_DEPTH: usize = 2;
+
+/// A fetch-block request in flight
+pub enum FetchRead {
+    /// Block read from the ITCM
+    Itcm(ItcmRead),
+    /// Rest of a line read through the instruction cache
+    Cache(ICacheRead),
+}
+
+/// The FetchStage struct represents the fetch stage of the scalar pipeline.
+///
+/// It requests fetch blocks along the sequential path, from the ITCM or, for code in
+/// external memory, through the instruction cache, keeping up to `depth` blocks in
+/// flight or waiting for the instruction buffer. Instructions move into the buffer in
+/// program order as blocks arrive and space allows.
 pub struct FetchStage {
-    pub pcs: [u32; 4],
-    pub pending_reads: [Option<ItcmRead>; 4],
+    /// Address of the next instruction to request
+    pub pc: u32,
+    /// Requested blocks, oldest first
+    pub pending_reads: VecDeque<FetchRead>,
+    /// Fetched instructions waiting for space in the instruction buffer
+    pub fetched: VecDeque<RawInstruction>,
+    pub depth: usize,
+    /// Cycles the instruction buffer had space but no fetched instruction to fill it
+    pub starved_cycles: u64,
 }
 
 impl FetchStage {
-    /// Creates a new FetchStage instance with initial program counters and empty pending reads
+    /// Creates a new FetchStage instance starting at address zero with no requests in flight
     pub fn new() -> Self {
         Self {
-            pcs: [0, 4, 8, 12],
-            pending_reads: [None; 4],
+            pc: 0,
+            pending_reads: VecDeque::new(),
+            fetched: VecDeque::new(),
+            depth: DEFAULT_FETCH_DEPTH,
+            starved_cycles: 0,
         }
     }
 
-    /// Advances the fetch stage by one tick, fetching instructions from ITCM and pushing them to the instruction buffer
-    pub fn tick(&mut self, instr_buffer: &mut InstructionBuffer, itcm: &mut Itcm) {
-        for lane in 0..4 {
-            match &mut self.pending_reads[lane] {
-                None => {
-                    self.pending_reads[lane] = Some(itcm.read(self.pcs[lane]));
+    /// Advances the fetch stage by one tick, fetching instructions from ITCM or the instruction cache and pushing them to the instruction buffer
+    pub fn tick(&mut self, instr_buffer: &mut InstructionBuffer, itcm: &mut Itcm, icache: &mut ICache) {
+        while let Some(read) = self.pending_reads.front_mut() {
+            let polled = match read {
+                FetchRead::Itcm(read) => read.poll(itcm),
+                FetchRead::Cache(read) => read.poll(icache),
+            };
+            match polled {
+                Poll::Ready(block) => {
+                    self.fetched.extend(block);
+                    self.pending_reads.pop_front();
                 }
-                Some(pending) => match pending.poll(itcm) {
-                    Poll::Ready(instr) => {
-                        self.pending_reads[lane] = None;
-                        instr_buffer.push(instr);
-                        self.pcs[lane] += 4 * 4;
-                        self.pending_reads[lane] = Some(itcm.read(self.pcs[lane]));
-                    }
-                    Poll::Pending => {}
-                },
+                Poll::Pending => break,
             }
         }
+
+        if self.fetched.is_empty() && !instr_buffer.is_full() {
+            self.starved_cycles += 1;
+        }
+        while !instr_buffer.is_full() && let Some(instr) = self.fetched.pop_front() {
+            instr_buffer.push(instr);
+        }
+
+        let block_instrs = (itcm.block_bytes / 4).max(1) as usize;
+        while self.pending_reads.len() + self.fetched.len().div_ceil(block_instrs) < self.depth.max(1) {
+            let read = if icache.caches(self.pc) {
+                let read = icache.read(self.pc);
+                self.pc = read.end(icache.config.line_bytes);
+                FetchRead::Cache(read)
+            } else {
+                let read = itcm.read(self.pc);
+                self.pc = read.end(itcm.block_bytes);
+                FetchRead::Itcm(read)
+            };
+            self.pending_reads.push_back(read);
+        }
+    }
+
+    /// Restart fetching from the given PC, dropping in-flight reads
+    pub fn redirect(&mut self, pc: u32, itcm: &mut Itcm) {
+        self.pc = pc;
+        self.pending_reads.clear();
+        self.fetched.clear();
+        itcm.flush();
     }
 }
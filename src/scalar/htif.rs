@@ -0,0 +1,99 @@
+use std::io::Write;
+use tracing::warn;
+use crate::common::elf::SymbolTable;
+use crate::scalar::memory::DataBus;
+
+/// Proxy syscall numbers understood through the `magic_mem` block
+const SYS_WRITE: u64 = 64;
+const SYS_EXIT: u64 = 93;
+/// Result returned for proxy syscalls the host does not implement
+const ENOSYS: i64 = 38;
+
+/// HTIF-style host interface. The program writes a command to the 64-bit `tohost`
+/// word and the host acknowledges it through `fromhost`, both located in DTCM.
+/// An RV32 program writes the high half first, the non-zero low half triggers the command.
+///
+/// Commands carry a device in bits 63:56, a command in bits 55:48 and a payload below:
+/// - device 0, command 0, odd payload: exit with code `payload >> 1`, so 1 means pass
+/// - device 0, command 0, even payload: address of a `magic_mem` proxy syscall block
+/// - device 1, command 1: write the low payload byte to the console
+pub struct Htif {
+    pub tohost: u32,
+    pub fromhost: u32,
+}
+
+impl Htif {
+    /// Locate `tohost` and `fromhost` from a program's symbols
+    pub fn from_symbols(symbols: &SymbolTable) -> Option<Self> {
+        let tohost = symbols.get("tohost")?.addr;
+        let fromhost = symbols.get("fromhost").map_or(tohost + 8, |s| s.addr);
+        Some(Self { tohost, fromhost })
+    }
+
+    /// Service a pending command, returning the exit code once the program exits
+    pub fn poll(&self, bus: &mut DataBus) -> Option<u32> {
+        if bus.load(self.tohost, 4)? == 0 {
+            return None;
+        }
+        let command = load_u64(bus, self.tohost)?;
+        let device = command >> 56;
+        let cmd = (command >> 48) & 0xFF;
+        let payload = command & 0xFFFF_FFFF_FFFF;
+
+        match (device, cmd) {
+            (0, 0) if payload & 1 != 0 => return Some((payload >> 1) as u32),
+            (0, 0) => {
+                let magic_mem = payload as u32;
+                let args: Vec<u64> = (0..4).filter_map(|i| load_u64(bus, magic_mem + 8 * i)).collect();
+                if let [SYS_EXIT, code, ..] = args[..] {
+                    return Some(code as u32);
+                }
+                let result = match args[..] {
+                    [SYS_WRITE, fd, buf, len] => syscall_write(bus, fd, buf as u32, len as u32),
+                    _ => {
+                        warn!("Unsupported HTIF syscall {:?}", args.first());
+                        -ENOSYS
+                    }
+                };
+                store_u64(bus, magic_mem, result as u64);
+            }
+            (1, 1) => {
+                let mut stdout = std::io::stdout().lock();
+                let _ = stdout.write_all(&[payload as u8]).and_then(|_| stdout.flush());
+            }
+            _ => warn!("Unsupported HTIF command 0x{:016x}", command),
+        }
+
+        store_u64(bus, self.tohost, 0);
+        store_u64(bus, self.fromhost, (device << 56) | (cmd << 48) | 1);
+        None
+    }
+}
+
+/// Proxy `write` to the host's stdout or stderr, returning the byte count or a negative errno
+fn syscall_write(bus: &mut DataBus, fd: u64, buf: u32, len: u32) -> i64 {
+    let Some(bytes) = (0..len).map(|i| bus.load(buf.wrapping_add(i), 1).map(|b| b as u8)).collect::<Option<Vec<u8>>>()
+    else {
+        return -14; // EFAULT
+    };
+    let written = match fd {
+        1 => std::io::stdout().lock().write_all(&bytes),
+        2 => std::io::stderr().lock().write_all(&bytes),
+        _ => return -9, // EBADF
+    };
+    match written {
+        Ok(()) => len as i64,
+        Err(_) => -5, // EIO
+    }
+}
+
+fn load_u64(bus: &mut DataBus, addr: u32) -> Option<u64> {
+    let low = bus.load(addr, 4)?;
+    let high = bus.load(addr.wrapping_add(4), 4)?;
+    Some((high as u64) << 32 | low as u64)
+}
+
+fn store_u64(bus: &mut DataBus, addr: u32, value: u64) {
+    bus.store(addr, 4, value as u32);
+    bus.store(addr.wrapping_add(4), 4, (value >> 32) as u32);
+}
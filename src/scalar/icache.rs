@@ -0,0 +1,420 @@
+use tracing::debug;
+use crate::common::axi::{AxiRead, ExternalMemory};
+use crate::common::io::{Future, Poll};
+use crate::scalar::instruction::RawInstruction;
+use crate::scalar::memory::DRAM_BASE;
+
+/// Line replacement policy within a set
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Replacement {
+    /// Evict the least recently used line
+    Lru,
+    /// Evict the line filled first
+    Fifo,
+    /// Evict a pseudo-random line
+    Random,
+}
+
+/// Geometry and timing of the instruction cache
+#[derive(Copy, Clone, Debug)]
+pub struct ICacheConfig {
+    /// Capacity in bytes
+    pub size: u32,
+    /// Bytes per line, a power of two of at least 4
+    pub line_bytes: u32,
+    pub ways: u32,
+    pub replacement: Replacement,
+    /// Cycles from a lookup until a hit's instructions are available
+    pub hit_latency: u8,
+    /// Fetch the next line when a line misses or a prefetched line is first used
+    pub prefetch: bool,
+}
+
+impl Default for ICacheConfig {
+    fn default() -> Self {
+        Self { size: 8 * 1024, line_bytes: 32, ways: 2, replacement: Replacement::Lru, hit_latency: 1, prefetch: false }
+    }
+}
+
+/// Lookup and fill counts of the instruction cache
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ICacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Valid lines replaced by fills
+    pub evictions: u64,
+    /// Next-line prefetches issued
+    pub prefetches: u64,
+    /// Prefetched lines later hit by a fetch
+    pub useful_prefetches: u64,
+}
+
+/// A valid cache line
+struct Line {
+    /// Address of the first byte
+    addr: u32,
+    data: Vec<u8>,
+    /// Cycles of the last lookup hitting the line and of its fill
+    last_used: u64,
+    filled: u64,
+    /// Whether the line was prefetched and has not been used yet
+    prefetched: bool,
+}
+
+/// A line fill, requested on a miss or prefetch and issued to external memory on the next tick
+struct Fill {
+    addr: u32,
+    read: Option<AxiRead>,
+    prefetch: bool,
+}
+
+/// Set-associative instruction cache in front of fetch for code in external memory.
+///
+/// The tag array takes one lookup per cycle. A hit delivers the rest of the line
+/// `hit_latency` cycles after the lookup, while a miss requests the line from the
+/// external memory and delivers it once the fill arrives. Fills of a line already on
+/// its way are merged. Lines are not kept coherent with stores, `fence.i` invalidates
+/// the whole cache.
+pub struct ICache {
+    pub config: ICacheConfig,
+    pub stats: ICacheStats,
+    /// Valid lines of each set, at most `ways` per set
+    sets: Vec<Vec<Line>>,
+    /// Requested and in-flight fills, oldest first
+    fills: Vec<Fill>,
+    /// First cycle the tag array can take another lookup
+    lookup_free: u64,
+    /// Current cycle
+    now: u64,
+    /// State of the xorshift generator picking random victims
+    seed: u32,
+}
+
+impl ICache {
+    pub fn new(config: ICacheConfig) -> Self {
+        Self {
+            config,
+            stats: ICacheStats::default(),
+            sets: Vec::new(),
+            fills: Vec::new(),
+            lookup_free: 0,
+            now: 0,
+            seed: 0x2545_f491,
+        }
+    }
+
+    /// Whether fetches from `addr` go through the cache rather than the ITCM
+    pub fn caches(&self, addr: u32) -> bool {
+        addr >= DRAM_BASE
+    }
+
+    /// Number of sets
+    pub fn num_sets(&self) -> usize {
+        (self.config.size / (self.config.line_bytes * self.config.ways.max(1))).max(1) as usize
+    }
+
+    /// Address of the line holding `addr`
+    fn line_of(&self, addr: u32) -> u32 {
+        addr & !(self.config.line_bytes - 1)
+    }
+
+    /// The set and position within it of the line at `addr`, if it is present
+    fn find(&self, addr: u32) -> Option<(usize, usize)> {
+        let set = (addr / self.config.line_bytes) as usize % self.num_sets();
+        let way = self.sets.get(set)?.iter().position(|line| line.addr == addr)?;
+        Some((set, way))
+    }
+
+    /// Look up `addr`, returning the read of the instructions from `addr` to the end of its line
+    pub fn read(&mut self, addr: u32) -> ICacheRead {
+        let line = self.line_of(addr);
+        let lookup = self.now.max(self.lookup_free);
+        self.lookup_free = lookup + 1;
+
+        let prefetch_next = match self.find(line) {
+            Some((set, way)) => {
+                self.stats.hits += 1;
+                let entry = &mut self.sets[set][way];
+                entry.last_used = lookup;
+                let first_use = std::mem::take(&mut entry.prefetched);
+                self.stats.useful_prefetches += first_use as u64;
+                first_use
+            }
+            None => {
+                debug!("I-cache miss addr=0x{:08x}", addr);
+                self.stats.misses += 1;
+                self.request(line, false);
+                true
+            }
+        };
+        if prefetch_next && self.config.prefetch {
+            self.request(line.wrapping_add(self.config.line_bytes), true);
+        }
+        ICacheRead { addr, ready_at: lookup + self.config.hit_latency.max(1) as u64 }
+    }
+
+    /// Request a fill of the line at `addr` unless it is present or already requested
+    fn request(&mut self, addr: u32, prefetch: bool) {
+        if !self.caches(addr) || self.find(addr).is_some() || self.fills.iter().any(|fill| fill.addr == addr) {
+            return;
+        }
+        self.stats.prefetches += prefetch as u64;
+        self.fills.push(Fill { addr, read: None, prefetch });
+    }
+
+    /// Drop every line
+    pub fn invalidate(&mut self) {
+        self.sets.iter_mut().for_each(Vec::clear);
+    }
+
+    /// Issue requested fills to external memory, install the fills that arrived and
+    /// advance to the next cycle
+    pub fn tick(&mut self, dram: &mut ExternalMemory) {
+        let line_bytes = self.config.line_bytes;
+        let mut arrived = Vec::new();
+        self.fills.retain_mut(|fill| {
+            // Lines outside the DRAM read as zeros, which decode as illegal instructions
+            if !dram.contains(fill.addr) || !dram.contains(fill.addr.wrapping_add(line_bytes - 1)) {
+                arrived.push((fill.addr, vec![0; line_bytes as usize], fill.prefetch));
+                return false;
+            }
+            let read = fill.read.get_or_insert_with(|| dram.read(fill.addr, line_bytes));
+            match read.poll(dram) {
+                Poll::Ready(data) => {
+                    arrived.push((fill.addr, data, fill.prefetch));
+                    false
+                }
+                Poll::Pending => true,
+            }
+        });
+        for (addr, data, prefetched) in arrived {
+            self.install(addr, data, prefetched);
+        }
+        self.now += 1;
+    }
+
+    /// Place a filled line in its set, evicting a victim if the set is full
+    fn install(&mut self, addr: u32, data: Vec<u8>, prefetched: bool) {
+        let sets = self.num_sets();
+        self.sets.resize_with(sets, Vec::new);
+        let index = (addr / self.config.line_bytes) as usize % sets;
+        let ways = self.config.ways.max(1) as usize;
+        if self.sets[index].len() >= ways {
+            let set = &self.sets[index];
+            let victim = match self.config.replacement {
+                Replacement::Lru => (0..set.len()).min_by_key(|&way| set[way].last_used).unwrap_or(0),
+                Replacement::Fifo => (0..set.len()).min_by_key(|&way| set[way].filled).unwrap_or(0),
+                Replacement::Random => {
+                    self.seed ^= self.seed << 13;
+                    self.seed ^= self.seed >> 17;
+                    self.seed ^= self.seed << 5;
+                    self.seed as usize % set.len()
+                }
+            };
+            self.sets[index].remove(victim);
+            self.stats.evictions += 1;
+        }
+        self.sets[index].push(Line { addr, data, last_used: self.now, filled: self.now, prefetched });
+    }
+}
+
+impl Default for ICache {
+    fn default() -> Self {
+        Self::new(ICacheConfig::default())
+    }
+}
+
+/// Instruction cache read future
+pub struct ICacheRead {
+    /// Address of the first instruction wanted, the read extends to the end of its line
+    pub addr: u32,
+    /// Cycle the lookup result is available
+    ready_at: u64,
+}
+
+impl ICacheRead {
+    /// Address just past the last instruction of the read
+    pub fn end(&self, line_bytes: u32) -> u32 {
+        (self.addr & !(line_bytes - 1)).wrapping_add(line_bytes)
+    }
+}
+
+impl Future for ICacheRead {
+    /// Raw instructions of the line from `addr` on
+    type Output = Vec<RawInstruction>;
+    /// Instruction cache as input context
+    type Input = ICache;
+
+    /// Poll the read, waiting for the line's fill on a miss
+    fn poll(&mut self, context: &mut Self::Input) -> Poll<Self::Output> {
+        if context.now < self.ready_at {
+            return Poll::Pending;
+        }
+        let line = context.line_of(self.addr);
+        let Some((set, way)) = context.find(line) else {
+            // Refill a line evicted before the read could take it
+            context.request(line, false);
+            return Poll::Pending;
+        };
+        let data = &context.sets[set][way].data;
+        let instrs = (self.addr..self.end(context.config.line_bytes)).step_by(4).map(|pc| {
+            let offset = (pc - line) as usize;
+            let word = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+            RawInstruction { pc, data: word }
+        });
+        Poll::Ready(instrs.collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::asm::assemble;
+    use crate::scalar::core::ScalarFrontend;
+    use crate::scalar::dispatch::Halt;
+
+    /// External memory whose words at the start of the DRAM hold their own index
+    fn dram() -> ExternalMemory {
+        let mut dram = ExternalMemory::default();
+        for i in 0..64 {
+            dram.store(DRAM_BASE + 4 * i, 4, i);
+        }
+        dram
+    }
+
+    /// Tick until a read completes, returning the cycles it took and its instructions
+    fn wait(cache: &mut ICache, dram: &mut ExternalMemory, mut read: ICacheRead) -> (u64, Vec<RawInstruction>) {
+        for cycles in 0..1_000 {
+            if let Poll::Ready(instrs) = read.poll(cache) {
+                return (cycles, instrs);
+            }
+            cache.tick(dram);
+            dram.tick();
+        }
+        panic!("read of 0x{:08x} never completed", read.addr);
+    }
+
+    /// Tick until every requested fill has been installed
+    fn settle(cache: &mut ICache, dram: &mut ExternalMemory) {
+        while !cache.fills.is_empty() {
+            cache.tick(dram);
+            dram.tick();
+        }
+    }
+
+    #[test]
+    fn miss_waits_for_the_fill_and_hit_for_the_lookup() {
+        let mut dram = dram();
+        let mut cache = ICache::default();
+        let read = cache.read(DRAM_BASE + 8);
+        let (cycles, instrs) = wait(&mut cache, &mut dram, read);
+        // The fill goes out on the next tick, then takes the read latency and 4 beats
+        assert_eq!(cycles, 1 + 20 + 4);
+        let words: Vec<_> = instrs.iter().map(|i| (i.pc - DRAM_BASE, i.data)).collect();
+        assert_eq!(words, vec![(8, 2), (12, 3), (16, 4), (20, 5), (24, 6), (28, 7)]);
+
+        let read = cache.read(DRAM_BASE);
+        let (cycles, instrs) = wait(&mut cache, &mut dram, read);
+        assert_eq!(cycles, 1);
+        assert_eq!(instrs.len(), 8);
+        assert_eq!((cache.stats.hits, cache.stats.misses), (1, 1));
+    }
+
+    #[test]
+    fn next_line_prefetch_counts_its_first_use() {
+        let mut dram = dram();
+        let mut cache = ICache::new(ICacheConfig { prefetch: true, ..ICacheConfig::default() });
+        let read = cache.read(DRAM_BASE);
+        assert_eq!(cache.stats.prefetches, 1);
+        wait(&mut cache, &mut dram, read);
+        settle(&mut cache, &mut dram);
+
+        // The first use of the prefetched line prefetches the one after it
+        let read = cache.read(DRAM_BASE + 32);
+        let (cycles, _) = wait(&mut cache, &mut dram, read);
+        assert_eq!(cycles, 1);
+        assert_eq!((cache.stats.useful_prefetches, cache.stats.prefetches), (1, 2));
+        let read = cache.read(DRAM_BASE + 36);
+        wait(&mut cache, &mut dram, read);
+        assert_eq!((cache.stats.useful_prefetches, cache.stats.misses), (1, 1));
+    }
+
+    #[test]
+    fn replacement_picks_the_victim_by_policy() {
+        let (a, b, c) = (DRAM_BASE, DRAM_BASE + 32, DRAM_BASE + 64);
+        for (replacement, evicted) in [(Replacement::Lru, b), (Replacement::Fifo, a)] {
+            let mut dram = dram();
+            // A single set of two ways
+            let config = ICacheConfig { size: 64, line_bytes: 32, ways: 2, replacement, ..ICacheConfig::default() };
+            let mut cache = ICache::new(config);
+            for addr in [a, b, a, c] {
+                let read = cache.read(addr);
+                wait(&mut cache, &mut dram, read);
+            }
+            assert_eq!(cache.stats.evictions, 1, "{:?}", replacement);
+            for addr in [a, b, c] {
+                assert_eq!(cache.find(addr).is_none(), addr == evicted, "{:?} line 0x{:08x}", replacement, addr);
+            }
+        }
+    }
+
+    #[test]
+    fn line_evicted_before_its_read_is_taken_is_refilled() {
+        let mut dram = dram();
+        let config = ICacheConfig { size: 32, line_bytes: 32, ways: 1, ..ICacheConfig::default() };
+        let mut cache = ICache::new(config);
+        let first = cache.read(DRAM_BASE);
+        settle(&mut cache, &mut dram);
+        let second = cache.read(DRAM_BASE + 32);
+        wait(&mut cache, &mut dram, second);
+        assert!(cache.find(DRAM_BASE).is_none());
+
+        let (_, instrs) = wait(&mut cache, &mut dram, first);
+        assert_eq!(instrs.first().map(|i| i.data), Some(0));
+        assert_eq!(cache.stats.evictions, 2);
+    }
+
+    #[test]
+    fn invalidate_drops_every_line() {
+        let mut dram = dram();
+        let mut cache = ICache::default();
+        let read = cache.read(DRAM_BASE);
+        wait(&mut cache, &mut dram, read);
+        cache.invalidate();
+        let read = cache.read(DRAM_BASE);
+        let (cycles, _) = wait(&mut cache, &mut dram, read);
+        assert!(cycles > 1, "the line was refetched");
+        assert_eq!((cache.stats.hits, cache.stats.misses), (0, 2));
+    }
+
+    /// Run a program placed at the start of the DRAM, returning the I-cache counts
+    fn run(source: &str) -> ICacheStats {
+        let program = assemble(&format!(".org 0x{:x}\n{}", DRAM_BASE, source)).expect("program assembles");
+        let mut core = ScalarFrontend::new();
+        core.load_program(&program.segments, DRAM_BASE, &program.symbols).unwrap();
+        assert!(matches!(core.run(10_000), Some(Halt::Exit(0))));
+        core.icache.stats
+    }
+
+    #[test]
+    fn program_in_dram_runs_from_the_cache_until_fence_i() {
+        let program = |sync: &str| {
+            run(&format!(
+                "    li   t0, 3
+                 loop:
+                     addi t0, t0, -1
+                     {}
+                     bnez t0, loop
+                     li   a0, 0
+                     ecall",
+                sync
+            ))
+        };
+        let plain = program("nop");
+        let fenced = program("fence.i");
+        // Fetch runs ahead into the lines past the program, which miss too
+        assert_eq!((plain.hits, plain.misses, plain.evictions), (4, 6, 0));
+        // Each `fence.i` refetches the rest of its line and the line fetch ran ahead into
+        assert_eq!(fenced.misses, plain.misses + 2 * 3);
+    }
+}
@@ -1,21 +1,28 @@
 use std::collections::VecDeque;
 use std::fmt::{Debug, Display, Formatter};
+use crate::matrix::decode as matrix;
+use crate::scalar::custom::{CustomInfo, CUSTOM_0};
+use crate::scalar::disasm::disassemble;
+use crate::vector::decode::{self as vector, LOAD_FP, OPFVF, OPIVX, OPMVX, STORE_FP};
 
 /// A raw RISC-V instruction.
 #[derive(Copy, Clone, Default)]
 pub struct RawInstruction {
+    pub pc: u32,
     pub data: u32
 }
 
 /// The type of RISC-V instruction.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum InstructionType {
-    R, I, S, B, U, J, Unknown
+    R, I, S, B, U, J, V, M, Unknown
 }
 
 /// A decoded RISC-V instruction.
 #[derive(Copy, Clone, Debug)]
 pub struct Instruction {
+    pub pc: u32,
+    pub raw: u32,
     pub opcode: u8,
     pub rd: u8,
     pub rs1: u8,
@@ -24,40 +31,144 @@ pub struct Instruction {
     pub funct7: u8,
     pub imm: i32,
     pub typ: InstructionType,
+    /// Scheduling info of a custom instruction claimed by a registered extension
+    pub custom: Option<CustomInfo>,
 }
 
 impl Instruction {
     /// Get the mnemonic of the instruction.
-    fn mnemonic(&self) -> &'static str {
+    pub fn mnemonic(&self) -> &'static str {
+        if let Some(custom) = self.custom {
+            return custom.mnemonic;
+        }
         match (self.opcode, self.funct3, self.funct7) {
             (0b0110011, 0b000, 0b0000000) => "add",
             (0b0110011, 0b000, 0b0100000) => "sub",
-            (0b0110011, 0b111, _) => "and",
-            (0b0110011, 0b110, _) => "or",
-            (0b0110011, 0b100, _) => "xor",
-            (0b0110011, 0b001, _) => "sll",
+            (0b0110011, 0b111, 0b0000000) => "and",
+            (0b0110011, 0b110, 0b0000000) => "or",
+            (0b0110011, 0b100, 0b0000000) => "xor",
+            (0b0110011, 0b001, 0b0000000) => "sll",
+            (0b0110011, 0b010, 0b0000000) => "slt",
+            (0b0110011, 0b011, 0b0000000) => "sltu",
             (0b0110011, 0b101, 0b0000000) => "srl",
             (0b0110011, 0b101, 0b0100000) => "sra",
 
+            (0b0110011, 0b000, 0b0000001) => "mul",
+            (0b0110011, 0b001, 0b0000001) => "mulh",
+            (0b0110011, 0b010, 0b0000001) => "mulhsu",
+            (0b0110011, 0b011, 0b0000001) => "mulhu",
+            (0b0110011, 0b100, 0b0000001) => "div",
+            (0b0110011, 0b101, 0b0000001) => "divu",
+            (0b0110011, 0b110, 0b0000001) => "rem",
+            (0b0110011, 0b111, 0b0000001) => "remu",
+
             (0b0010011, 0b000, _) => "addi",
             (0b0010011, 0b010, _) => "slti",
+            (0b0010011, 0b011, _) => "sltiu",
+            (0b0010011, 0b100, _) => "xori",
             (0b0010011, 0b111, _) => "andi",
             (0b0010011, 0b110, _) => "ori",
+            (0b0010011, 0b001, _) => "slli",
+            (0b0010011, 0b101, 0b0000000) => "srli",
+            (0b0010011, 0b101, 0b0100000) => "srai",
 
+            (0b0000011, 0b000, _) => "lb",
+            (0b0000011, 0b001, _) => "lh",
             (0b0000011, 0b010, _) => "lw",
+            (0b0000011, 0b100, _) => "lbu",
+            (0b0000011, 0b101, _) => "lhu",
+            (0b0100011, 0b000, _) => "sb",
+            (0b0100011, 0b001, _) => "sh",
             (0b0100011, 0b010, _) => "sw",
 
             (0b1100011, 0b000, _) => "beq",
             (0b1100011, 0b001, _) => "bne",
+            (0b1100011, 0b100, _) => "blt",
+            (0b1100011, 0b101, _) => "bge",
+            (0b1100011, 0b110, _) => "bltu",
+            (0b1100011, 0b111, _) => "bgeu",
 
             (0b0110111, _, _) => "lui",
             (0b0010111, _, _) => "auipc",
             (0b1101111, _, _) => "jal",
             (0b1100111, _, _) => "jalr",
 
+            (0b1010111, 0b111, 0b0000000..=0b0111111) => "vsetvli",
+            (0b1010111, 0b111, 0b1100000..=0b1111111) => "vsetivli",
+            (0b1010111, 0b111, 0b1000000) => "vsetvl",
+            (0b1010111, _, _) => vector::mnemonic(self).unwrap_or("unknown"),
+            (LOAD_FP | STORE_FP, _, _) => vector::mem_mnemonic(self).unwrap_or("unknown"),
+            (CUSTOM_0, _, _) => matrix::mnemonic(self).unwrap_or("unknown"),
+
             _ => "unknown",
         }
     }
+
+    /// Whether the instruction executes in the CSR unit: SYSTEM and FENCE opcodes,
+    /// vector configuration, plus any encoding no other unit implements, which raises
+    /// illegal instruction.
+    pub fn is_system(&self) -> bool {
+        matches!(self.opcode, 0b1110011 | 0b0001111) || self.is_vset() || self.mnemonic() == "unknown"
+    }
+
+    /// Whether the instruction is `vsetvli`, `vsetivli` or `vsetvl`.
+    pub fn is_vset(&self) -> bool {
+        self.opcode == 0b1010111 && self.funct3 == 0b111
+    }
+
+    /// Whether the instruction executes in a vector unit.
+    pub fn is_vector(&self) -> bool {
+        matches!(self.opcode, 0b1010111 | LOAD_FP | STORE_FP) && !self.is_vset()
+    }
+
+    /// Whether the instruction executes in the matrix engine.
+    pub fn is_matrix(&self) -> bool {
+        matrix::MatrixOp::decode(self).is_some()
+    }
+
+    /// Whether the instruction writes its `rd` field.
+    pub fn writes_rd(&self) -> bool {
+        if let Some(custom) = self.custom {
+            return custom.writes_rd;
+        }
+        match self.typ {
+            InstructionType::R | InstructionType::I | InstructionType::U | InstructionType::J => true,
+            InstructionType::V => self.is_vset() || matches!(self.mnemonic(), "vmv.x.s" | "vcpop.m" | "vfirst.m"),
+            _ => false,
+        }
+    }
+
+    /// Whether the instruction reads its `rs1` field.
+    pub fn reads_rs1(&self) -> bool {
+        if let Some(custom) = self.custom {
+            return custom.reads_rs1;
+        }
+        match self.typ {
+            InstructionType::R | InstructionType::I | InstructionType::S | InstructionType::B => true,
+            InstructionType::V => match self.opcode {
+                LOAD_FP | STORE_FP => true,
+                _ => matches!(self.mnemonic(), "vsetvli" | "vsetvl") || matches!(self.funct3, OPIVX | OPMVX | OPFVF),
+            },
+            // Requantizing readouts take their shift from `x[rs1]`
+            InstructionType::M => self.mnemonic().starts_with("mread.") && self.mnemonic() != "mread.w",
+            _ => false,
+        }
+    }
+
+    /// Whether the instruction reads its `rs2` field.
+    pub fn reads_rs2(&self) -> bool {
+        if let Some(custom) = self.custom {
+            return custom.reads_rs2;
+        }
+        match self.typ {
+            InstructionType::R | InstructionType::S | InstructionType::B => true,
+            InstructionType::V => match self.opcode {
+                LOAD_FP | STORE_FP => (self.funct7 >> 1) & 0b11 == 0b10, // strided
+                _ => self.mnemonic() == "vsetvl",
+            },
+            _ => false,
+        }
+    }
 }
 
 impl From<RawInstruction> for Instruction {
@@ -74,6 +185,8 @@ impl From<RawInstruction> for Instruction {
             0b0110011 => (InstructionType::R, 0), // add, sub, and, or, etc
             0b0010011 => (InstructionType::I, (data as i32) >> 20),
             0b0000011 => (InstructionType::I, (data as i32) >> 20), // load
+            0b1100111 => (InstructionType::I, (data as i32) >> 20), // jalr
+            0b0001111 | 0b1110011 => (InstructionType::I, (data as i32) >> 20), // fence / system
             0b0100011 => {
                 // store: imm[11:5 | 4:0]
                 let imm = (((data >> 25) << 5) | ((data >> 7) & 0x1F)) as i32;
@@ -100,10 +213,19 @@ impl From<RawInstruction> for Instruction {
                     | (((data >> 21) & 0x3FF) << 1)) as i32;
                 (InstructionType::J, sign_extend(imm, 21))
             }
+            LOAD_FP | STORE_FP => (InstructionType::V, 0),
+            0b1010111 => {
+                // OP-V, the immediate is the vtype of vsetvli/vsetivli
+                let zimm = if data >> 30 == 0b11 { (data >> 20) & 0x3FF } else { (data >> 20) & 0x7FF };
+                (InstructionType::V, zimm as i32)
+            }
+            CUSTOM_0 => (InstructionType::M, 0), // matrix engine, register fields name tiles and vector registers
             _ => (InstructionType::Unknown, 0),
         };
 
         Instruction {
+            pc: raw.pc,
+            raw: data,
             opcode,
             rd,
             rs1,
@@ -112,46 +234,14 @@ impl From<RawInstruction> for Instruction {
             funct7,
             imm,
             typ,
+            custom: None,
         }
     }
 }
 
 impl Display for Instruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let r = |r: u8| format!("x{}", r);
-        let name = self.mnemonic();
-
-        if name == "addi" && self.rd == 0 && self.rs1 == 0 && self.imm == 0 {
-            return write!(f, "nop");
-        }
-
-        match self.typ {
-            InstructionType::R => {
-                write!(f, "{} {}, {}, {}", name, r(self.rd), r(self.rs1), r(self.rs2))
-            }
-            InstructionType::I => {
-                write!(f, "{} {}, {}, {}", name, r(self.rd), r(self.rs1), self.imm)
-            }
-            InstructionType::S => {
-                write!(f, "{} {}, {}({})", name, r(self.rs2), self.imm, r(self.rs1))
-            }
-            InstructionType::B => {
-                write!(f, "{} {}, {}, {}", name, r(self.rs1), r(self.rs2), self.imm)
-            }
-            InstructionType::U => {
-                write!(f, "{} {}, {}", name, r(self.rd), self.imm)
-            }
-            InstructionType::J => {
-                write!(f, "{} {}, {}", name, r(self.rd), self.imm)
-            }
-            InstructionType::Unknown => {
-                write!(
-                    f,
-                    "unknown(op={:#x}, f3={}, f7={}, rd={}, rs1={}, rs2={}, imm={})",
-                    self.opcode, self.funct3, self.funct7, self.rd, self.rs1, self.rs2, self.imm
-                )
-            }
-        }
+        f.write_str(&disassemble(self, None))
     }
 }
 
@@ -183,6 +273,16 @@ impl InstructionBuffer {
         }
     }
 
+    /// Whether the buffer has no space left.
+    pub fn is_full(&self) -> bool {
+        self.queue.len() >= self.capacity
+    }
+
+    /// Drop every buffered instruction.
+    pub fn flush(&mut self) {
+        self.queue.clear();
+    }
+
     /// Pop a batch of raw instructions from the buffer.
     pub fn pop_batch(&mut self, n: usize) -> Vec<RawInstruction> {
         let mut out = Vec::new();
@@ -1,108 +1,374 @@
+use std::collections::{BTreeMap, VecDeque};
 use tracing::debug;
+use crate::common::axi::ExternalMemory;
 use crate::common::io::{Future, Poll};
+use crate::devices::mmio::Mmio;
+use crate::scalar::banks::BankArbiter;
 use crate::scalar::instruction::RawInstruction;
 
+/// Base address of the ITCM
+pub const ITCM_BASE: u32 = 0x0000_0000;
+/// Size of the ITCM in bytes
+pub const ITCM_SIZE: u32 = 8 * 1024;
+/// Base address of the DTCM
+pub const DTCM_BASE: u32 = 0x0001_0000;
+/// Size of the DTCM in bytes
+pub const DTCM_SIZE: u32 = 32 * 1024;
+/// Base address of the external DRAM
+pub const DRAM_BASE: u32 = 0x8000_0000;
+/// Default size of the external DRAM in bytes
+pub const DEFAULT_DRAM_SIZE: u32 = 4 * 1024 * 1024;
+
+/// Default number of fetch-block requests the ITCM accepts per cycle
+pub const DEFAULT_ITCM_PORTS: usize = 1;
+/// Default bytes per fetch block, one 128-bit line
+pub const DEFAULT_FETCH_BLOCK_BYTES: u32 = 16;
+
+/// Fetch-side counts of the ITCM
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ItcmStats {
+    /// Fetch blocks read
+    pub blocks: u64,
+    /// Cycles a queued request waited because every port was taken
+    pub port_stall_cycles: u64,
+}
+
 /// ITCM (Instruction Tightly Coupled Memory)
+///
+/// Fetch reads whole aligned blocks of `block_bytes`. Requests queue until one of
+/// the `ports` accepts them, at most one per port per cycle, and are then pipelined,
+/// each completing `latency` cycles after it was accepted.
 pub struct Itcm {
     /// 8KB Itcm
-    data: [RawInstruction; 2048],
+    data: [u8; ITCM_SIZE as usize],
     /// Simulated IO latency
     latency: u8,
-    /// Pending read request (address, remaining latency)
-    pending: Option<(u32, u8)>
+    pub ports: usize,
+    pub block_bytes: u32,
+    pub stats: ItcmStats,
+    /// Fetch requests waiting for a port, oldest first
+    pending: VecDeque<u64>,
+    /// Cycle the block of each accepted request is ready, by request
+    ready_at: BTreeMap<u64, u64>,
+    /// Identifier of the next request
+    next_id: u64,
+    /// Current cycle
+    now: u64,
 }
 
 impl Itcm {
-    /// Create a new ITCM with given latency (in cycles)
+    /// Create a new zero-filled ITCM with given latency (in cycles)
     pub fn new(latency: u8) -> Self {
-        let mut data = [RawInstruction::default(); 2048];
-        // 0x00: add x5, x1, x2
-        data[0] = RawInstruction { data: 0x002082B3 };
-        // 0x04: add x6, x5, x3
-        data[1] = RawInstruction { data: 0x00328333 };
-        // 0x08: add x7, x6, x4
-        data[2] = RawInstruction { data: 0x004303B3 };
-        // 0x0C: sw x7, 0(x0)
-        data[3] = RawInstruction { data: 0x00702023 };
-        // 0x10: lw x8, 0(x0)
-        data[4] = RawInstruction { data: 0x00002403 };
-        // 0x14: add x9, x8, x5
-        data[5] = RawInstruction { data: 0x005404B3 };
-        // 0x18: add x10, x9, x9
-        data[6] = RawInstruction { data: 0x00948533 };
-        // 0x1C: add x11, x10, x10
-        data[7] = RawInstruction { data: 0x00A505B3 };
-        // 0x20: add x12, x11, x11
-        data[8] = RawInstruction { data: 0x00B58633 };
-        // 0x24: add x13, x12, x12
-        data[9] = RawInstruction { data: 0x00C606B3 };
-        // 0x28: add x14, x13, x13
-        data[10] = RawInstruction { data: 0x00D68733 };
-        // 0x2C: add x15, x14, x14
-        data[11] = RawInstruction { data: 0x00E707B3 };
-        // 0x30: sw x15, 8(x14)
-        data[12] = RawInstruction { data: 0x00F70823 };
-        // 0x34: lw x17, 8(x14)
-        data[13] = RawInstruction { data: 0x00872883 };
-        // 0x38: add x17, x17, x17
-        data[14] = RawInstruction { data: 0x011888B3 };
-        // 0x3C: nop
-        data[15] = RawInstruction { data: 0x00000013 };
-
         Self {
-            data,
+            data: [0; ITCM_SIZE as usize],
             latency,
-            pending: None
+            ports: DEFAULT_ITCM_PORTS,
+            block_bytes: DEFAULT_FETCH_BLOCK_BYTES,
+            stats: ItcmStats::default(),
+            pending: VecDeque::new(),
+            ready_at: BTreeMap::new(),
+            next_id: 0,
+            now: 0,
+        }
+    }
+
+    /// Whether the address falls inside the ITCM
+    pub fn contains(&self, addr: u32) -> bool {
+        addr.wrapping_sub(ITCM_BASE) < ITCM_SIZE
+    }
+
+    /// Load `size` bytes (1, 2 or 4) from the given address
+    pub fn load(&self, addr: u32, size: u32) -> u32 {
+        load_le(&self.data, addr - ITCM_BASE, size)
+    }
+
+    /// Store the low `size` bytes (1, 2 or 4) of `value` at the given address
+    pub fn store(&mut self, addr: u32, size: u32, value: u32) {
+        store_le(&mut self.data, addr - ITCM_BASE, size, value)
+    }
+
+    /// Fetch requests waiting for a port
+    pub fn queued(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Accept queued requests on the free ports, then advance to the next cycle
+    pub fn tick(&mut self) {
+        for _ in 0..self.ports.max(1) {
+            let Some(id) = self.pending.pop_front() else {
+                break;
+            };
+            self.ready_at.insert(id, self.now + self.latency.max(1) as u64);
+        }
+        if !self.pending.is_empty() {
+            self.stats.port_stall_cycles += 1;
         }
+        self.now += 1;
+    }
+
+    /// Drop every queued and in-flight fetch request
+    pub fn flush(&mut self) {
+        self.pending.clear();
+        self.ready_at.clear();
     }
 }
 
-/// ITCM read request future
+/// ITCM fetch-block read request future
 #[derive(Copy, Clone)]
 pub struct ItcmRead {
+    /// Address of the first instruction wanted, the block extends to the next block boundary
     pub addr: u32,
-    pub remaining_cycles: u8
+    id: u64,
+}
+
+impl ItcmRead {
+    /// Address just past the last instruction of the block
+    pub fn end(&self, block_bytes: u32) -> u32 {
+        (self.addr & !(block_bytes - 1)).wrapping_add(block_bytes)
+    }
 }
 
 impl Future for ItcmRead {
-    /// Raw instruction read from ITCM
-    type Output = RawInstruction;
+    /// Raw instructions of the block from `addr` on
+    type Output = Vec<RawInstruction>;
     /// ITCM reference as input context
     type Input = Itcm;
 
     /// Poll the read request
     fn poll(&mut self, context: &mut Self::Input) -> Poll<Self::Output> {
-        if self.remaining_cycles > 0 {
-            self.remaining_cycles -= 1;
-            return Poll::Pending;
+        match context.ready_at.get(&self.id) {
+            Some(&ready) if ready <= context.now => {
+                context.ready_at.remove(&self.id);
+                context.stats.blocks += 1;
+                let end = self.end(context.block_bytes);
+                let block = (self.addr..end).step_by(4).map(|addr| context._read(addr)).collect();
+                Poll::Ready(block)
+            }
+            _ => Poll::Pending,
         }
-        Poll::Ready(context._read(self.addr))
     }
 }
 
 impl Itcm {
-    /// Issue a read request to ITCM
-    pub fn read(&self, addr: u32) -> ItcmRead {
+    /// Queue a request for the fetch block holding `addr`
+    pub fn read(&mut self, addr: u32) -> ItcmRead {
         debug!("ITCM read request addr=0x{:08x}", addr);
-        ItcmRead {
-            addr,
-            remaining_cycles: self.latency.saturating_sub(1)
-        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.push_back(id);
+        ItcmRead { addr, id }
     }
 
     /// Internal read function
     pub(crate) fn _read(&self, addr: u32) -> RawInstruction {
-        debug_assert!(addr % 4 == 0, "Unaligend ITCM read: 0x{:08x}", addr);
-        let index = ((addr / 4) as usize) % self.data.len();
-        debug!("ITCM read addr=0x{:08x}, index={}, data=0x{:08x}", addr, index, self.data[index].data);
-        self.data[index]
+        debug_assert!(addr.is_multiple_of(4), "Unaligend ITCM read: 0x{:08x}", addr);
+        let offset = addr.wrapping_sub(ITCM_BASE) % ITCM_SIZE;
+        let data = load_le(&self.data, offset, 4);
+        debug!("ITCM read addr=0x{:08x}, offset={}, data=0x{:08x}", addr, offset, data);
+        RawInstruction { pc: addr, data }
     }
 }
 
 /// DTCM (Data Tightly Coupled Memory)
 pub struct Dtcm {
-    data: [RawInstruction; 8192],
-    latency: u16
+    /// 32KB Dtcm
+    data: [u8; DTCM_SIZE as usize],
+    /// Simulated access latency
+    latency: u8,
+    /// Arbiter for the bank ports shared by the scalar LSU, vector LSU and DMA
+    pub arbiter: BankArbiter,
+}
+
+impl Dtcm {
+    /// Create a new zero-filled DTCM with given latency (in cycles)
+    pub fn new(latency: u8) -> Self {
+        Self {
+            data: [0; DTCM_SIZE as usize],
+            latency,
+            arbiter: BankArbiter::default(),
+        }
+    }
+
+    /// Access latency in cycles
+    pub fn latency(&self) -> u8 {
+        self.latency
+    }
+
+    /// Whether the address falls inside the DTCM
+    pub fn contains(&self, addr: u32) -> bool {
+        addr.wrapping_sub(DTCM_BASE) < DTCM_SIZE
+    }
+
+    /// Load `size` bytes (1, 2 or 4) from the given address
+    pub fn load(&self, addr: u32, size: u32) -> u32 {
+        load_le(&self.data, addr - DTCM_BASE, size)
+    }
+
+    /// Store the low `size` bytes (1, 2 or 4) of `value` at the given address
+    pub fn store(&mut self, addr: u32, size: u32, value: u32) {
+        store_le(&mut self.data, addr - DTCM_BASE, size, value)
+    }
+
+    /// Split a sequence of `(addr, size)` accesses into beats of at most `bytes_per_beat`
+    /// bytes in which each bank serves a single word, returning the banks of each beat.
+    /// Accesses outside the DTCM take beat bandwidth but no bank.
+    pub fn beats(&self, accesses: &[(u32, u32)], bytes_per_beat: u32) -> Vec<u64> {
+        let config = &self.arbiter.config;
+        let mut beats: Vec<u64> = Vec::new();
+        let mut bytes = 0;
+        let mut rows = vec![None; config.banks as usize];
+        for &(addr, size) in accesses {
+            let last = addr.wrapping_add(size - 1);
+            let words: Vec<u32> = if self.contains(addr) && self.contains(last) {
+                (addr / config.width..=last / config.width).map(|word| word * config.width).collect()
+            } else {
+                Vec::new()
+            };
+            let conflict = words.iter().any(|&word| rows[config.bank(word)].is_some_and(|open| open != config.row(word)));
+            if beats.is_empty() || conflict || bytes + size > bytes_per_beat {
+                beats.push(0);
+                bytes = 0;
+                rows.fill(None);
+            }
+            for word in words {
+                rows[config.bank(word)] = Some(config.row(word));
+                *beats.last_mut().unwrap() |= 1 << config.bank(word);
+            }
+            bytes += size;
+        }
+        if beats.is_empty() {
+            beats.push(0);
+        }
+        beats
+    }
+}
+
+/// External DRAM holding data that does not fit in the tightly coupled memories
+pub struct Dram {
+    data: Vec<u8>,
+}
+
+impl Dram {
+    /// Create a zero-filled DRAM of `size` bytes at `DRAM_BASE`
+    pub fn new(size: u32) -> Self {
+        Self { data: vec![0; size as usize] }
+    }
+
+    /// Whether the address falls inside the DRAM
+    pub fn contains(&self, addr: u32) -> bool {
+        (addr.wrapping_sub(DRAM_BASE) as usize) < self.data.len()
+    }
+
+    /// Load `size` bytes (1, 2 or 4) from the given address
+    pub fn load(&self, addr: u32, size: u32) -> u32 {
+        load_le(&self.data, addr - DRAM_BASE, size)
+    }
+
+    /// Store the low `size` bytes (1, 2 or 4) of `value` at the given address
+    pub fn store(&mut self, addr: u32, size: u32, value: u32) {
+        store_le(&mut self.data, addr - DRAM_BASE, size, value)
+    }
 }
 
+impl Default for Dram {
+    fn default() -> Self {
+        Self::new(DEFAULT_DRAM_SIZE)
+    }
+}
 
+/// Data-side view of the address space, shared by the LSU and the debugger
+pub struct DataBus<'a> {
+    pub itcm: &'a mut Itcm,
+    pub dtcm: &'a mut Dtcm,
+    pub dram: &'a mut ExternalMemory,
+    pub mmio: &'a mut Mmio,
+}
+
+impl<'a> DataBus<'a> {
+    /// Create a data bus over the tightly coupled memories, the DRAM and memory-mapped devices
+    pub fn new(itcm: &'a mut Itcm, dtcm: &'a mut Dtcm, dram: &'a mut ExternalMemory, mmio: &'a mut Mmio) -> Self {
+        Self { itcm, dtcm, dram, mmio }
+    }
+
+    /// Load `size` bytes from the given address, or `None` if it is unmapped
+    pub fn load(&mut self, addr: u32, size: u32) -> Option<u32> {
+        if self.dtcm.contains(addr) && self.dtcm.contains(addr.wrapping_add(size - 1)) {
+            Some(self.dtcm.load(addr, size))
+        } else if self.itcm.contains(addr) && self.itcm.contains(addr.wrapping_add(size - 1)) {
+            Some(self.itcm.load(addr, size))
+        } else if self.dram.contains(addr) && self.dram.contains(addr.wrapping_add(size - 1)) {
+            Some(self.dram.load(addr, size))
+        } else {
+            self.mmio.load(addr, size)
+        }
+    }
+
+    /// Load `size` bytes from memory without touching devices, `None` if the address is
+    /// unmapped or memory-mapped I/O whose registers may have read side effects
+    pub fn peek(&self, addr: u32, size: u32) -> Option<u32> {
+        if self.dtcm.contains(addr) && self.dtcm.contains(addr.wrapping_add(size - 1)) {
+            Some(self.dtcm.load(addr, size))
+        } else if self.itcm.contains(addr) && self.itcm.contains(addr.wrapping_add(size - 1)) {
+            Some(self.itcm.load(addr, size))
+        } else if self.dram.contains(addr) && self.dram.contains(addr.wrapping_add(size - 1)) {
+            Some(self.dram.load(addr, size))
+        } else {
+            None
+        }
+    }
+
+    /// Store `size` bytes to the given address, returns false if it is unmapped
+    pub fn store(&mut self, addr: u32, size: u32, value: u32) -> bool {
+        if self.dtcm.contains(addr) && self.dtcm.contains(addr.wrapping_add(size - 1)) {
+            self.dtcm.store(addr, size, value);
+            true
+        } else if self.itcm.contains(addr) && self.itcm.contains(addr.wrapping_add(size - 1)) {
+            self.itcm.store(addr, size, value);
+            true
+        } else if self.dram.contains(addr) && self.dram.contains(addr.wrapping_add(size - 1)) {
+            self.dram.store(addr, size, value);
+            true
+        } else {
+            self.mmio.store(addr, size, value)
+        }
+    }
+}
+
+/// Little-endian load of `size` bytes at `offset`
+fn load_le(data: &[u8], offset: u32, size: u32) -> u32 {
+    let offset = offset as usize;
+    data[offset..offset + size as usize]
+        .iter()
+        .rev()
+        .fold(0, |acc, b| (acc << 8) | *b as u32)
+}
+
+/// Little-endian store of the low `size` bytes of `value` at `offset`
+fn store_le(data: &mut [u8], offset: u32, size: u32, value: u32) {
+    let offset = offset as usize;
+    data[offset..offset + size as usize].copy_from_slice(&value.to_le_bytes()[..size as usize]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dtcm_beats_group_words_by_bank_and_row() {
+        let dtcm = Dtcm::new(1);
+        // 8 banks of 4-byte words, so the bank's row changes every 32 bytes
+        let words = |count: u32, stride: u32| (0..count).map(|i| (DTCM_BASE + i * stride, 4)).collect::<Vec<_>>();
+        let cases = [
+            ("consecutive words", words(4, 4), 16, vec![0x0f]),
+            ("beat size limit", words(8, 4), 16, vec![0x0f, 0xf0]),
+            ("same bank, next row", words(2, 32), 16, vec![0x01, 0x01]),
+            ("same word twice", vec![(DTCM_BASE, 1), (DTCM_BASE + 1, 1)], 16, vec![0x01]),
+            ("word spanning two banks", vec![(DTCM_BASE + 2, 4)], 16, vec![0x03]),
+            ("outside the DTCM", vec![(DRAM_BASE, 4), (DRAM_BASE + 4, 4)], 4, vec![0, 0]),
+            ("no accesses", Vec::new(), 16, vec![0]),
+        ];
+        for (name, accesses, bytes_per_beat, beats) in cases {
+            assert_eq!(dtcm.beats(&accesses, bytes_per_beat), beats, "{}", name);
+        }
+    }
+}
@@ -1,8 +1,18 @@
 mod fetch;
 pub mod core;
-mod instruction;
-mod memory;
+pub mod instruction;
+pub mod memory;
 mod decode;
-mod dispatch;
+pub mod dispatch;
 mod units;
-mod scoreboard;
\ No newline at end of file
+mod scoreboard;
+pub mod regfile;
+pub mod disasm;
+pub mod asm;
+pub mod csr;
+pub mod custom;
+pub mod htif;
+pub mod semihost;
+pub mod banks;
+pub mod icache;
+pub mod dcache;
\ No newline at end of file
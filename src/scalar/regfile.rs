@@ -0,0 +1,27 @@
+/// ABI names of the integer registers, indexed by register number
+pub const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+/// Scalar integer register file (x0..x31)
+#[derive(Default)]
+pub struct RegisterFile {
+    regs: [u32; 32],
+}
+
+impl RegisterFile {
+    /// Read a register, x0 always reads as zero
+    pub fn read(&self, index: u8) -> u32 {
+        self.regs[index as usize]
+    }
+
+    /// Write a register, writes to x0 are discarded
+    pub fn write(&mut self, index: u8, value: u32) {
+        if index != 0 {
+            self.regs[index as usize] = value;
+        }
+    }
+}
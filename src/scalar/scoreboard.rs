@@ -1,13 +1,17 @@
+use std::collections::BTreeSet;
+use crate::scalar::custom::{CustomInfo, CustomUnit};
 use crate::scalar::instruction::Instruction;
 
 /// Simple scoreboard for scalar pipeline.
 /// Tracks register availability and functional unit busy states.
 pub struct Scoreboard {
     pub reg_busy: [bool; 32], // x0..x31
-    pub pending_busy: [bool; 32],
     pub alu_busy: Vec<bool>,
     pub bru_busy: Vec<bool>,
     pub lsu_busy: bool,
+    pub csr_busy: bool,
+    /// Extensions whose dedicated unit is executing an instruction
+    pub extension_busy: BTreeSet<u8>,
 }
 
 impl Scoreboard {
@@ -15,40 +19,33 @@ impl Scoreboard {
     pub fn new(num_alus: usize, num_brus: usize) -> Self {
         Self {
             reg_busy: [false; 32],
-            pending_busy: [false; 32],
             alu_busy: vec![false; num_alus],
             bru_busy: vec![false; num_brus],
             lsu_busy: false,
+            csr_busy: false,
+            extension_busy: BTreeSet::new(),
         }
     }
 
     /// Check if an instruction can be issued without hazard
     pub fn can_issue(&self, instr: &Instruction) -> bool {
-        let rs1_busy = instr.rs1 != 0 && (self.reg_busy[instr.rs1 as usize] || self.pending_busy[instr.rs1 as usize]);
-        let rs2_busy = instr.rs2 != 0 && (self.reg_busy[instr.rs2 as usize] || self.pending_busy[instr.rs2 as usize]);
-        let rd_waw = instr.rd != 0 && self.reg_busy[instr.rd as usize];
+        let rs1_busy = instr.reads_rs1() && instr.rs1 != 0 && self.reg_busy[instr.rs1 as usize];
+        let rs2_busy = instr.reads_rs2() && instr.rs2 != 0 && self.reg_busy[instr.rs2 as usize];
+        let rd_waw = instr.writes_rd() && instr.rd != 0 && self.reg_busy[instr.rd as usize];
         !(rs1_busy || rs2_busy || rd_waw)
     }
 
     /// Mark destination register as busy
     pub fn mark_issue(&mut self, instr: &Instruction) {
-        if instr.rd != 0 {
-            self.pending_busy[instr.rd as usize] = false;
+        if instr.writes_rd() && instr.rd != 0 {
             self.reg_busy[instr.rd as usize] = true;
         }
     }
 
-    pub fn predict_issue(&mut self, instr: &Instruction) {
-        if instr.rd != 0 {
-            self.pending_busy[instr.rd as usize] = true;
-        }
-    }
-
     /// Mark destination register as ready after writeback
     pub fn mark_complete(&mut self, instr: &Instruction) {
-        if instr.rd != 0 {
+        if instr.writes_rd() && instr.rd != 0 {
             self.reg_busy[instr.rd as usize] = false;
-
         }
     }
 
@@ -64,39 +61,84 @@ impl Scoreboard {
 
     /// Allocate a functional unit
     pub fn allocate_unit(&mut self, instr: &Instruction) -> bool {
+        if let Some(custom) = instr.custom {
+            return self.allocate_custom(&custom);
+        }
+        if instr.is_system() { // SYSTEM / FENCE / illegal
+            let free = !self.csr_busy;
+            self.csr_busy = true;
+            return free;
+        }
         match instr.opcode {
-            0b0110011 | 0b0010011 => { // ALU
+            0b0110011 | 0b0010011 | 0b0110111 | 0b0010111 => { // ALU
                 if let Some(i) = self.find_free_alu() {
                     self.alu_busy[i] = true;
                     return true;
                 }
             }
-            0b1100011 => { // BRANCH
+            0b1100011 | 0b1101111 | 0b1100111 => { // BRANCH / JUMP
                 if let Some(i) = self.find_free_bru() {
                     self.bru_busy[i] = true;
                     return true;
                 }
             }
-            0b0000011 | 0b0100011 => { // LOAD / STORE
-                if !self.lsu_busy {
-                    self.lsu_busy = true;
-                    return true;
-                }
+            0b0000011 | 0b0100011 if !self.lsu_busy => { // LOAD / STORE
+                self.lsu_busy = true;
+                return true;
+            }
+            0b1010111 | 0b0000111 | 0b0100111 => { // OP-V / VECTOR LOAD / STORE, queued in the vector backend
+                return true;
+            }
+            0b0001011 => { // CUSTOM-0 matrix engine, which tracks its own occupancy
+                return true;
             }
             _ => {}
         }
         false
     }
 
+    /// Allocate the unit a custom instruction executes in
+    fn allocate_custom(&mut self, custom: &CustomInfo) -> bool {
+        match custom.unit {
+            CustomUnit::Alu => match self.find_free_alu() {
+                Some(i) => {
+                    self.alu_busy[i] = true;
+                    true
+                }
+                None => false,
+            },
+            CustomUnit::Lsu => !std::mem::replace(&mut self.lsu_busy, true),
+            CustomUnit::Dedicated => self.extension_busy.insert(custom.extension),
+        }
+    }
+
     /// Free a functional unit (called after execution done)
     pub fn release_unit(&mut self, instr: &Instruction) {
+        if let Some(custom) = instr.custom {
+            match custom.unit {
+                CustomUnit::Alu => {
+                    if let Some(i) = self.alu_busy.iter().position(|b| *b) {
+                        self.alu_busy[i] = false;
+                    }
+                }
+                CustomUnit::Lsu => self.lsu_busy = false,
+                CustomUnit::Dedicated => {
+                    self.extension_busy.remove(&custom.extension);
+                }
+            }
+            return;
+        }
+        if instr.is_system() {
+            self.csr_busy = false;
+            return;
+        }
         match instr.opcode {
-            0b0110011 | 0b0010011 => {
+            0b0110011 | 0b0010011 | 0b0110111 | 0b0010111 => {
                 if let Some(i) = self.alu_busy.iter().position(|b| *b) {
                     self.alu_busy[i] = false;
                 }
             }
-            0b1100011 => {
+            0b1100011 | 0b1101111 | 0b1100111 => {
                 if let Some(i) = self.bru_busy.iter().position(|b| *b) {
                     self.bru_busy[i] = false;
                 }
@@ -107,4 +149,4 @@ impl Scoreboard {
             _ => {}
         }
     }
-}
\ No newline at end of file
+}
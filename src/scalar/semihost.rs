@@ -0,0 +1,228 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+use tracing::{debug, warn};
+use crate::scalar::memory::DataBus;
+
+/// `slli x0, x0, 0x1f` marking the start of a semihosting call
+const ENTRY_NOP: u32 = 0x01F01013;
+/// `srai x0, x0, 7` marking the end of a semihosting call
+const EXIT_NOP: u32 = 0x40705013;
+
+/// Semihosting operation numbers passed in `a0`
+const SYS_OPEN: u32 = 0x01;
+const SYS_CLOSE: u32 = 0x02;
+const SYS_WRITE0: u32 = 0x04;
+const SYS_WRITE: u32 = 0x05;
+const SYS_READ: u32 = 0x06;
+const SYS_CLOCK: u32 = 0x10;
+const SYS_EXIT: u32 = 0x18;
+const SYS_EXIT_EXTENDED: u32 = 0x20;
+
+/// `SYS_EXIT` reason for a normal application exit
+const ADP_STOPPED_APPLICATION_EXIT: u32 = 0x20026;
+/// Special file name opening the host console
+const CONSOLE_NAME: &[u8] = b":tt";
+/// Upper bound on a string read from simulated memory
+const MAX_STRING: u32 = 4096;
+/// Upper bound on the bytes moved by one `SYS_READ`, shorter reads are allowed
+const MAX_READ: u32 = 64 * 1024;
+
+/// Outcome of a semihosting call
+pub enum SemihostResult {
+    /// Value returned to the program in `a0`, if any
+    Return(Option<u32>),
+    /// The program asked to exit with the given status
+    Exit(u32),
+}
+
+/// Host end of an open semihosting handle
+enum Handle {
+    Stdin,
+    Stdout,
+    Stderr,
+    File(File),
+}
+
+/// RISC-V semihosting service. A call is an `ebreak` surrounded by the entry and exit
+/// markers, with the operation in `a0` and a parameter or parameter block pointer in `a1`.
+/// Host files are only reachable below `root`, file access is refused when it is `None`.
+pub struct Semihost {
+    pub root: Option<PathBuf>,
+    handles: Vec<Option<Handle>>,
+    start: Instant,
+}
+
+impl Semihost {
+    /// Create a service sandboxing host file access to `root`
+    pub fn new(root: Option<PathBuf>) -> Self {
+        Self { root, handles: Vec::new(), start: Instant::now() }
+    }
+
+    /// Whether the `ebreak` at `pc` is wrapped in the semihosting marker instructions
+    pub fn is_call(bus: &mut DataBus, pc: u32) -> bool {
+        bus.load(pc.wrapping_sub(4), 4) == Some(ENTRY_NOP) && bus.load(pc.wrapping_add(4), 4) == Some(EXIT_NOP)
+    }
+
+    /// Perform operation `op` with parameter `param`
+    pub fn call(&mut self, op: u32, param: u32, bus: &mut DataBus) -> SemihostResult {
+        let mut args = |n: u32| bus.load(param.wrapping_add(4 * n), 4).unwrap_or(0);
+        let result = match op {
+            SYS_OPEN => {
+                let (name, mode, len) = (args(0), args(1), args(2));
+                self.open(bus, name, mode, len)
+            }
+            SYS_CLOSE => match self.handles.get_mut(args(0).wrapping_sub(1) as usize) {
+                Some(handle @ Some(_)) => {
+                    *handle = None;
+                    0
+                }
+                _ => u32::MAX,
+            },
+            SYS_WRITE0 => {
+                let text = read_string(bus, param, None);
+                let _ = write_console(&mut io::stdout(), &text);
+                return SemihostResult::Return(None);
+            }
+            SYS_WRITE => {
+                let (handle, buf, len) = (args(0), args(1), args(2));
+                self.write(bus, handle, buf, len)
+            }
+            SYS_READ => {
+                let (handle, buf, len) = (args(0), args(1), args(2));
+                self.read(bus, handle, buf, len)
+            }
+            SYS_CLOCK => (self.start.elapsed().as_millis() / 10) as u32,
+            SYS_EXIT => return SemihostResult::Exit((param != ADP_STOPPED_APPLICATION_EXIT) as u32),
+            SYS_EXIT_EXTENDED => {
+                let (reason, code) = (args(0), args(1));
+                let code = if reason == ADP_STOPPED_APPLICATION_EXIT { code } else { 1 };
+                return SemihostResult::Exit(code);
+            }
+            _ => {
+                warn!("Unsupported semihosting operation 0x{:02x}", op);
+                u32::MAX
+            }
+        };
+        SemihostResult::Return(Some(result))
+    }
+
+    /// `SYS_OPEN`: returns a handle, or -1 on failure
+    fn open(&mut self, bus: &mut DataBus, name: u32, mode: u32, len: u32) -> u32 {
+        let name = read_string(bus, name, Some(len));
+        let handle = if name == CONSOLE_NAME {
+            match mode {
+                0..=3 => Handle::Stdin,
+                4..=7 => Handle::Stdout,
+                _ => Handle::Stderr,
+            }
+        } else {
+            let name = String::from_utf8_lossy(&name);
+            if self.root.is_none() {
+                warn!("Semihosting open of {:?} refused, no host directory is configured", name);
+                return u32::MAX;
+            }
+            let Some(path) = self.sandboxed(&name) else {
+                warn!("Semihosting open of {:?} refused outside the sandbox", name);
+                return u32::MAX;
+            };
+            let mut options = OpenOptions::new();
+            // ARM fopen modes: r, rb, r+, r+b, w, wb, w+, w+b, a, ab, a+, a+b
+            match mode / 4 {
+                0 => options.read(true).write(mode & 2 != 0),
+                1 => options.write(true).create(true).truncate(true).read(mode & 2 != 0),
+                _ => options.append(true).create(true).read(mode & 2 != 0),
+            };
+            match options.open(&path) {
+                Ok(file) => Handle::File(file),
+                Err(e) => {
+                    debug!("Semihosting open of {} failed: {}", path.display(), e);
+                    return u32::MAX;
+                }
+            }
+        };
+
+        let slot = match self.handles.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None => {
+                self.handles.push(None);
+                self.handles.len() - 1
+            }
+        };
+        self.handles[slot] = Some(handle);
+        slot as u32 + 1
+    }
+
+    /// `SYS_WRITE`: returns the number of bytes not written
+    fn write(&mut self, bus: &mut DataBus, handle: u32, buf: u32, len: u32) -> u32 {
+        let bytes: Vec<u8> = (0..len).map_while(|i| bus.load(buf.wrapping_add(i), 1).map(|b| b as u8)).collect();
+        let written = match self.handle(handle) {
+            Some(Handle::Stdout) => write_console(&mut io::stdout(), &bytes),
+            Some(Handle::Stderr) => write_console(&mut io::stderr(), &bytes),
+            Some(Handle::File(file)) => file.write_all(&bytes),
+            _ => return len,
+        };
+        match written {
+            Ok(()) => len - bytes.len() as u32,
+            Err(_) => len,
+        }
+    }
+
+    /// `SYS_READ`: returns the number of bytes not read, `len` meaning end of file
+    fn read(&mut self, bus: &mut DataBus, handle: u32, buf: u32, len: u32) -> u32 {
+        let mut bytes = vec![0; len.min(MAX_READ) as usize];
+        let count = match self.handle(handle) {
+            Some(Handle::Stdin) => io::stdin().read(&mut bytes),
+            Some(Handle::File(file)) => file.read(&mut bytes),
+            _ => return u32::MAX,
+        };
+        let Ok(count) = count else {
+            return u32::MAX;
+        };
+        for (i, byte) in bytes[..count].iter().enumerate() {
+            if !bus.store(buf.wrapping_add(i as u32), 1, *byte as u32) {
+                return u32::MAX;
+            }
+        }
+        len - count as u32
+    }
+
+    fn handle(&mut self, handle: u32) -> Option<&mut Handle> {
+        self.handles.get_mut(handle.wrapping_sub(1) as usize)?.as_mut()
+    }
+
+    /// Resolve a program-supplied path below the sandbox root, refusing anything that escapes it
+    fn sandboxed(&self, name: &str) -> Option<PathBuf> {
+        let root = self.root.as_ref()?.canonicalize().ok()?;
+        if !Path::new(name).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return None;
+        }
+        let path = root.join(name);
+        // Follow symlinks of existing files, a new file's parent must stay inside
+        let resolved = match path.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(_) => path.parent()?.canonicalize().ok()?.join(path.file_name()?),
+        };
+        resolved.starts_with(&root).then_some(resolved)
+    }
+}
+
+impl Default for Semihost {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Read `len` bytes, or a NUL-terminated string when `len` is `None`, from simulated memory
+fn read_string(bus: &mut DataBus, addr: u32, len: Option<u32>) -> Vec<u8> {
+    (0..len.unwrap_or(MAX_STRING))
+        .map_while(|i| bus.load(addr.wrapping_add(i), 1).map(|b| b as u8))
+        .take_while(|b| len.is_some() || *b != 0)
+        .collect()
+}
+
+fn write_console(out: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(bytes)?;
+    out.flush()
+}
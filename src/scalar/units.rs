@@ -1,29 +1,91 @@
+use crate::common::axi::{AxiRead, AxiWrite};
+use crate::common::io::{Future, Poll};
+use crate::scalar::banks::Requestor;
+use crate::scalar::dcache::{Access, DCache};
+use crate::scalar::csr::{
+    CsrFile, Trap, CAUSE_BREAKPOINT, CAUSE_ECALL_M, CAUSE_ILLEGAL_INSTRUCTION, CAUSE_LOAD_ACCESS_FAULT,
+    CAUSE_MISALIGNED_FETCH, CAUSE_STORE_ACCESS_FAULT,
+};
 use crate::scalar::instruction::Instruction;
+use crate::scalar::memory::DataBus;
 
 pub struct AluUnit {
     pub busy: bool,
     pub remaining: u8,
     pub current: Option<Instruction>,
+    pub result: u32,
 }
 
 impl AluUnit {
     pub fn new() -> Self {
-        Self { busy: false, remaining: 0, current: None }
+        Self { busy: false, remaining: 0, current: None, result: 0 }
     }
 
-    pub fn issue(&mut self, instr: Instruction) {
+    pub fn issue(&mut self, instr: Instruction, rs1: u32, rs2: u32) {
         self.busy = true;
         self.remaining = 1;
         self.current = Some(instr);
+        self.result = Self::execute(&instr, rs1, rs2);
+    }
+
+    /// Compute the result of an ALU instruction from its source operands
+    fn execute(instr: &Instruction, rs1: u32, rs2: u32) -> u32 {
+        let imm = instr.imm as u32;
+        match instr.opcode {
+            0b0110111 => imm, // LUI
+            0b0010111 => instr.pc.wrapping_add(imm), // AUIPC
+            0b0010011 => match instr.funct3 {
+                0b000 => rs1.wrapping_add(imm),
+                0b010 => ((rs1 as i32) < instr.imm) as u32,
+                0b011 => (rs1 < imm) as u32,
+                0b100 => rs1 ^ imm,
+                0b110 => rs1 | imm,
+                0b111 => rs1 & imm,
+                0b001 => rs1 << (imm & 0x1F),
+                _ if imm & 0x400 != 0 => ((rs1 as i32) >> (imm & 0x1F)) as u32,
+                _ => rs1 >> (imm & 0x1F),
+            },
+            _ if instr.funct7 == 0b0000001 => Self::execute_muldiv(instr.funct3, rs1, rs2),
+            _ => match (instr.funct3, instr.funct7) {
+                (0b000, 0b0100000) => rs1.wrapping_sub(rs2),
+                (0b000, _) => rs1.wrapping_add(rs2),
+                (0b001, _) => rs1 << (rs2 & 0x1F),
+                (0b010, _) => ((rs1 as i32) < (rs2 as i32)) as u32,
+                (0b011, _) => (rs1 < rs2) as u32,
+                (0b100, _) => rs1 ^ rs2,
+                (0b101, 0b0100000) => ((rs1 as i32) >> (rs2 & 0x1F)) as u32,
+                (0b101, _) => rs1 >> (rs2 & 0x1F),
+                (0b110, _) => rs1 | rs2,
+                _ => rs1 & rs2,
+            },
+        }
     }
 
-    pub fn tick(&mut self) -> Option<Instruction> {
+    /// Compute an M extension multiply or divide. Division by zero and signed
+    /// overflow give the architecturally defined results instead of trapping.
+    fn execute_muldiv(funct3: u8, rs1: u32, rs2: u32) -> u32 {
+        let (a, b) = (rs1 as i32, rs2 as i32);
+        match funct3 {
+            0b000 => rs1.wrapping_mul(rs2),
+            0b001 => ((a as i64 * b as i64) >> 32) as u32,
+            0b010 => ((a as i64 * rs2 as i64) >> 32) as u32,
+            0b011 => ((rs1 as u64 * rs2 as u64) >> 32) as u32,
+            0b100 if b == 0 => u32::MAX,
+            0b100 => a.wrapping_div(b) as u32,
+            0b101 => rs1.checked_div(rs2).unwrap_or(u32::MAX),
+            0b110 if b == 0 => rs1,
+            0b110 => a.wrapping_rem(b) as u32,
+            _ => rs1.checked_rem(rs2).unwrap_or(rs1),
+        }
+    }
+
+    pub fn tick(&mut self) -> Option<(Instruction, u32)> {
         if self.busy {
             if self.remaining > 0 {
                 self.remaining -= 1;
             } else {
                 self.busy = false;
-                return self.current.take();
+                return self.current.take().map(|instr| (instr, self.result));
             }
         }
         None
@@ -41,19 +103,44 @@ impl BruUnit {
         Self { busy: false, remaining: 0, current: None }
     }
 
-    pub fn issue(&mut self, instr: Instruction) {
+    /// Issue a branch or jump, returning the redirect target if control flow leaves the fall-through path.
+    /// A taken target that is not 4-byte aligned raises instruction-address-misaligned.
+    pub fn issue(&mut self, instr: Instruction, rs1: u32, rs2: u32) -> Result<Option<u32>, Trap> {
+        let target = match instr.opcode {
+            0b1101111 => Some(instr.pc.wrapping_add(instr.imm as u32)), // JAL
+            0b1100111 => Some(rs1.wrapping_add(instr.imm as u32) & !1), // JALR
+            _ => {
+                let taken = match instr.funct3 {
+                    0b000 => rs1 == rs2,
+                    0b001 => rs1 != rs2,
+                    0b100 => (rs1 as i32) < (rs2 as i32),
+                    0b101 => (rs1 as i32) >= (rs2 as i32),
+                    0b110 => rs1 < rs2,
+                    _ => rs1 >= rs2,
+                };
+                taken.then(|| instr.pc.wrapping_add(instr.imm as u32))
+            }
+        };
+        if let Some(target) = target
+            && !target.is_multiple_of(4)
+        {
+            return Err(Trap { cause: CAUSE_MISALIGNED_FETCH, tval: target });
+        }
+
         self.busy = true;
         self.remaining = 1;
         self.current = Some(instr);
+        Ok(target)
     }
 
-    pub fn tick(&mut self) -> Option<Instruction> {
+    pub fn tick(&mut self) -> Option<(Instruction, u32)> {
         if self.busy {
             if self.remaining > 0 {
                 self.remaining -= 1;
             } else {
                 self.busy = false;
-                return self.current.take();
+                // Jumps write the return address
+                return self.current.take().map(|instr| (instr, instr.pc.wrapping_add(4)));
             }
         }
         None
@@ -64,28 +151,239 @@ pub struct LsuUnit {
     pub busy: bool,
     pub remaining: u8,
     pub current: Option<Instruction>,
+    pub result: u32,
+    /// DRAM access the current instruction waits for
+    pub pending: Option<DramAccess>,
+    /// DTCM bank grant the current instruction waits for
+    pub ticket: Option<u64>,
+    /// Optional data cache for DRAM accesses, which otherwise go uncached to the AXI port
+    pub dcache: Option<DCache>,
+    /// DRAM address the current instruction looks up in the data cache
+    lookup: Option<u32>,
+}
+
+/// What the LSU did in a cycle
+#[derive(Default)]
+pub struct LsuEvents {
+    /// Instruction leaving the unit with its result
+    pub completed: Option<(Instruction, u32)>,
+    /// Instruction whose cache miss moved into an MSHR, leaving the unit free
+    pub missed: Option<Instruction>,
+    /// Earlier misses whose line arrived, with their load results
+    pub filled: Vec<(Instruction, u32)>,
+}
+
+/// Response of an uncached scalar access the unit waits for on the AXI port
+pub enum DramAccess {
+    Read(AxiRead),
+    Write(AxiWrite),
 }
 
 impl LsuUnit {
     pub fn new() -> Self {
-        Self { busy: false, remaining: 0, current: None }
+        Self { busy: false, remaining: 0, current: None, result: 0, pending: None, ticket: None, dcache: None, lookup: None }
+    }
+
+    /// Issue a load or store, performing the memory access immediately so later
+    /// accesses from any unit observe it in program order.
+    /// DTCM accesses complete `latency` cycles after their bank grant, counting the
+    /// grant cycle. DRAM accesses look up the data cache if there is one, and otherwise
+    /// occupy the AXI port and complete when it responds. Accesses outside the
+    /// memory map raise an access fault and leave the unit free.
+    pub fn issue(&mut self, instr: Instruction, rs1: u32, rs2: u32, bus: &mut DataBus) -> Result<(), Trap> {
+        let addr = rs1.wrapping_add(instr.imm as u32);
+        let size = 1 << (instr.funct3 & 0b11);
+        let store = instr.opcode == 0b0100011;
+        let in_dram = bus.dram.contains(addr) && bus.dram.contains(addr.wrapping_add(size - 1));
+        if in_dram && self.dcache.is_some() {
+            if store {
+                bus.dram.store(addr, size, rs2);
+            } else {
+                self.result = extend_load(instr.funct3, bus.dram.load(addr, size));
+            }
+            self.lookup = Some(addr);
+        } else if in_dram {
+            self.pending = Some(if store {
+                bus.dram.store(addr, size, rs2);
+                DramAccess::Write(bus.dram.write_response(addr, size))
+            } else {
+                self.result = extend_load(instr.funct3, bus.dram.load(addr, size));
+                DramAccess::Read(bus.dram.read(addr, size))
+            });
+        } else if store {
+            if !bus.store(addr, size, rs2) {
+                return Err(Trap { cause: CAUSE_STORE_ACCESS_FAULT, tval: addr });
+            }
+        } else {
+            let value = bus.load(addr, size).ok_or(Trap { cause: CAUSE_LOAD_ACCESS_FAULT, tval: addr })?;
+            self.result = extend_load(instr.funct3, value);
+        }
+
+        self.busy = true;
+        self.remaining = match &self.dcache {
+            Some(dcache) if in_dram => dcache.config.hit_latency,
+            // The AXI response already carries the external memory latency
+            None if in_dram => 0,
+            _ => bus.dtcm.latency(),
+        };
+        self.current = Some(instr);
+        if bus.dtcm.contains(addr) && bus.dtcm.contains(addr.wrapping_add(size - 1)) {
+            let beats = bus.dtcm.beats(&[(addr, size)], size);
+            self.ticket = Some(bus.dtcm.arbiter.request(Requestor::ScalarLsu, beats));
+            self.remaining = self.remaining.saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Whether no access is in the unit or waiting for a line fill
+    pub fn is_idle(&self) -> bool {
+        !self.busy && self.dcache.as_ref().is_none_or(|dcache| dcache.outstanding() == 0)
+    }
+
+    pub fn tick(&mut self, bus: &mut DataBus) -> LsuEvents {
+        let mut events = LsuEvents::default();
+        events.completed = self.advance(bus, &mut events.missed);
+        if let Some(dcache) = &mut self.dcache {
+            events.filled = dcache.tick(bus.dram);
+        }
+        events
+    }
+
+    /// Advance the access in the unit, returning it once it completes
+    fn advance(&mut self, bus: &mut DataBus, missed: &mut Option<Instruction>) -> Option<(Instruction, u32)> {
+        if let (Some(addr), Some(instr), Some(dcache)) = (self.lookup, self.current, self.dcache.as_mut()) {
+            let size = 1 << (instr.funct3 & 0b11);
+            match dcache.access(instr, addr, size, instr.opcode == 0b0100011, self.result, bus.dram) {
+                Access::Hit => self.lookup = None,
+                Access::Miss => {
+                    self.lookup = None;
+                    self.busy = false;
+                    *missed = self.current.take();
+                    return None;
+                }
+                Access::Blocked => return None,
+            }
+        }
+        if let Some(ticket) = self.ticket {
+            if !bus.dtcm.arbiter.is_granted(Requestor::ScalarLsu, ticket) {
+                return None;
+            }
+            self.ticket = None;
+        }
+        match self.pending.as_mut() {
+            // The data moved at issue, the response only times the access
+            Some(DramAccess::Read(read)) => match read.poll(bus.dram) {
+                Poll::Pending => return None,
+                Poll::Ready(_) => self.pending = None,
+            },
+            Some(DramAccess::Write(write)) => match write.poll(bus.dram) {
+                Poll::Pending => return None,
+                Poll::Ready(()) => self.pending = None,
+            },
+            None => {}
+        }
+        if self.busy {
+            if self.remaining > 0 {
+                self.remaining -= 1;
+            } else {
+                self.busy = false;
+                return self.current.take().map(|instr| (instr, self.result));
+            }
+        }
+        None
+    }
+}
+
+/// Sign- or zero-extend a loaded value as selected by the load's funct3
+fn extend_load(funct3: u8, value: u32) -> u32 {
+    match funct3 {
+        0b000 => value as i8 as i32 as u32,
+        0b001 => value as i16 as i32 as u32,
+        _ => value,
+    }
+}
+
+/// Executes CSR accesses and the other SYSTEM and FENCE instructions
+pub struct CsrUnit {
+    pub busy: bool,
+    pub remaining: u8,
+    pub current: Option<Instruction>,
+    pub result: u32,
+}
+
+impl CsrUnit {
+    pub fn new() -> Self {
+        Self { busy: false, remaining: 0, current: None, result: 0 }
     }
 
-    pub fn issue(&mut self, instr: Instruction) {
+    /// Issue a system instruction, returning the redirect target if it changes control flow.
+    /// Exceptions, including `ecall` and `ebreak`, are returned as traps and leave the unit free.
+    pub fn issue(&mut self, instr: Instruction, rs1: u32, rs2: u32, csrs: &mut CsrFile) -> Result<Option<u32>, Trap> {
+        let illegal = Trap { cause: CAUSE_ILLEGAL_INSTRUCTION, tval: instr.raw };
+        let mut redirect = None;
+
+        match (instr.opcode, instr.funct3) {
+            (0b0001111, 0b000) => {} // FENCE, memory is always coherent
+            (0b0001111, 0b001) => redirect = Some(instr.pc.wrapping_add(4)), // FENCE.I refetches
+            (0b1110011, 0b000) => match instr.imm & 0xFFF {
+                0x000 => return Err(Trap { cause: CAUSE_ECALL_M, tval: 0 }),
+                0x001 => return Err(Trap { cause: CAUSE_BREAKPOINT, tval: instr.pc }),
+                0x302 => redirect = Some(csrs.mret()),
+                0x105 => {} // WFI
+                _ => return Err(illegal),
+            },
+            (0b1110011, 0b001..=0b011 | 0b101..=0b111) => {
+                let csr = (instr.imm as u32 & 0xFFF) as u16;
+                let old = csrs.read(csr).ok_or(illegal)?;
+                // Immediate forms take the rs1 field as a zero-extended value
+                let src = if instr.funct3 & 0b100 != 0 { instr.rs1 as u32 } else { rs1 };
+                let value = match instr.funct3 & 0b11 {
+                    0b01 => Some(src),
+                    // Set and clear with a zero source do not write
+                    _ if instr.rs1 == 0 => None,
+                    0b10 => Some(old | src),
+                    _ => Some(old & !src),
+                };
+                if let Some(value) = value && !csrs.write(csr, value) {
+                    return Err(illegal);
+                }
+                self.result = old;
+            }
+            (0b1010111, 0b111) => {
+                let (avl, vtype) = match instr.mnemonic() {
+                    "vsetivli" => (instr.rs1 as u32, instr.imm as u32),
+                    "vsetvli" => (rs1, instr.imm as u32),
+                    "vsetvl" => (rs1, rs2),
+                    _ => return Err(illegal),
+                };
+                // An immediate AVL is always used, a register AVL of x0 requests VLMAX,
+                // or keeps vl unchanged when rd is x0 as well
+                let avl = match (instr.mnemonic(), instr.rs1, instr.rd) {
+                    ("vsetivli", ..) => Some(avl),
+                    (_, 0, 0) => None,
+                    (_, 0, _) => Some(u32::MAX),
+                    _ => Some(avl),
+                };
+                self.result = csrs.vector.set_vl(avl, vtype);
+            }
+            _ => return Err(illegal),
+        }
+
         self.busy = true;
         self.remaining = 1;
         self.current = Some(instr);
+        Ok(redirect)
     }
 
-    pub fn tick(&mut self) -> Option<Instruction> {
+    pub fn tick(&mut self) -> Option<(Instruction, u32)> {
         if self.busy {
             if self.remaining > 0 {
                 self.remaining -= 1;
             } else {
                 self.busy = false;
-                return self.current.take();
+                return self.current.take().map(|instr| (instr, self.result));
             }
         }
         None
     }
-}
\ No newline at end of file
+}
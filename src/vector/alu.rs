@@ -0,0 +1,526 @@
+use crate::common::float::Format;
+use crate::scalar::csr::{Trap, CAUSE_ILLEGAL_INSTRUCTION};
+use crate::scalar::instruction::Instruction;
+use crate::vector::config::{VectorConfig, Vtype, ELEN};
+use crate::vector::decode::{Convert, Op, Operand, Shape, VectorOp};
+use crate::vector::regfile::{group_mask, VectorRegisterFile};
+
+/// Default datapath width in bits, one 128-bit register per cycle at LMUL=1
+pub const DEFAULT_DATAPATH_BITS: u32 = 128;
+/// Extra pipeline cycles of the multipliers
+const MUL_LATENCY: u32 = 2;
+
+/// Timed vector integer execution unit.
+///
+/// Instructions execute functionally at issue and keep the unit busy while the
+/// datapath works through the body: each cycle processes `datapath_bits` worth of
+/// elements of the widest operand, so halving SEW doubles the elements per cycle.
+/// Multiplies add pipeline latency, divides iterate one bit per cycle and reductions
+/// add the stages of their lane tree.
+pub struct VectorAlu {
+    pub busy: bool,
+    pub remaining: u32,
+    pub current: Option<Instruction>,
+    /// Registers used by the current instruction
+    pub regs: u32,
+    /// Scalar result written back to `x[rd]` on completion
+    pub result: u32,
+    /// Datapath width in bits
+    pub datapath_bits: u32,
+}
+
+/// Element widths in bits of an operation's destination and sources
+pub struct Widths {
+    pub vd: u32,
+    pub vs2: u32,
+    pub vs1: u32,
+}
+
+impl VectorAlu {
+    /// Create a unit with a datapath of `datapath_bits`
+    pub fn new(datapath_bits: u32) -> Self {
+        Self { busy: false, remaining: 0, current: None, regs: 0, result: 0, datapath_bits }
+    }
+
+    /// Issue a vector arithmetic instruction with `rs1` holding `x[rs1]`.
+    /// Unimplemented encodings, a `vill` configuration and misaligned register groups raise
+    /// illegal instruction and leave the unit free.
+    pub fn issue(
+        &mut self,
+        instr: Instruction,
+        rs1: u32,
+        config: &mut VectorConfig,
+        vregs: &mut VectorRegisterFile,
+    ) -> Result<(), Trap> {
+        let illegal = Trap { cause: CAUSE_ILLEGAL_INSTRUCTION, tval: instr.raw };
+        let (Some(op), Some(vtype)) = (VectorOp::decode(&instr), config.vtype()) else {
+            return Err(illegal);
+        };
+        let widths = widths(&op, vtype.sew);
+        if !is_legal(&op, vtype, &widths, config.vstart) {
+            return Err(illegal);
+        }
+
+        self.result = execute(&op, rs1, vtype.sew, &widths, config, vregs);
+        let elements = config.vl.saturating_sub(config.vstart);
+        config.vstart = 0;
+
+        self.busy = true;
+        self.remaining = self.cycles(&op, vtype.sew, &widths, elements);
+        self.current = Some(instr);
+        self.regs = registers(&op, vtype, &widths);
+        Ok(())
+    }
+
+    /// Cycles the datapath needs for `elements` body elements
+    fn cycles(&self, op: &VectorOp, sew: u32, widths: &Widths, elements: u32) -> u32 {
+        let per_cycle = (self.datapath_bits / widths.vd.max(widths.vs2)).max(1);
+        let passes = elements.div_ceil(per_cycle).max(1);
+        let latency = match op.op {
+            Op::Mul | Op::Mulh | Op::Macc | Op::Nmsac | Op::Madd | Op::Nmsub | Op::SatMul => MUL_LATENCY,
+            Op::Div | Op::Rem => sew,
+            _ => 0,
+        };
+        let tree = match op.shape {
+            Shape::Reduce | Shape::WidenReduce => per_cycle.ilog2(),
+            // Mask bits are counted a full datapath at a time
+            Shape::ToScalar if op.op != Op::MoveToScalar => {
+                return elements.div_ceil(self.datapath_bits).max(1) + self.datapath_bits.ilog2();
+            }
+            _ => 0,
+        };
+        passes + latency + tree
+    }
+
+    pub fn tick(&mut self) -> Option<(Instruction, u32)> {
+        if self.busy {
+            if self.remaining > 0 {
+                self.remaining -= 1;
+            } else {
+                self.busy = false;
+                return self.current.take().map(|instr| (instr, self.result));
+            }
+        }
+        None
+    }
+}
+
+impl Default for VectorAlu {
+    fn default() -> Self {
+        Self::new(DEFAULT_DATAPATH_BITS)
+    }
+}
+
+/// Registers a vector arithmetic instruction would use under `config`, `None` if it is illegal
+pub fn registers_of(instr: &Instruction, config: &VectorConfig) -> Option<u32> {
+    let op = VectorOp::decode(instr)?;
+    let vtype = config.vtype()?;
+    let widths = widths(&op, vtype.sew);
+    is_legal(&op, vtype, &widths, config.vstart).then(|| registers(&op, vtype, &widths))
+}
+
+fn registers(op: &VectorOp, vtype: Vtype, widths: &Widths) -> u32 {
+    if op.shape == Shape::ToScalar {
+        return group_mask(op.vs2, 1) | op.masked as u32;
+    }
+    // Masks and reduction scalars occupy a single register
+    let reduction = matches!(op.shape, Shape::Reduce | Shape::WidenReduce);
+    let vd_regs = if reduction || op.shape == Shape::Compare { 1 } else { group_regs(vtype, widths.vd) };
+    let mut regs = group_mask(op.vd, vd_regs) | group_mask(op.vs2, group_regs(vtype, widths.vs2));
+    if let Operand::Vector(vs1) = op.operand {
+        regs |= group_mask(vs1, if reduction { 1 } else { group_regs(vtype, widths.vs1) });
+    }
+    if op.masked {
+        regs |= 1;
+    }
+    regs
+}
+
+/// Element widths of an operation under `sew`
+pub fn widths(op: &VectorOp, sew: u32) -> Widths {
+    let wide = 2 * sew;
+    match op.shape {
+        Shape::Single | Shape::Compare | Shape::ToScalar => Widths { vd: sew, vs2: sew, vs1: sew },
+        Shape::Widen => Widths { vd: wide, vs2: sew, vs1: sew },
+        Shape::WidenWide => Widths { vd: wide, vs2: wide, vs1: sew },
+        Shape::Narrow => Widths { vd: sew, vs2: wide, vs1: sew },
+        Shape::Reduce => Widths { vd: sew, vs2: sew, vs1: sew },
+        Shape::WidenReduce => Widths { vd: wide, vs2: sew, vs1: wide },
+    }
+}
+
+/// Registers in a group of elements `width` bits wide, scaling LMUL by `width / SEW`
+fn group_regs(vtype: Vtype, width: u32) -> u8 {
+    let emul_log2 = vtype.lmul_log2 + (width / vtype.sew).ilog2() as i32;
+    1 << emul_log2.max(0)
+}
+
+/// Check the configuration and register operands of an operation against the RVV constraints
+fn is_legal(op: &VectorOp, vtype: Vtype, widths: &Widths, vstart: u32) -> bool {
+    let widening = widths.vd.max(widths.vs2).max(widths.vs1) > vtype.sew;
+    let reduction = matches!(op.shape, Shape::Reduce | Shape::WidenReduce);
+    if widening && (2 * vtype.sew > ELEN || (!reduction && vtype.lmul_log2 >= 3)) {
+        return false;
+    }
+    if reduction && vstart != 0 {
+        return false;
+    }
+    // Floating-point elements must have a supported format
+    let float = |width: u32| Format::of_width(width).is_some();
+    let floats = match op.op {
+        Op::FConvert(Convert::ToInt { .. }) => float(widths.vs2),
+        Op::FConvert(Convert::FromInt { .. }) => float(widths.vd),
+        _ if op.op.is_float() => float(widths.vd) && float(widths.vs2) && float(widths.vs1),
+        _ => true,
+    };
+    if !floats {
+        return false;
+    }
+    if op.shape == Shape::ToScalar {
+        // vmv.x.s has no masked form, the mask scans must start from element 0
+        return if op.op == Op::MoveToScalar { !op.masked } else { vstart == 0 };
+    }
+
+    let aligned = |reg: u8, width: u32| reg.is_multiple_of(group_regs(vtype, width));
+    let vd_group = !matches!(op.shape, Shape::Compare) && !reduction;
+    if (vd_group && !aligned(op.vd, widths.vd)) || !aligned(op.vs2, widths.vs2) {
+        return false;
+    }
+    if let Operand::Vector(vs1) = op.operand
+        && !reduction
+        && !aligned(vs1, widths.vs1)
+    {
+        return false;
+    }
+    // A masked operation may not overwrite the mask in v0, except with a single mask or element
+    if op.masked && vd_group && op.vd == 0 {
+        return false;
+    }
+    // A destination may overlap a source of another width only where RVV 1.0 allows:
+    // a wider destination in the highest-numbered part of its group when the source
+    // EMUL is at least 1, a narrower destination in the lowest-numbered part of the source
+    let legal_overlap = |reg: u8, width: u32| {
+        let (d, dn) = (op.vd, group_regs(vtype, widths.vd));
+        let (s, sn) = (reg, group_regs(vtype, width));
+        if width == widths.vd || !(s < d + dn && d < s + sn) {
+            true
+        } else if widths.vd > width {
+            vtype.lmul_log2 + (width / vtype.sew).ilog2() as i32 >= 0 && s + sn == d + dn
+        } else {
+            s == d
+        }
+    };
+    if vd_group
+        && (!legal_overlap(op.vs2, widths.vs2)
+            || matches!(op.operand, Operand::Vector(vs1) if !legal_overlap(vs1, widths.vs1)))
+    {
+        return false;
+    }
+    true
+}
+
+/// Execute an operation on the body elements from `vstart` to `vl`, returning the scalar result.
+/// Inactive and tail elements are left undisturbed.
+fn execute(
+    op: &VectorOp,
+    rs1: u32,
+    sew: u32,
+    widths: &Widths,
+    config: &mut VectorConfig,
+    vregs: &mut VectorRegisterFile,
+) -> u32 {
+    let active = |vregs: &VectorRegisterFile, i: u32| !op.masked || vregs.mask_bit(0, i);
+    let a = |vregs: &VectorRegisterFile, i: u32| extend(vregs.read(op.vs2, i, widths.vs2), widths.vs2, op.signed_vs2);
+    let b = |vregs: &VectorRegisterFile, i: u32| {
+        let value = match op.operand {
+            Operand::Vector(vs1) => vregs.read(vs1, i, widths.vs1),
+            Operand::Scalar => rs1,
+            Operand::Immediate(imm) => imm as u32,
+        };
+        extend(value & mask(widths.vs1), widths.vs1, op.signed_vs1)
+    };
+
+    if op.shape == Shape::ToScalar {
+        let mut set = (0..config.vl).filter(|i| active(vregs, *i) && vregs.mask_bit(op.vs2, *i));
+        return match op.op {
+            Op::MoveToScalar => extend(vregs.read(op.vs2, 0, sew), sew, true) as u32,
+            Op::Popcount => set.count() as u32,
+            _ => set.next().unwrap_or(u32::MAX),
+        };
+    }
+
+    if matches!(op.shape, Shape::Reduce | Shape::WidenReduce) {
+        if config.vl == 0 {
+            return 0;
+        }
+        let mut acc = b(vregs, 0);
+        for i in (0..config.vl).filter(|i| active(vregs, *i)) {
+            acc = compute(op.op, acc, a(vregs, i), 0, sew, config.vxrm);
+        }
+        vregs.write(op.vd, 0, widths.vd, acc as u32);
+        return 0;
+    }
+
+    let results: Vec<(u32, i128)> = (config.vstart..config.vl)
+        .filter(|i| active(vregs, *i))
+        .map(|i| {
+            let d = extend(vregs.read(op.vd, i, widths.vd), widths.vd, true);
+            let shift = b(vregs, i) & (widths.vs2 as i128 - 1);
+            let b = if matches!(op.op, Op::Sll | Op::Shr | Op::ScaleShr | Op::Clip) { shift } else { b(vregs, i) };
+            (i, compute(op.op, a(vregs, i), b, d, sew, config.vxrm))
+        })
+        .collect();
+    for (i, value) in results {
+        if matches!(op.op, Op::SatAdd | Op::SatSub | Op::SatMul | Op::Clip) {
+            // The result signedness follows vs2, vnclip clips a signed value to SEW
+            let (min, max) = if op.signed_vs2 {
+                (-(1i128 << (widths.vd - 1)), (1i128 << (widths.vd - 1)) - 1)
+            } else {
+                (0, (1i128 << widths.vd) - 1)
+            };
+            let clipped = value.clamp(min, max);
+            config.vxsat |= clipped != value;
+            vregs.write(op.vd, i, widths.vd, clipped as u32);
+        } else if op.shape == Shape::Compare {
+            vregs.set_mask_bit(op.vd, i, value != 0);
+        } else {
+            vregs.write(op.vd, i, widths.vd, value as u32);
+        }
+    }
+    0
+}
+
+/// Compute one element from `a` (`vs2`), `b` (`vs1`, `rs1` or the immediate) and `d` (`vd`).
+/// Operands are already extended, so results are exact before truncation or saturation
+/// to the element width. Fixed-point results are rounded according to `vxrm`.
+fn compute(op: Op, a: i128, b: i128, d: i128, sew: u32, vxrm: u32) -> i128 {
+    match op {
+        Op::Add => a + b,
+        Op::Sub => a - b,
+        Op::Rsub => b - a,
+        Op::Min => a.min(b),
+        Op::Max => a.max(b),
+        Op::And => a & b,
+        Op::Or => a | b,
+        Op::Xor => a ^ b,
+        Op::Sll => a << b,
+        Op::Shr => a >> b,
+        Op::Eq => (a == b) as i128,
+        Op::Ne => (a != b) as i128,
+        Op::Lt => (a < b) as i128,
+        Op::Le => (a <= b) as i128,
+        Op::Gt => (a > b) as i128,
+        Op::Mul => a * b,
+        Op::Mulh => (a * b) >> sew,
+        // Division by zero gives all ones and the remainder the dividend, like the scalar M extension
+        Op::Div if b == 0 => -1,
+        Op::Div => a / b,
+        Op::Rem if b == 0 => a,
+        Op::Rem => a % b,
+        Op::Macc => d + b * a,
+        Op::Nmsac => d - b * a,
+        Op::Madd => b * d + a,
+        Op::Nmsub => a - b * d,
+        Op::SatAdd => a + b,
+        Op::SatSub => a - b,
+        Op::AvgAdd => round_shift(a + b, 1, vxrm),
+        Op::AvgSub => round_shift(a - b, 1, vxrm),
+        Op::SatMul => round_shift(a * b, sew - 1, vxrm),
+        Op::ScaleShr | Op::Clip => round_shift(a, b as u32, vxrm),
+        // Scalar results are computed over the whole mask in `execute`
+        Op::MoveToScalar | Op::Popcount | Op::FindFirst => 0,
+        // Floating-point operations execute in the FPU
+        _ => 0,
+    }
+}
+
+/// Shift `value` right by `shift` bits, rounding the discarded bits by `vxrm`:
+/// round-to-nearest-up, round-to-nearest-even, round-down or round-to-odd
+pub fn round_shift(value: i128, shift: u32, vxrm: u32) -> i128 {
+    if shift == 0 {
+        return value;
+    }
+    let bit = |n: u32| (value >> n) & 1;
+    let discarded = value & ((1 << shift) - 1);
+    let increment = match vxrm {
+        0 => bit(shift - 1),
+        1 => bit(shift - 1) & ((discarded & ((1 << (shift - 1)) - 1) != 0) as i128 | bit(shift)),
+        2 => 0,
+        _ => (bit(shift) == 0 && discarded != 0) as i128,
+    };
+    (value >> shift) + increment
+}
+
+/// Sign- or zero-extend the low `bits` of `value`
+fn extend(value: u32, bits: u32, signed: bool) -> i128 {
+    let shift = 32 - bits;
+    if signed { (((value << shift) as i32) >> shift) as i128 } else { ((value << shift) >> shift) as i128 }
+}
+
+/// Mask of the low `bits` bits
+fn mask(bits: u32) -> u32 {
+    u32::MAX >> (32 - bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::instruction::RawInstruction;
+    use crate::vector::decode::{OPIVV, OPIVX, OPMVV};
+
+    const VSMUL: u32 = 0b100111;
+    const VNSRL: u32 = 0b101100;
+    const VWADDU: u32 = 0b110000;
+    const VWADDU_W: u32 = 0b110100;
+    const VNCLIPU: u32 = 0b101110;
+    const VNCLIP: u32 = 0b101111;
+    const RNU: u32 = 0;
+    const RNE: u32 = 1;
+    const RDN: u32 = 2;
+    const ROD: u32 = 3;
+
+    /// Execute an unmasked SEW=8 instruction on element 0 with `vd = v1`, `vs2 = v2` and
+    /// `vs1 = v4` or `x4`, returning the result byte and `vxsat`
+    fn run_e8(funct6: u32, funct3: u8, vxrm: u32, vs2: u32, vs1: u32) -> (u32, bool) {
+        let raw = (funct6 << 26) | (1 << 25) | (2 << 20) | (4 << 15) | ((funct3 as u32) << 12) | (1 << 7) | 0b1010111;
+        let instr = Instruction::from(RawInstruction { pc: 0, data: raw });
+        let mut config = VectorConfig::default();
+        config.set_vl(Some(1), 0); // e8, m1
+        config.vxrm = vxrm;
+        let mut vregs = VectorRegisterFile::new(config.vlen);
+        // Narrowing clips read a double-width vs2
+        vregs.write(2, 0, if funct6 == VSMUL { 8 } else { 16 }, vs2);
+        vregs.write(4, 0, 8, vs1);
+        VectorAlu::default().issue(instr, vs1, &mut config, &mut vregs).expect("legal instruction");
+        (vregs.read(1, 0, 8), config.vxsat)
+    }
+
+    #[test]
+    fn round_shift_follows_vxrm() {
+        // (value, shift, [rnu, rne, rdn, rod])
+        let cases: [(i128, u32, [i128; 4]); 10] = [
+            (8, 2, [2, 2, 2, 2]),
+            (9, 2, [2, 2, 2, 3]),
+            (10, 2, [3, 2, 2, 3]),
+            (11, 2, [3, 3, 2, 3]),
+            (14, 2, [4, 4, 3, 3]),
+            (5, 1, [3, 2, 2, 3]),
+            (7, 1, [4, 4, 3, 3]),
+            (-5, 1, [-2, -2, -3, -3]),
+            (-7, 1, [-3, -4, -4, -3]),
+            (-11, 2, [-3, -3, -3, -3]),
+        ];
+        for (value, shift, expected) in cases {
+            for (vxrm, want) in [RNU, RNE, RDN, ROD].into_iter().zip(expected) {
+                assert_eq!(round_shift(value, shift, vxrm), want, "{} >> {} with vxrm {}", value, shift, vxrm);
+            }
+        }
+    }
+
+    #[test]
+    fn narrowing_clips_round_and_saturate() {
+        // (funct6, vxrm, vs2, shift, result, vxsat)
+        let cases = [
+            (VNCLIP, RNU, 10, 2, 3, false),
+            (VNCLIP, RNE, 10, 2, 2, false),
+            (VNCLIP, RDN, 11, 2, 2, false),
+            (VNCLIP, ROD, 8, 2, 2, false),
+            (VNCLIP, ROD, 9, 2, 3, false),
+            (VNCLIP, RNU, -11i16 as u16 as u32, 2, -3i8 as u8 as u32, false),
+            (VNCLIP, RNU, 1000, 2, 0x7F, true),
+            (VNCLIP, RNU, -1000i16 as u16 as u32, 2, 0x80, true),
+            (VNCLIP, RNU, 0x7FFF, 8, 0x7F, true),
+            (VNCLIP, RNU, 0x7F80, 8, 0x7F, true),
+            (VNCLIPU, RNU, 40, 4, 3, false),
+            (VNCLIPU, RNE, 40, 4, 2, false),
+            (VNCLIPU, RDN, 47, 4, 2, false),
+            (VNCLIPU, ROD, 32, 4, 2, false),
+            (VNCLIPU, RNU, 0xFFFF, 4, 0xFF, true),
+            // Rounding up can carry past the largest result
+            (VNCLIPU, RNU, 0x0FF8, 4, 0xFF, true),
+            (VNCLIPU, RDN, 0x0FF8, 4, 0xFF, false),
+            (VNCLIPU, RNU, 0x0FF8, 3, 0xFF, true),
+            // Only the low log2(2 * SEW) bits of the shift are used
+            (VNCLIPU, RNU, 0x0100, 0x14, 0x10, false),
+        ];
+        for (funct6, vxrm, vs2, shift, result, vxsat) in cases {
+            assert_eq!(
+                run_e8(funct6, OPIVX, vxrm, vs2, shift),
+                (result, vxsat),
+                "funct6 {:06b} vxrm {} vs2 0x{:04x} shift {}", funct6, vxrm, vs2, shift
+            );
+        }
+    }
+
+    #[test]
+    fn vsmul_rounds_and_saturates() {
+        let n = |v: i8| v as u8 as u32;
+        // (vxrm, vs2, vs1, result, vxsat)
+        let cases = [
+            (RNU, n(64), n(64), n(32), false),
+            (RNU, n(-128), n(64), n(-64), false),
+            // 3 * -85 = -255, -1.99 after the shift by 7
+            (RNU, n(3), n(-85), n(-2), false),
+            (RNE, n(3), n(-85), n(-2), false),
+            (RDN, n(3), n(-85), n(-2), false),
+            (ROD, n(3), n(-85), n(-1), false),
+            // 3 * 64 = 192, 1.5 after the shift by 7
+            (RNU, n(3), n(64), n(2), false),
+            (RNE, n(3), n(64), n(2), false),
+            (RDN, n(3), n(64), n(1), false),
+            (ROD, n(3), n(64), n(1), false),
+            // -1.0 * -1.0 is the only product that overflows
+            (RNU, n(-128), n(-128), n(127), true),
+            (RDN, n(-128), n(-128), n(127), true),
+            (RNU, n(-128), n(127), n(-127), false),
+        ];
+        for (vxrm, vs2, vs1, result, vxsat) in cases {
+            assert_eq!(
+                run_e8(VSMUL, OPIVV, vxrm, vs2, vs1),
+                (result, vxsat),
+                "vsmul 0x{:02x} * 0x{:02x} vxrm {}", vs2, vs1, vxrm
+            );
+        }
+    }
+
+    /// Whether an unmasked `funct6 vd, vs2, vs1` vector-vector instruction is legal at SEW=8
+    fn legal(funct6: u32, funct3: u8, lmul_log2: i32, vd: u32, vs2: u32, vs1: u32) -> bool {
+        let raw = (funct6 << 26) | (1 << 25) | (vs2 << 20) | (vs1 << 15) | ((funct3 as u32) << 12) | (vd << 7) | 0b1010111;
+        let op = VectorOp::decode(&Instruction::from(RawInstruction { pc: 0, data: raw })).expect("vector operation");
+        let vtype = Vtype { sew: 8, lmul_log2, ta: false, ma: false };
+        is_legal(&op, vtype, &widths(&op, vtype.sew), 0)
+    }
+
+    #[test]
+    fn mixed_width_overlap_follows_rvv() {
+        // (funct6, funct3, lmul_log2, vd, vs2, vs1, legal)
+        let cases = [
+            (VWADDU, OPMVV, 0, 2, 4, 6, true),
+            // A wide destination may overlap a narrow source in its highest-numbered part
+            (VWADDU, OPMVV, 0, 2, 3, 6, true),
+            (VWADDU, OPMVV, 0, 2, 6, 3, true),
+            (VWADDU, OPMVV, 0, 2, 2, 6, false),
+            (VWADDU, OPMVV, 0, 2, 6, 2, false),
+            (VWADDU, OPMVV, 1, 4, 6, 8, true),
+            (VWADDU, OPMVV, 1, 4, 4, 8, false),
+            // but not when the source EMUL is fractional
+            (VWADDU, OPMVV, -1, 2, 2, 6, false),
+            // A wide source may be the wide destination itself
+            (VWADDU_W, OPMVV, 0, 2, 2, 3, true),
+            (VWADDU_W, OPMVV, 0, 2, 2, 2, false),
+            // A narrow destination may overlap the lowest-numbered part of a wide source
+            (VNSRL, OPIVV, 0, 2, 2, 6, true),
+            (VNSRL, OPIVV, 0, 3, 2, 6, false),
+            (VNSRL, OPIVV, 0, 4, 2, 4, true),
+            (VNSRL, OPIVV, 1, 4, 4, 8, true),
+            (VNSRL, OPIVV, 1, 6, 4, 8, false),
+        ];
+        for (funct6, funct3, lmul_log2, vd, vs2, vs1, expected) in cases {
+            assert_eq!(
+                legal(funct6, funct3, lmul_log2, vd, vs2, vs1),
+                expected,
+                "funct6 {:06b} lmul_log2 {} v{}, v{}, v{}", funct6, lmul_log2, vd, vs2, vs1
+            );
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use tracing::debug;
+use crate::scalar::csr::{CsrFile, Trap, CAUSE_ILLEGAL_INSTRUCTION};
+use crate::scalar::instruction::Instruction;
+use crate::scalar::memory::DataBus;
+use crate::vector::alu::{self, VectorAlu};
+use crate::vector::config::DEFAULT_VLEN;
+use crate::vector::decode::{VectorOp, VectorPermuteOp, LOAD_FP, STORE_FP};
+use crate::vector::fpu::{self, VectorFpu};
+use crate::vector::lsu::{self, VectorLsu};
+use crate::vector::permute::{self, VectorPermuteUnit};
+use crate::vector::regfile::VectorRegisterFile;
+
+/// Default number of instructions the vector queue holds
+pub const DEFAULT_QUEUE_CAPACITY: usize = 8;
+
+/// A vector instruction waiting in the queue with the scalar operands captured at dispatch
+#[derive(Copy, Clone, Debug)]
+pub struct QueuedInstruction {
+    pub instr: Instruction,
+    pub rs1: u32,
+    pub rs2: u32,
+    /// Vector registers the instruction reads or writes
+    pub regs: u32,
+}
+
+/// Vector unit an instruction executes in
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Unit {
+    Alu,
+    Lsu,
+    Permute,
+    Fpu,
+}
+
+impl QueuedInstruction {
+    /// Whether the instruction executes in the vector LSU
+    fn is_memory(&self) -> bool {
+        matches!(self.instr.opcode, LOAD_FP | STORE_FP)
+    }
+
+    /// Unit the instruction executes in
+    fn unit(&self) -> Unit {
+        unit_of(&self.instr)
+    }
+}
+
+/// Unit a vector instruction executes in
+fn unit_of(instr: &Instruction) -> Unit {
+    if matches!(instr.opcode, LOAD_FP | STORE_FP) {
+        Unit::Lsu
+    } else if VectorPermuteOp::decode(instr).is_some() {
+        Unit::Permute
+    } else if VectorOp::decode(instr).is_some_and(|op| op.op.is_float()) {
+        Unit::Fpu
+    } else {
+        Unit::Alu
+    }
+}
+
+/// Queue of vector instructions between scalar dispatch and the vector units
+pub struct VectorQueue {
+    pub inner: VecDeque<QueuedInstruction>,
+    pub capacity: usize,
+}
+
+impl VectorQueue {
+    /// Create a new VectorQueue with the given capacity
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Whether the queue has no space left
+    pub fn is_full(&self) -> bool {
+        self.inner.len() >= self.capacity
+    }
+}
+
+/// What happened in the vector units during one cycle
+pub struct VectorEvents {
+    /// Completed instructions with their scalar results
+    pub completed: Vec<(Instruction, u32)>,
+    /// Load or store that faulted when it executed
+    pub fault: Option<(Instruction, Trap)>,
+}
+
+/// Vector backend fed by the scalar dispatch stage.
+///
+/// Dispatch checks a vector instruction against the current `vtype`, so illegal
+/// instructions still trap precisely, and queues it with the `x[rs1]`/`x[rs2]` values
+/// it read. Queued instructions issue in order, at most one per unit and cycle, once
+/// no executing instruction shares a register with them, and execute at that point.
+/// Configuration changes wait for the backend to drain, so an instruction always
+/// executes under the `vtype` it was checked against. Scalar results such as `vmv.x.s`
+/// are handed back to dispatch for writeback when their unit completes.
+///
+/// Memory faults are only found once a load or store executes. Dispatch holds back
+/// younger scalar instructions while a load or store is queued, so a fault only
+/// discards younger vector instructions and remains precise.
+pub struct VectorBackend {
+    pub queue: VectorQueue,
+    pub alu: VectorAlu,
+    pub lsu: VectorLsu,
+    pub permute: VectorPermuteUnit,
+    pub fpu: VectorFpu,
+    pub regs: VectorRegisterFile,
+}
+
+impl VectorBackend {
+    /// Create a backend with registers of `vlen` bits and a queue of `capacity` instructions
+    pub fn new(vlen: u32, capacity: usize) -> Self {
+        Self {
+            queue: VectorQueue::new(capacity),
+            alu: VectorAlu::default(),
+            lsu: VectorLsu::default(),
+            permute: VectorPermuteUnit::default(),
+            fpu: VectorFpu::default(),
+            regs: VectorRegisterFile::new(vlen),
+        }
+    }
+
+    /// Queue a vector instruction with its scalar operands, raising illegal instruction
+    /// if it is not valid under the vector configuration and `frm` in `csrs`. The caller
+    /// checks that the queue has room.
+    pub fn dispatch(&mut self, instr: Instruction, rs1: u32, rs2: u32, csrs: &CsrFile) -> Result<(), Trap> {
+        let config = &csrs.vector;
+        let regs = match unit_of(&instr) {
+            Unit::Alu => alu::registers_of(&instr, config),
+            Unit::Lsu => lsu::registers_of(&instr, config),
+            Unit::Permute => permute::registers_of(&instr, config),
+            Unit::Fpu => fpu::registers_of(&instr, config, &csrs.fcsr),
+        };
+        let regs = regs.ok_or(Trap { cause: CAUSE_ILLEGAL_INSTRUCTION, tval: instr.raw })?;
+        self.queue.inner.push_back(QueuedInstruction { instr, rs1, rs2, regs });
+        Ok(())
+    }
+
+    /// Issue queued instructions and advance the units. Instructions sharing a
+    /// register with `external`, the registers the matrix engine is using, wait.
+    /// Loads and stores wait while `scalar_memory` reports an older scalar access in flight.
+    pub fn tick(&mut self, csrs: &mut CsrFile, bus: &mut DataBus, external: u32, scalar_memory: bool) -> VectorEvents {
+        let config = &mut csrs.vector;
+        let mut fault = None;
+        while let Some(&entry) = self.queue.inner.front() {
+            let in_flight = [
+                (self.alu.busy, self.alu.regs),
+                (self.lsu.busy, self.lsu.regs),
+                (self.permute.busy, self.permute.regs),
+                (self.fpu.busy, self.fpu.regs),
+                (true, external),
+            ];
+            if in_flight.iter().any(|&(busy, regs)| busy && regs & entry.regs != 0) {
+                debug!("Vector stall: register hazard for {}", entry.instr);
+                break;
+            }
+            if entry.is_memory() && scalar_memory {
+                debug!("Vector stall: {} waits for older scalar memory accesses", entry.instr);
+                break;
+            }
+            let unit = entry.unit();
+            let busy = match unit {
+                Unit::Alu => self.alu.busy,
+                Unit::Lsu => self.lsu.busy,
+                Unit::Permute => self.permute.busy,
+                Unit::Fpu => self.fpu.busy,
+            };
+            if busy {
+                debug!("Vector stall: no free unit for {}", entry.instr);
+                break;
+            }
+
+            self.queue.inner.pop_front();
+            debug!("Vector issued: {}", entry.instr);
+            let issued = match unit {
+                Unit::Alu => self.alu.issue(entry.instr, entry.rs1, config, &mut self.regs),
+                Unit::Lsu => self.lsu.issue(entry.instr, entry.rs1, entry.rs2, config, &mut self.regs, bus),
+                Unit::Permute => self.permute.issue(entry.instr, entry.rs1, config, &mut self.regs),
+                Unit::Fpu => self.fpu.issue(entry.instr, entry.rs1, config, &mut csrs.fcsr, &mut self.regs),
+            };
+            if let Err(trap) = issued {
+                fault = Some((entry.instr, trap));
+                break;
+            }
+        }
+
+        let completed = [self.alu.tick(), self.lsu.tick(bus), self.permute.tick(), self.fpu.tick()].into_iter().flatten().collect();
+        VectorEvents { completed, fault }
+    }
+
+    /// Discard the queued instructions, returning them
+    pub fn flush(&mut self) -> Vec<Instruction> {
+        self.queue.inner.drain(..).map(|entry| entry.instr).collect()
+    }
+
+    /// Whether a queued load or store has yet to access memory
+    pub fn has_pending_memory(&self) -> bool {
+        self.queue.inner.iter().any(QueuedInstruction::is_memory)
+    }
+
+    /// Whether a queued or executing instruction uses any register in `regs`
+    pub fn uses(&self, regs: u32) -> bool {
+        let units = [
+            (self.alu.busy, self.alu.regs),
+            (self.lsu.busy, self.lsu.regs),
+            (self.permute.busy, self.permute.regs),
+            (self.fpu.busy, self.fpu.regs),
+        ];
+        self.queue.inner.iter().any(|entry| entry.regs & regs != 0)
+            || units.iter().any(|&(busy, used)| busy && used & regs != 0)
+    }
+
+    /// Whether no vector instruction is queued or executing
+    pub fn is_idle(&self) -> bool {
+        self.queue.inner.is_empty() && !self.alu.busy && !self.lsu.busy && !self.permute.busy && !self.fpu.busy
+    }
+}
+
+impl Default for VectorBackend {
+    fn default() -> Self {
+        Self::new(DEFAULT_VLEN, DEFAULT_QUEUE_CAPACITY)
+    }
+}
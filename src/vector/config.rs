@@ -0,0 +1,124 @@
+use std::fmt::{Display, Formatter};
+
+/// Widest element the vector unit supports, in bits
+pub const ELEN: u32 = 32;
+/// Default vector register length in bits
+pub const DEFAULT_VLEN: u32 = 128;
+
+/// `vtype` bit marking an unsupported configuration
+pub const VTYPE_VILL: u32 = 1 << 31;
+
+/// Decoded fields of a `vtype` value
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Vtype {
+    /// Selected element width in bits
+    pub sew: u32,
+    /// Register group multiplier as a power of two, -3 (mf8) to 3 (m8)
+    pub lmul_log2: i32,
+    /// Tail agnostic
+    pub ta: bool,
+    /// Mask agnostic
+    pub ma: bool,
+}
+
+impl Vtype {
+    /// Decode a `vtype` value, or `None` if it is reserved or unsupported for `ELEN`
+    pub fn decode(bits: u32) -> Option<Self> {
+        let vsew = (bits >> 3) & 0b111;
+        let vlmul = bits & 0b111;
+        if bits & VTYPE_VILL != 0 || bits >> 8 != 0 || vsew > 3 || vlmul == 0b100 {
+            return None;
+        }
+        let vtype = Self {
+            sew: 8 << vsew,
+            // vlmul is a signed 3-bit field
+            lmul_log2: ((vlmul << 29) as i32) >> 29,
+            ta: bits & (1 << 6) != 0,
+            ma: bits & (1 << 7) != 0,
+        };
+        // A fractional group only holds elements up to LMUL * ELEN wide
+        let max_sew = ELEN >> (-vtype.lmul_log2).max(0);
+        (vtype.sew <= max_sew).then_some(vtype)
+    }
+
+    /// Encode back to `vtype` bits
+    pub fn bits(&self) -> u32 {
+        let vsew = self.sew.trailing_zeros() - 3;
+        let vlmul = (self.lmul_log2 as u32) & 0b111;
+        ((self.ma as u32) << 7) | ((self.ta as u32) << 6) | (vsew << 3) | vlmul
+    }
+
+    /// Maximum vector length for registers of `vlen` bits
+    pub fn vlmax(&self, vlen: u32) -> u32 {
+        let elements = vlen / self.sew;
+        if self.lmul_log2 >= 0 { elements << self.lmul_log2 } else { elements >> -self.lmul_log2 }
+    }
+
+    /// Number of registers in a group, fractional groups occupy one
+    pub fn group_regs(&self) -> u32 {
+        1 << self.lmul_log2.max(0)
+    }
+}
+
+impl Display for Vtype {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lmul = if self.lmul_log2 >= 0 { format!("m{}", 1 << self.lmul_log2) } else { format!("mf{}", 1 << -self.lmul_log2) };
+        let ta = if self.ta { "ta" } else { "tu" };
+        let ma = if self.ma { "ma" } else { "mu" };
+        write!(f, "e{}, {}, {}, {}", self.sew, lmul, ta, ma)
+    }
+}
+
+/// Vector configuration and fixed-point CSR state
+pub struct VectorConfig {
+    /// Vector register length in bits
+    pub vlen: u32,
+    pub vtype: u32,
+    pub vl: u32,
+    pub vstart: u32,
+    /// Fixed-point rounding mode
+    pub vxrm: u32,
+    /// Fixed-point saturation flag
+    pub vxsat: bool,
+}
+
+impl VectorConfig {
+    /// Create configuration state for registers of `vlen` bits, starting with `vill` set.
+    /// `vlen` must be a power of two no narrower than `ELEN`.
+    pub fn new(vlen: u32) -> Self {
+        assert!(vlen.is_power_of_two() && vlen >= ELEN, "unsupported VLEN {}", vlen);
+        Self { vlen, vtype: VTYPE_VILL, vl: 0, vstart: 0, vxrm: 0, vxsat: false }
+    }
+
+    /// Decoded current `vtype`, `None` while `vill` is set
+    pub fn vtype(&self) -> Option<Vtype> {
+        Vtype::decode(self.vtype)
+    }
+
+    /// Vector register length in bytes
+    pub fn vlenb(&self) -> u32 {
+        self.vlen / 8
+    }
+
+    /// Apply a `vsetvl`-family configuration and return the new `vl`.
+    /// `avl` is `None` to keep the current `vl` (rs1 and rd both x0) and
+    /// `u32::MAX` to request `VLMAX` (rs1 is x0, rd is not).
+    pub fn set_vl(&mut self, avl: Option<u32>, vtype: u32) -> u32 {
+        self.vstart = 0;
+        let Some(decoded) = Vtype::decode(vtype) else {
+            self.vtype = VTYPE_VILL;
+            self.vl = 0;
+            return 0;
+        };
+        let vlmax = decoded.vlmax(self.vlen);
+        self.vtype = vtype;
+        self.vl = avl.unwrap_or(self.vl).min(vlmax);
+        self.vl
+    }
+}
+
+impl Default for VectorConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_VLEN)
+    }
+}
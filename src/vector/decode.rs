@@ -0,0 +1,737 @@
+use crate::scalar::instruction::{sign_extend, Instruction};
+
+/// OP-V operand categories selected by funct3
+pub const OPIVV: u8 = 0b000;
+pub const OPFVV: u8 = 0b001;
+pub const OPMVV: u8 = 0b010;
+pub const OPIVI: u8 = 0b011;
+pub const OPIVX: u8 = 0b100;
+pub const OPFVF: u8 = 0b101;
+pub const OPMVX: u8 = 0b110;
+
+/// Where the first source operand of an arithmetic instruction comes from
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Operand {
+    /// Elements of the `vs1` group
+    Vector(u8),
+    /// `x[rs1]`, captured at issue
+    Scalar,
+    /// The sign-extended 5-bit immediate in the `rs1` field
+    Immediate(i32),
+}
+
+/// Integer operation, with signedness carried separately in `VectorOp`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Op {
+    Add, Sub, Rsub, Min, Max, And, Or, Xor,
+    Sll, Shr,
+    Eq, Ne, Lt, Le, Gt,
+    Mul, Mulh, Div, Rem,
+    /// `vd + vs1 * vs2`
+    Macc,
+    /// `vd - vs1 * vs2`
+    Nmsac,
+    /// `vs1 * vd + vs2`
+    Madd,
+    /// `-(vs1 * vd) + vs2`
+    Nmsub,
+    /// Saturating add and subtract
+    SatAdd, SatSub,
+    /// Averaging add and subtract, rounded by `vxrm`
+    AvgAdd, AvgSub,
+    /// Fractional multiply `(vs2 * vs1) >> (SEW - 1)`, rounded and saturated
+    SatMul,
+    /// Shift right rounded by `vxrm`
+    ScaleShr,
+    /// Narrowing shift right, rounded and saturated to SEW
+    Clip,
+    /// `x[rd] = vs2[0]`
+    MoveToScalar,
+    /// `x[rd]` = number of active set bits of the mask in `vs2`
+    Popcount,
+    /// `x[rd]` = index of the first active set bit of the mask in `vs2`, or -1
+    FindFirst,
+    /// Floating-point operations, rounded by `frm`
+    FAdd, FSub, FMul, FMin, FMax,
+    /// `vd + vs1 * vs2` with a single rounding
+    FMacc,
+    /// Reduction sum in element order
+    FSumOrdered,
+    /// Reduction sum in tree order
+    FSumUnordered,
+    /// Conversion between integers and floating-point
+    FConvert(Convert),
+}
+
+impl Op {
+    /// Whether the operation executes in the floating-point unit
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            Op::FAdd | Op::FSub | Op::FMul | Op::FMin | Op::FMax | Op::FMacc
+                | Op::FSumOrdered | Op::FSumUnordered | Op::FConvert(_)
+        )
+    }
+}
+
+/// Conversion selected by the `vs1` field of `vfcvt`, `vfwcvt` and `vfncvt`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Convert {
+    /// Float to integer, rounded toward zero instead of by `frm` when `truncate` is set
+    ToInt { signed: bool, truncate: bool },
+    /// Integer to float
+    FromInt { signed: bool },
+    /// Float to float, rounded to odd instead of by `frm` when `odd` is set
+    Float { odd: bool },
+}
+
+/// How operand and result element widths relate to SEW
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Shape {
+    /// SEW results from SEW operands
+    Single,
+    /// 2*SEW results from SEW operands
+    Widen,
+    /// 2*SEW results from a 2*SEW `vs2` and a SEW first operand
+    WidenWide,
+    /// SEW results from a 2*SEW `vs2` and a SEW first operand
+    Narrow,
+    /// One mask bit per element into `vd`
+    Compare,
+    /// `vd[0] = vs1[0] op vs2[*]` at SEW
+    Reduce,
+    /// `vd[0] = vs1[0] op vs2[*]` with a 2*SEW accumulator
+    WidenReduce,
+    /// A scalar result written back to `x[rd]`
+    ToScalar,
+}
+
+/// A decoded vector integer arithmetic instruction
+#[derive(Copy, Clone, Debug)]
+pub struct VectorOp {
+    pub op: Op,
+    pub shape: Shape,
+    /// Whether `vs2` elements are signed
+    pub signed_vs2: bool,
+    /// Whether the first source operand is signed
+    pub signed_vs1: bool,
+    pub vd: u8,
+    pub vs2: u8,
+    pub operand: Operand,
+    /// Whether inactive elements are selected by `v0`
+    pub masked: bool,
+}
+
+impl VectorOp {
+    /// Decode an OP-V arithmetic instruction, `None` if it is not an implemented integer operation
+    pub fn decode(instr: &Instruction) -> Option<Self> {
+        mnemonic(instr)?;
+        let funct6 = instr.funct7 >> 1;
+        let opm = instr.funct3 & 0b11 == 0b10;
+        use Op::*;
+        use Shape::*;
+        let (op, shape, signed_vs2, signed_vs1) = match (opm, funct6) {
+            _ if matches!(instr.funct3, OPFVV | OPFVF) => float_entry(instr, funct6)?,
+            (false, 0b000000) => (Add, Single, true, true),
+            (false, 0b000010) => (Sub, Single, true, true),
+            (false, 0b000011) => (Rsub, Single, true, true),
+            (false, 0b000100) => (Min, Single, false, false),
+            (false, 0b000101) => (Min, Single, true, true),
+            (false, 0b000110) => (Max, Single, false, false),
+            (false, 0b000111) => (Max, Single, true, true),
+            (false, 0b001001) => (And, Single, true, true),
+            (false, 0b001010) => (Or, Single, true, true),
+            (false, 0b001011) => (Xor, Single, true, true),
+            (false, 0b011000) => (Eq, Compare, true, true),
+            (false, 0b011001) => (Ne, Compare, true, true),
+            (false, 0b011010) => (Lt, Compare, false, false),
+            (false, 0b011011) => (Lt, Compare, true, true),
+            (false, 0b011100) => (Le, Compare, false, false),
+            (false, 0b011101) => (Le, Compare, true, true),
+            (false, 0b011110) => (Gt, Compare, false, false),
+            (false, 0b011111) => (Gt, Compare, true, true),
+            (false, 0b100101) => (Sll, Single, false, false),
+            (false, 0b101000) => (Shr, Single, false, false),
+            (false, 0b101001) => (Shr, Single, true, false),
+            (false, 0b101100) => (Shr, Narrow, false, false),
+            (false, 0b101101) => (Shr, Narrow, true, false),
+            (false, 0b100000) => (SatAdd, Single, false, false),
+            (false, 0b100001) => (SatAdd, Single, true, true),
+            (false, 0b100010) => (SatSub, Single, false, false),
+            (false, 0b100011) => (SatSub, Single, true, true),
+            // The immediate form is vmv<nr>r.v
+            (false, 0b100111) if instr.funct3 != OPIVI => (SatMul, Single, true, true),
+            (false, 0b101010) => (ScaleShr, Single, false, false),
+            (false, 0b101011) => (ScaleShr, Single, true, false),
+            (false, 0b101110) => (Clip, Narrow, false, false),
+            (false, 0b101111) => (Clip, Narrow, true, false),
+            (false, 0b110000) => (Add, WidenReduce, false, false),
+            (false, 0b110001) => (Add, WidenReduce, true, true),
+
+            // The scalar-operand form is vmv.s.x
+            (true, 0b010000) if instr.funct3 == OPMVV => match instr.rs1 {
+                0b00000 => (MoveToScalar, ToScalar, true, true),
+                0b10000 => (Popcount, ToScalar, false, false),
+                _ => (FindFirst, ToScalar, false, false),
+            },
+            (true, 0b000000) => (Add, Reduce, true, true),
+            (true, 0b000001) => (And, Reduce, true, true),
+            (true, 0b000010) => (Or, Reduce, true, true),
+            (true, 0b000011) => (Xor, Reduce, true, true),
+            (true, 0b000100) => (Min, Reduce, false, false),
+            (true, 0b000101) => (Min, Reduce, true, true),
+            (true, 0b000110) => (Max, Reduce, false, false),
+            (true, 0b000111) => (Max, Reduce, true, true),
+            (true, 0b001000) => (AvgAdd, Single, false, false),
+            (true, 0b001001) => (AvgAdd, Single, true, true),
+            (true, 0b001010) => (AvgSub, Single, false, false),
+            (true, 0b001011) => (AvgSub, Single, true, true),
+            (true, 0b100000) => (Div, Single, false, false),
+            (true, 0b100001) => (Div, Single, true, true),
+            (true, 0b100010) => (Rem, Single, false, false),
+            (true, 0b100011) => (Rem, Single, true, true),
+            (true, 0b100100) => (Mulh, Single, false, false),
+            (true, 0b100101) => (Mul, Single, true, true),
+            (true, 0b100110) => (Mulh, Single, true, false),
+            (true, 0b100111) => (Mulh, Single, true, true),
+            (true, 0b101001) => (Madd, Single, true, true),
+            (true, 0b101011) => (Nmsub, Single, true, true),
+            (true, 0b101101) => (Macc, Single, true, true),
+            (true, 0b101111) => (Nmsac, Single, true, true),
+            (true, 0b110000) => (Add, Widen, false, false),
+            (true, 0b110001) => (Add, Widen, true, true),
+            (true, 0b110010) => (Sub, Widen, false, false),
+            (true, 0b110011) => (Sub, Widen, true, true),
+            (true, 0b110100) => (Add, WidenWide, false, false),
+            (true, 0b110101) => (Add, WidenWide, true, true),
+            (true, 0b110110) => (Sub, WidenWide, false, false),
+            (true, 0b110111) => (Sub, WidenWide, true, true),
+            (true, 0b111000) => (Mul, Widen, false, false),
+            (true, 0b111010) => (Mul, Widen, true, false),
+            (true, 0b111011) => (Mul, Widen, true, true),
+            (true, 0b111100) => (Macc, Widen, false, false),
+            (true, 0b111101) => (Macc, Widen, true, true),
+            (true, 0b111110) => (Macc, Widen, true, false),
+            (true, 0b111111) => (Macc, Widen, false, true),
+            _ => return None,
+        };
+        let operand = match instr.funct3 {
+            // The vs1 field of the scalar-result group and of conversions selects the operation
+            OPMVV if shape == ToScalar => Operand::Scalar,
+            OPFVV if matches!(op, FConvert(_)) => Operand::Scalar,
+            OPIVV | OPMVV | OPFVV => Operand::Vector(instr.rs1),
+            OPIVI => Operand::Immediate(sign_extend(instr.rs1 as i32, 5)),
+            _ => Operand::Scalar,
+        };
+        Some(Self {
+            op,
+            shape,
+            signed_vs2,
+            signed_vs1,
+            vd: instr.rd,
+            vs2: instr.rs2,
+            operand,
+            masked: instr.funct7 & 1 == 0,
+        })
+    }
+}
+
+/// Decode table entry of an OPFVV or OPFVF instruction
+fn float_entry(instr: &Instruction, funct6: u8) -> Option<(Op, Shape, bool, bool)> {
+    use Op::*;
+    use Shape::*;
+    let entry = match funct6 {
+        0b000000 => (FAdd, Single, false, false),
+        0b000001 => (FSumUnordered, Reduce, false, false),
+        0b000010 => (FSub, Single, false, false),
+        0b000011 => (FSumOrdered, Reduce, false, false),
+        0b000100 => (FMin, Single, false, false),
+        0b000101 => (FMin, Reduce, false, false),
+        0b000110 => (FMax, Single, false, false),
+        0b000111 => (FMax, Reduce, false, false),
+        0b010010 => {
+            let signed = instr.rs1 & 1 != 0;
+            let convert = match instr.rs1 & 0b111 {
+                0b000 | 0b001 => Convert::ToInt { signed, truncate: false },
+                0b010 | 0b011 => Convert::FromInt { signed },
+                0b100 | 0b101 => Convert::Float { odd: signed },
+                _ => Convert::ToInt { signed, truncate: true },
+            };
+            let shape = match instr.rs1 >> 3 {
+                0b00 => Single,
+                0b01 => Widen,
+                _ => Narrow,
+            };
+            (FConvert(convert), shape, signed, signed)
+        }
+        0b100100 => (FMul, Single, false, false),
+        0b101100 => (FMacc, Single, false, false),
+        0b110001 => (FSumUnordered, WidenReduce, false, false),
+        0b110011 => (FSumOrdered, WidenReduce, false, false),
+        0b111100 => (FMacc, Widen, false, false),
+        _ => return None,
+    };
+    Some(entry)
+}
+
+/// Mask, permutation and move operation of the permute unit
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Permute {
+    /// Mask-register logical operations, `AndNot` and `OrNot` invert `vs1`
+    MaskAnd, MaskNand, MaskAndNot, MaskXor, MaskOr, MaskNor, MaskOrNot, MaskXnor,
+    /// Set mask bits before, up to and including, or only at the first set bit of `vs2`
+    SetBeforeFirst, SetIncludingFirst, SetOnlyFirst,
+    /// `vd[i]` = number of set bits of `vs2` below element `i`
+    Iota,
+    /// `vd[i] = i`
+    Index,
+    /// Pack the elements of `vs2` selected by the mask in `vs1`
+    Compress,
+    /// `vd[i] = vs2[index]` with the index taken from `vs1`, `x[rs1]` or the immediate
+    Gather,
+    /// `vd[i] = vs2[vs1[i]]` with 16-bit indices
+    GatherEi16,
+    /// `vd[i + offset] = vs2[i]`
+    SlideUp,
+    /// `vd[i] = vs2[i + offset]`
+    SlideDown,
+    /// Slide up by one, inserting `x[rs1]` at element 0
+    Slide1Up,
+    /// Slide down by one, inserting `x[rs1]` at element `vl - 1`
+    Slide1Down,
+    /// `vd[i] = v0[i] ? operand : vs2[i]`
+    Merge,
+    /// `vd[i] = operand`
+    Move,
+    /// `vd[0] = x[rs1]`
+    MoveToElement,
+    /// Copy a whole group of this many registers
+    WholeMove(u8),
+}
+
+/// A decoded vector mask, permutation or move instruction
+#[derive(Copy, Clone, Debug)]
+pub struct VectorPermuteOp {
+    pub op: Permute,
+    pub vd: u8,
+    pub vs2: u8,
+    pub operand: Operand,
+    /// Whether inactive elements are selected by `v0`, for `vmerge` which elements take the operand
+    pub masked: bool,
+}
+
+impl VectorPermuteOp {
+    /// Decode an OP-V permutation instruction, `None` if it is not one
+    pub fn decode(instr: &Instruction) -> Option<Self> {
+        mnemonic(instr)?;
+        use Permute::*;
+        let op = match (instr.funct7 >> 1, instr.funct3) {
+            (0b011000, OPMVV) => MaskAndNot,
+            (0b011001, OPMVV) => MaskAnd,
+            (0b011010, OPMVV) => MaskOr,
+            (0b011011, OPMVV) => MaskXor,
+            (0b011100, OPMVV) => MaskOrNot,
+            (0b011101, OPMVV) => MaskNand,
+            (0b011110, OPMVV) => MaskNor,
+            (0b011111, OPMVV) => MaskXnor,
+            (0b010100, OPMVV) => match instr.rs1 {
+                0b00001 => SetBeforeFirst,
+                0b00010 => SetOnlyFirst,
+                0b00011 => SetIncludingFirst,
+                0b10000 => Iota,
+                _ => Index,
+            },
+            (0b010111, OPMVV) => Compress,
+            (0b001100, OPIVV | OPIVX | OPIVI) => Gather,
+            (0b001110, OPIVV) => GatherEi16,
+            (0b001110, OPIVX | OPIVI) => SlideUp,
+            (0b001111, OPIVX | OPIVI) => SlideDown,
+            (0b001110, OPMVX) => Slide1Up,
+            (0b001111, OPMVX) => Slide1Down,
+            (0b010111, OPIVV | OPIVX | OPIVI) if instr.funct7 & 1 == 0 => Merge,
+            (0b010111, OPIVV | OPIVX | OPIVI) => Move,
+            (0b010000, OPMVX) => MoveToElement,
+            (0b100111, OPIVI) => WholeMove(instr.rs1 + 1),
+            _ => return None,
+        };
+        let operand = match instr.funct3 {
+            // Indices and slide offsets are unsigned immediates
+            OPIVI if matches!(op, Gather | SlideUp | SlideDown) => Operand::Immediate(instr.rs1 as i32),
+            OPIVI => Operand::Immediate(sign_extend(instr.rs1 as i32, 5)),
+            OPIVX | OPMVX => Operand::Scalar,
+            _ => Operand::Vector(instr.rs1),
+        };
+        Some(Self {
+            op,
+            vd: instr.rd,
+            vs2: instr.rs2,
+            operand,
+            masked: instr.funct7 & 1 == 0,
+        })
+    }
+}
+
+/// Mnemonic of an OP-V arithmetic or permutation instruction, `None` for encodings that are not implemented
+pub fn mnemonic(instr: &Instruction) -> Option<&'static str> {
+    let name = match (instr.funct7 >> 1, instr.funct3) {
+        (0b000000, OPIVV) => "vadd.vv",
+        (0b000000, OPIVX) => "vadd.vx",
+        (0b000000, OPIVI) => "vadd.vi",
+        (0b000010, OPIVV) => "vsub.vv",
+        (0b000010, OPIVX) => "vsub.vx",
+        (0b000011, OPIVX) => "vrsub.vx",
+        (0b000011, OPIVI) => "vrsub.vi",
+        (0b000100, OPIVV) => "vminu.vv",
+        (0b000100, OPIVX) => "vminu.vx",
+        (0b000101, OPIVV) => "vmin.vv",
+        (0b000101, OPIVX) => "vmin.vx",
+        (0b000110, OPIVV) => "vmaxu.vv",
+        (0b000110, OPIVX) => "vmaxu.vx",
+        (0b000111, OPIVV) => "vmax.vv",
+        (0b000111, OPIVX) => "vmax.vx",
+        (0b001001, OPIVV) => "vand.vv",
+        (0b001001, OPIVX) => "vand.vx",
+        (0b001001, OPIVI) => "vand.vi",
+        (0b001010, OPIVV) => "vor.vv",
+        (0b001010, OPIVX) => "vor.vx",
+        (0b001010, OPIVI) => "vor.vi",
+        (0b001011, OPIVV) => "vxor.vv",
+        (0b001011, OPIVX) => "vxor.vx",
+        (0b001011, OPIVI) => "vxor.vi",
+        (0b011000, OPIVV) => "vmseq.vv",
+        (0b011000, OPIVX) => "vmseq.vx",
+        (0b011000, OPIVI) => "vmseq.vi",
+        (0b011001, OPIVV) => "vmsne.vv",
+        (0b011001, OPIVX) => "vmsne.vx",
+        (0b011001, OPIVI) => "vmsne.vi",
+        (0b011010, OPIVV) => "vmsltu.vv",
+        (0b011010, OPIVX) => "vmsltu.vx",
+        (0b011011, OPIVV) => "vmslt.vv",
+        (0b011011, OPIVX) => "vmslt.vx",
+        (0b011100, OPIVV) => "vmsleu.vv",
+        (0b011100, OPIVX) => "vmsleu.vx",
+        (0b011100, OPIVI) => "vmsleu.vi",
+        (0b011101, OPIVV) => "vmsle.vv",
+        (0b011101, OPIVX) => "vmsle.vx",
+        (0b011101, OPIVI) => "vmsle.vi",
+        (0b011110, OPIVX) => "vmsgtu.vx",
+        (0b011110, OPIVI) => "vmsgtu.vi",
+        (0b011111, OPIVX) => "vmsgt.vx",
+        (0b011111, OPIVI) => "vmsgt.vi",
+        (0b100101, OPIVV) => "vsll.vv",
+        (0b100101, OPIVX) => "vsll.vx",
+        (0b100101, OPIVI) => "vsll.vi",
+        (0b101000, OPIVV) => "vsrl.vv",
+        (0b101000, OPIVX) => "vsrl.vx",
+        (0b101000, OPIVI) => "vsrl.vi",
+        (0b101001, OPIVV) => "vsra.vv",
+        (0b101001, OPIVX) => "vsra.vx",
+        (0b101001, OPIVI) => "vsra.vi",
+        (0b101100, OPIVV) => "vnsrl.wv",
+        (0b101100, OPIVX) => "vnsrl.wx",
+        (0b101100, OPIVI) => "vnsrl.wi",
+        (0b101101, OPIVV) => "vnsra.wv",
+        (0b101101, OPIVX) => "vnsra.wx",
+        (0b101101, OPIVI) => "vnsra.wi",
+        (0b100000, OPIVV) => "vsaddu.vv",
+        (0b100000, OPIVX) => "vsaddu.vx",
+        (0b100000, OPIVI) => "vsaddu.vi",
+        (0b100001, OPIVV) => "vsadd.vv",
+        (0b100001, OPIVX) => "vsadd.vx",
+        (0b100001, OPIVI) => "vsadd.vi",
+        (0b100010, OPIVV) => "vssubu.vv",
+        (0b100010, OPIVX) => "vssubu.vx",
+        (0b100011, OPIVV) => "vssub.vv",
+        (0b100011, OPIVX) => "vssub.vx",
+        (0b100111, OPIVV) => "vsmul.vv",
+        (0b100111, OPIVX) => "vsmul.vx",
+        (0b101010, OPIVV) => "vssrl.vv",
+        (0b101010, OPIVX) => "vssrl.vx",
+        (0b101010, OPIVI) => "vssrl.vi",
+        (0b101011, OPIVV) => "vssra.vv",
+        (0b101011, OPIVX) => "vssra.vx",
+        (0b101011, OPIVI) => "vssra.vi",
+        (0b101110, OPIVV) => "vnclipu.wv",
+        (0b101110, OPIVX) => "vnclipu.wx",
+        (0b101110, OPIVI) => "vnclipu.wi",
+        (0b101111, OPIVV) => "vnclip.wv",
+        (0b101111, OPIVX) => "vnclip.wx",
+        (0b101111, OPIVI) => "vnclip.wi",
+        (0b001100, OPIVV) => "vrgather.vv",
+        (0b001100, OPIVX) => "vrgather.vx",
+        (0b001100, OPIVI) => "vrgather.vi",
+        (0b001110, OPIVV) => "vrgatherei16.vv",
+        (0b001110, OPIVX) => "vslideup.vx",
+        (0b001110, OPIVI) => "vslideup.vi",
+        (0b001111, OPIVX) => "vslidedown.vx",
+        (0b001111, OPIVI) => "vslidedown.vi",
+        // vmv.v.* is the unmasked vmerge with vs2 = v0
+        (0b010111, OPIVV) if instr.funct7 & 1 == 0 => "vmerge.vvm",
+        (0b010111, OPIVX) if instr.funct7 & 1 == 0 => "vmerge.vxm",
+        (0b010111, OPIVI) if instr.funct7 & 1 == 0 => "vmerge.vim",
+        (0b010111, OPIVV | OPIVX | OPIVI) if instr.rs2 != 0 => return None,
+        (0b010111, OPIVV) => "vmv.v.v",
+        (0b010111, OPIVX) => "vmv.v.x",
+        (0b010111, OPIVI) => "vmv.v.i",
+        (0b100111, OPIVI) => match instr.rs1 {
+            0 => "vmv1r.v",
+            1 => "vmv2r.v",
+            3 => "vmv4r.v",
+            7 => "vmv8r.v",
+            _ => return None,
+        },
+        (0b000000, OPFVV) => "vfadd.vv",
+        (0b000001, OPFVV) => "vfredusum.vs",
+        (0b000010, OPFVV) => "vfsub.vv",
+        (0b000011, OPFVV) => "vfredosum.vs",
+        (0b000100, OPFVV) => "vfmin.vv",
+        (0b000101, OPFVV) => "vfredmin.vs",
+        (0b000110, OPFVV) => "vfmax.vv",
+        (0b000111, OPFVV) => "vfredmax.vs",
+        (0b010010, OPFVV) => match instr.rs1 {
+            0b00000 => "vfcvt.xu.f.v",
+            0b00001 => "vfcvt.x.f.v",
+            0b00010 => "vfcvt.f.xu.v",
+            0b00011 => "vfcvt.f.x.v",
+            0b00110 => "vfcvt.rtz.xu.f.v",
+            0b00111 => "vfcvt.rtz.x.f.v",
+            0b01000 => "vfwcvt.xu.f.v",
+            0b01001 => "vfwcvt.x.f.v",
+            0b01010 => "vfwcvt.f.xu.v",
+            0b01011 => "vfwcvt.f.x.v",
+            0b01100 => "vfwcvt.f.f.v",
+            0b01110 => "vfwcvt.rtz.xu.f.v",
+            0b01111 => "vfwcvt.rtz.x.f.v",
+            0b10000 => "vfncvt.xu.f.w",
+            0b10001 => "vfncvt.x.f.w",
+            0b10010 => "vfncvt.f.xu.w",
+            0b10011 => "vfncvt.f.x.w",
+            0b10100 => "vfncvt.f.f.w",
+            0b10101 => "vfncvt.rod.f.f.w",
+            0b10110 => "vfncvt.rtz.xu.f.w",
+            0b10111 => "vfncvt.rtz.x.f.w",
+            _ => return None,
+        },
+        (0b100100, OPFVV) => "vfmul.vv",
+        (0b101100, OPFVV) => "vfmacc.vv",
+        (0b110001, OPFVV) => "vfwredusum.vs",
+        (0b110011, OPFVV) => "vfwredosum.vs",
+        (0b111100, OPFVV) => "vfwmacc.vv",
+        (0b000000, OPFVF) => "vfadd.vf",
+        (0b000010, OPFVF) => "vfsub.vf",
+        (0b000100, OPFVF) => "vfmin.vf",
+        (0b000110, OPFVF) => "vfmax.vf",
+        (0b100100, OPFVF) => "vfmul.vf",
+        (0b101100, OPFVF) => "vfmacc.vf",
+        (0b111100, OPFVF) => "vfwmacc.vf",
+
+        (0b110000, OPIVV) => "vwredsumu.vs",
+        (0b110001, OPIVV) => "vwredsum.vs",
+
+        (0b010000, OPMVV) => match instr.rs1 {
+            0b00000 => "vmv.x.s",
+            0b10000 => "vcpop.m",
+            0b10001 => "vfirst.m",
+            _ => return None,
+        },
+        (0b010000, OPMVX) if instr.rs2 == 0 => "vmv.s.x",
+        (0b010100, OPMVV) => match instr.rs1 {
+            0b00001 => "vmsbf.m",
+            0b00010 => "vmsof.m",
+            0b00011 => "vmsif.m",
+            0b10000 => "viota.m",
+            0b10001 if instr.rs2 == 0 => "vid.v",
+            _ => return None,
+        },
+        (0b010111, OPMVV) => "vcompress.vm",
+        (0b011000, OPMVV) => "vmandn.mm",
+        (0b011001, OPMVV) => "vmand.mm",
+        (0b011010, OPMVV) => "vmor.mm",
+        (0b011011, OPMVV) => "vmxor.mm",
+        (0b011100, OPMVV) => "vmorn.mm",
+        (0b011101, OPMVV) => "vmnand.mm",
+        (0b011110, OPMVV) => "vmnor.mm",
+        (0b011111, OPMVV) => "vmxnor.mm",
+        (0b001110, OPMVX) => "vslide1up.vx",
+        (0b001111, OPMVX) => "vslide1down.vx",
+        (0b000000, OPMVV) => "vredsum.vs",
+        (0b000001, OPMVV) => "vredand.vs",
+        (0b000010, OPMVV) => "vredor.vs",
+        (0b000011, OPMVV) => "vredxor.vs",
+        (0b000100, OPMVV) => "vredminu.vs",
+        (0b000101, OPMVV) => "vredmin.vs",
+        (0b000110, OPMVV) => "vredmaxu.vs",
+        (0b000111, OPMVV) => "vredmax.vs",
+        (0b001000, OPMVV) => "vaaddu.vv",
+        (0b001000, OPMVX) => "vaaddu.vx",
+        (0b001001, OPMVV) => "vaadd.vv",
+        (0b001001, OPMVX) => "vaadd.vx",
+        (0b001010, OPMVV) => "vasubu.vv",
+        (0b001010, OPMVX) => "vasubu.vx",
+        (0b001011, OPMVV) => "vasub.vv",
+        (0b001011, OPMVX) => "vasub.vx",
+        (0b100000, OPMVV) => "vdivu.vv",
+        (0b100000, OPMVX) => "vdivu.vx",
+        (0b100001, OPMVV) => "vdiv.vv",
+        (0b100001, OPMVX) => "vdiv.vx",
+        (0b100010, OPMVV) => "vremu.vv",
+        (0b100010, OPMVX) => "vremu.vx",
+        (0b100011, OPMVV) => "vrem.vv",
+        (0b100011, OPMVX) => "vrem.vx",
+        (0b100100, OPMVV) => "vmulhu.vv",
+        (0b100100, OPMVX) => "vmulhu.vx",
+        (0b100101, OPMVV) => "vmul.vv",
+        (0b100101, OPMVX) => "vmul.vx",
+        (0b100110, OPMVV) => "vmulhsu.vv",
+        (0b100110, OPMVX) => "vmulhsu.vx",
+        (0b100111, OPMVV) => "vmulh.vv",
+        (0b100111, OPMVX) => "vmulh.vx",
+        (0b101001, OPMVV) => "vmadd.vv",
+        (0b101001, OPMVX) => "vmadd.vx",
+        (0b101011, OPMVV) => "vnmsub.vv",
+        (0b101011, OPMVX) => "vnmsub.vx",
+        (0b101101, OPMVV) => "vmacc.vv",
+        (0b101101, OPMVX) => "vmacc.vx",
+        (0b101111, OPMVV) => "vnmsac.vv",
+        (0b101111, OPMVX) => "vnmsac.vx",
+        (0b110000, OPMVV) => "vwaddu.vv",
+        (0b110000, OPMVX) => "vwaddu.vx",
+        (0b110001, OPMVV) => "vwadd.vv",
+        (0b110001, OPMVX) => "vwadd.vx",
+        (0b110010, OPMVV) => "vwsubu.vv",
+        (0b110010, OPMVX) => "vwsubu.vx",
+        (0b110011, OPMVV) => "vwsub.vv",
+        (0b110011, OPMVX) => "vwsub.vx",
+        (0b110100, OPMVV) => "vwaddu.wv",
+        (0b110100, OPMVX) => "vwaddu.wx",
+        (0b110101, OPMVV) => "vwadd.wv",
+        (0b110101, OPMVX) => "vwadd.wx",
+        (0b110110, OPMVV) => "vwsubu.wv",
+        (0b110110, OPMVX) => "vwsubu.wx",
+        (0b110111, OPMVV) => "vwsub.wv",
+        (0b110111, OPMVX) => "vwsub.wx",
+        (0b111000, OPMVV) => "vwmulu.vv",
+        (0b111000, OPMVX) => "vwmulu.vx",
+        (0b111010, OPMVV) => "vwmulsu.vv",
+        (0b111010, OPMVX) => "vwmulsu.vx",
+        (0b111011, OPMVV) => "vwmul.vv",
+        (0b111011, OPMVX) => "vwmul.vx",
+        (0b111100, OPMVV) => "vwmaccu.vv",
+        (0b111100, OPMVX) => "vwmaccu.vx",
+        (0b111101, OPMVV) => "vwmacc.vv",
+        (0b111101, OPMVX) => "vwmacc.vx",
+        (0b111110, OPMVX) => "vwmaccus.vx",
+        (0b111111, OPMVV) => "vwmaccsu.vv",
+        (0b111111, OPMVX) => "vwmaccsu.vx",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Vector load opcode, shared with scalar floating-point loads
+pub const LOAD_FP: u8 = 0b0000111;
+/// Vector store opcode, shared with scalar floating-point stores
+pub const STORE_FP: u8 = 0b0100111;
+
+/// Addressing mode of a vector load or store
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Addressing {
+    /// Consecutive elements, segments packed back to back
+    UnitStride,
+    /// `vlm.v`/`vsm.v`, `ceil(vl / 8)` bytes of a mask register
+    Mask,
+    /// Elements `x[rs2]` bytes apart
+    Strided,
+    /// Byte offsets taken from the elements of `vs2`
+    Indexed { ordered: bool },
+}
+
+/// A decoded vector load or store
+#[derive(Copy, Clone, Debug)]
+pub struct VectorMemOp {
+    pub store: bool,
+    pub addressing: Addressing,
+    /// Element width from the instruction, the index width for indexed accesses
+    pub eew: u32,
+    /// Number of fields per segment, 1 for plain accesses
+    pub fields: u32,
+    /// Destination of loads, data source of stores
+    pub vd: u8,
+    /// Index register group of indexed accesses
+    pub vs2: u8,
+    pub masked: bool,
+}
+
+impl VectorMemOp {
+    /// Decode a vector load or store, `None` if it is not an implemented access
+    pub fn decode(instr: &Instruction) -> Option<Self> {
+        mem_mnemonic(instr)?;
+        let mop = (instr.funct7 >> 1) & 0b11;
+        let addressing = match mop {
+            0b00 if instr.rs2 == 0b01011 => Addressing::Mask,
+            0b00 => Addressing::UnitStride,
+            0b10 => Addressing::Strided,
+            _ => Addressing::Indexed { ordered: mop == 0b11 },
+        };
+        Some(Self {
+            store: instr.opcode == STORE_FP,
+            addressing,
+            eew: eew(instr.funct3)?,
+            fields: (instr.funct7 as u32 >> 4) + 1,
+            vd: instr.rd,
+            vs2: instr.rs2,
+            masked: instr.funct7 & 1 == 0,
+        })
+    }
+}
+
+/// Element width in bits selected by the width field of a vector load or store
+fn eew(funct3: u8) -> Option<u32> {
+    match funct3 {
+        0b000 => Some(8),
+        0b101 => Some(16),
+        0b110 => Some(32),
+        _ => None,
+    }
+}
+
+/// Mnemonic of a vector load or store. Segment accesses share the mnemonic of the
+/// plain access, the disassembler adds the field count.
+pub fn mem_mnemonic(instr: &Instruction) -> Option<&'static str> {
+    let mop = (instr.funct7 >> 1) & 0b11;
+    // mew selects element widths above 64 bits
+    if instr.funct7 & 0b1000 != 0 {
+        return None;
+    }
+    let name = match (instr.opcode == STORE_FP, mop, instr.funct3) {
+        (false, 0b00, 0b000) if instr.rs2 == 0b01011 && instr.funct7 >> 4 == 0 => "vlm.v",
+        (true, 0b00, 0b000) if instr.rs2 == 0b01011 && instr.funct7 >> 4 == 0 => "vsm.v",
+        (_, 0b00, _) if instr.rs2 != 0 => return None,
+        (false, 0b00, 0b000) => "vle8.v",
+        (false, 0b00, 0b101) => "vle16.v",
+        (false, 0b00, 0b110) => "vle32.v",
+        (true, 0b00, 0b000) => "vse8.v",
+        (true, 0b00, 0b101) => "vse16.v",
+        (true, 0b00, 0b110) => "vse32.v",
+        (false, 0b10, 0b000) => "vlse8.v",
+        (false, 0b10, 0b101) => "vlse16.v",
+        (false, 0b10, 0b110) => "vlse32.v",
+        (true, 0b10, 0b000) => "vsse8.v",
+        (true, 0b10, 0b101) => "vsse16.v",
+        (true, 0b10, 0b110) => "vsse32.v",
+        (false, 0b01, 0b000) => "vluxei8.v",
+        (false, 0b01, 0b101) => "vluxei16.v",
+        (false, 0b01, 0b110) => "vluxei32.v",
+        (true, 0b01, 0b000) => "vsuxei8.v",
+        (true, 0b01, 0b101) => "vsuxei16.v",
+        (true, 0b01, 0b110) => "vsuxei32.v",
+        (false, 0b11, 0b000) => "vloxei8.v",
+        (false, 0b11, 0b101) => "vloxei16.v",
+        (false, 0b11, 0b110) => "vloxei32.v",
+        (true, 0b11, 0b000) => "vsoxei8.v",
+        (true, 0b11, 0b101) => "vsoxei16.v",
+        (true, 0b11, 0b110) => "vsoxei32.v",
+        _ => return None,
+    };
+    Some(name)
+}
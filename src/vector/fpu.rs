@@ -0,0 +1,263 @@
+use crate::common::float::{Format, Rounding};
+use crate::scalar::csr::{FloatCsr, Trap, CAUSE_ILLEGAL_INSTRUCTION};
+use crate::scalar::instruction::Instruction;
+use crate::vector::alu::{self, Widths, DEFAULT_DATAPATH_BITS};
+use crate::vector::config::VectorConfig;
+use crate::vector::decode::{Convert, Op, Operand, Shape, VectorOp};
+use crate::vector::regfile::VectorRegisterFile;
+
+/// Default pipeline latency of a floating-point operation in cycles
+pub const DEFAULT_FP_LATENCY: u32 = 4;
+
+/// Timed vector floating-point unit.
+///
+/// Elements are binary16 at SEW=16 and binary32 at SEW=32. The `.vf` forms take their
+/// scalar operand from the low SEW bits of `x[rs1]`, as there are no separate
+/// floating-point registers. Instructions execute
+/// functionally at issue, rounding by `frm` and accruing `fflags`, and keep the unit
+/// busy while the datapath works through the body like the integer unit, plus the
+/// pipeline latency. Ordered reductions wait for each sum before starting the next,
+/// unordered reductions pay the latency once per level of their adder tree.
+pub struct VectorFpu {
+    pub busy: bool,
+    pub remaining: u32,
+    pub current: Option<Instruction>,
+    /// Registers used by the current instruction
+    pub regs: u32,
+    /// Datapath width in bits
+    pub datapath_bits: u32,
+    /// Pipeline latency of one operation in cycles
+    pub latency: u32,
+}
+
+impl VectorFpu {
+    /// Create a unit with a datapath of `datapath_bits` and a pipeline of `latency` cycles
+    pub fn new(datapath_bits: u32, latency: u32) -> Self {
+        Self { busy: false, remaining: 0, current: None, regs: 0, datapath_bits, latency }
+    }
+
+    /// Issue a vector floating-point instruction with `rs1` holding the bits of the scalar
+    /// operand. Unsupported element widths, a `vill` configuration and a reserved `frm`
+    /// raise illegal instruction and leave the unit free.
+    pub fn issue(
+        &mut self,
+        instr: Instruction,
+        rs1: u32,
+        config: &mut VectorConfig,
+        fcsr: &mut FloatCsr,
+        vregs: &mut VectorRegisterFile,
+    ) -> Result<(), Trap> {
+        let illegal = Trap { cause: CAUSE_ILLEGAL_INSTRUCTION, tval: instr.raw };
+        let (Some(op), Some(vtype), Some(rounding)) =
+            (VectorOp::decode(&instr), config.vtype(), Rounding::from_frm(fcsr.frm))
+        else {
+            return Err(illegal);
+        };
+        let regs = alu::registers_of(&instr, config).ok_or(illegal)?;
+        let widths = alu::widths(&op, vtype.sew);
+
+        execute(&op, rs1, &widths, rounding, config, &mut fcsr.fflags, vregs);
+        let elements = config.vl.saturating_sub(config.vstart);
+        config.vstart = 0;
+
+        self.busy = true;
+        self.remaining = self.cycles(&op, &widths, elements);
+        self.current = Some(instr);
+        self.regs = regs;
+        Ok(())
+    }
+
+    /// Cycles the datapath needs for `elements` body elements
+    fn cycles(&self, op: &VectorOp, widths: &Widths, elements: u32) -> u32 {
+        let per_cycle = (self.datapath_bits / widths.vd.max(widths.vs2)).max(1);
+        let passes = elements.div_ceil(per_cycle).max(1);
+        match (op.op, op.shape) {
+            (Op::FSumOrdered, _) => elements.max(1) * self.latency,
+            (_, Shape::Reduce | Shape::WidenReduce) => passes + (per_cycle.ilog2() + 1) * self.latency,
+            _ => passes + self.latency,
+        }
+    }
+
+    pub fn tick(&mut self) -> Option<(Instruction, u32)> {
+        if self.busy {
+            if self.remaining > 0 {
+                self.remaining -= 1;
+            } else {
+                self.busy = false;
+                return self.current.take().map(|instr| (instr, 0));
+            }
+        }
+        None
+    }
+}
+
+impl Default for VectorFpu {
+    fn default() -> Self {
+        Self::new(DEFAULT_DATAPATH_BITS, DEFAULT_FP_LATENCY)
+    }
+}
+
+/// Registers a floating-point instruction would use under `config` and `fcsr`, `None` if it is illegal
+pub fn registers_of(instr: &Instruction, config: &VectorConfig, fcsr: &FloatCsr) -> Option<u32> {
+    Rounding::from_frm(fcsr.frm)?;
+    alu::registers_of(instr, config)
+}
+
+/// Execute an operation on the body elements from `vstart` to `vl`, accruing exception flags.
+/// Inactive and tail elements are left undisturbed.
+fn execute(
+    op: &VectorOp,
+    rs1: u32,
+    widths: &Widths,
+    rounding: Rounding,
+    config: &VectorConfig,
+    flags: &mut u32,
+    vregs: &mut VectorRegisterFile,
+) {
+    let active = |vregs: &VectorRegisterFile, i: u32| !op.masked || vregs.mask_bit(0, i);
+    let vs1 = match op.operand {
+        Operand::Vector(vs1) => vs1,
+        _ => 0,
+    };
+
+    if matches!(op.shape, Shape::Reduce | Shape::WidenReduce) {
+        let (Some(sum), Some(element)) = (Format::of_width(widths.vd), Format::of_width(widths.vs2)) else {
+            return;
+        };
+        if config.vl == 0 {
+            return;
+        }
+        // Widening reductions convert each element exactly before accumulating
+        let values: Vec<u32> = (0..config.vl)
+            .filter(|i| active(vregs, *i))
+            .map(|i| vregs.read(op.vs2, i, widths.vs2))
+            .map(|x| if sum == element { x } else { sum.convert(element, x, rounding, flags) })
+            .collect();
+        let start = vregs.read(vs1, 0, widths.vd);
+        let result = match op.op {
+            Op::FSumOrdered => values.iter().fold(start, |acc, &x| sum.add(acc, x, rounding, flags)),
+            Op::FSumUnordered => {
+                // Pairwise sums across the elements, then the scalar operand
+                let mut level = values;
+                while level.len() > 1 {
+                    level = level
+                        .chunks(2)
+                        .map(|pair| match pair {
+                            [a, b] => sum.add(*a, *b, rounding, flags),
+                            _ => pair[0],
+                        })
+                        .collect();
+                }
+                level.first().map_or(start, |&total| sum.add(start, total, rounding, flags))
+            }
+            _ => values.iter().fold(start, |acc, &x| sum.min_max(acc, x, op.op == Op::FMax, flags)),
+        };
+        vregs.write(op.vd, 0, widths.vd, result);
+        return;
+    }
+
+    let results: Vec<(u32, u32)> = (config.vstart..config.vl)
+        .filter(|i| active(vregs, *i))
+        .filter_map(|i| {
+            let a = vregs.read(op.vs2, i, widths.vs2);
+            let b = match op.operand {
+                Operand::Scalar => rs1 & (u32::MAX >> (32 - widths.vs1)),
+                _ => vregs.read(vs1, i, widths.vs1),
+            };
+            let d = vregs.read(op.vd, i, widths.vd);
+            compute(op.op, widths, a, b, d, rounding, flags).map(|value| (i, value))
+        })
+        .collect();
+    for (i, value) in results {
+        vregs.write(op.vd, i, widths.vd, value);
+    }
+}
+
+/// Compute one element from `a` (`vs2`), `b` (`vs1`) and `d` (`vd`) as raw bits
+fn compute(op: Op, widths: &Widths, a: u32, b: u32, d: u32, rounding: Rounding, flags: &mut u32) -> Option<u32> {
+    let float = Format::of_width;
+    let value = match op {
+        Op::FAdd => float(widths.vd)?.add(a, b, rounding, flags),
+        Op::FSub => {
+            let format = float(widths.vd)?;
+            format.add(a, format.negate(b), rounding, flags)
+        }
+        Op::FMul => float(widths.vd)?.mul(a, b, rounding, flags),
+        Op::FMin => float(widths.vd)?.min_max(a, b, false, flags),
+        Op::FMax => float(widths.vd)?.min_max(a, b, true, flags),
+        Op::FMacc => {
+            let (acc, element) = (float(widths.vd)?, float(widths.vs2)?);
+            // Widening sources convert exactly before the fused multiply-add
+            let (a, b) = if acc == element {
+                (a, b)
+            } else {
+                (acc.convert(element, a, rounding, flags), acc.convert(element, b, rounding, flags))
+            };
+            acc.fma(b, a, d, rounding, flags)
+        }
+        Op::FConvert(Convert::ToInt { signed, truncate }) => {
+            let rounding = if truncate { Rounding::TowardZero } else { rounding };
+            float(widths.vs2)?.to_int(a, signed, widths.vd, rounding, flags)
+        }
+        Op::FConvert(Convert::FromInt { signed }) => {
+            let shift = 32 - widths.vs2;
+            let value = if signed { ((a << shift) as i32 >> shift) as i64 } else { a as i64 };
+            float(widths.vd)?.from_int(value, rounding, flags)
+        }
+        Op::FConvert(Convert::Float { odd }) => {
+            let rounding = if odd { Rounding::Odd } else { rounding };
+            float(widths.vd)?.convert(float(widths.vs2)?, a, rounding, flags)
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::instruction::RawInstruction;
+    use crate::vector::decode::OPFVF;
+
+    /// Unmasked OPFVF instruction `vd = v4, vs2 = v8` with its scalar in `x10`
+    fn opfvf(funct6: u32) -> Instruction {
+        let raw = (funct6 << 26) | (1 << 25) | (8 << 20) | (10 << 15) | ((OPFVF as u32) << 12) | (4 << 7) | 0b1010111;
+        Instruction::from(RawInstruction { pc: 0, data: raw })
+    }
+
+    /// Issue `instr` on two elements of SEW `sew`
+    fn run(instr: Instruction, sew: u32, rs1: u32, vregs: &mut VectorRegisterFile) {
+        let mut config = VectorConfig::default();
+        config.set_vl(Some(2), (sew.trailing_zeros() - 3) << 3);
+        let mut fcsr = FloatCsr::default();
+        VectorFpu::default().issue(instr, rs1, &mut config, &mut fcsr, vregs).expect("legal instruction");
+    }
+
+    #[test]
+    fn vf_forms_broadcast_the_scalar() {
+        let mut vregs = VectorRegisterFile::new(128);
+        vregs.write(8, 0, 32, 1.0f32.to_bits());
+        vregs.write(8, 1, 32, (-2.0f32).to_bits());
+        let vfadd = opfvf(0b000000);
+        assert_eq!(vfadd.mnemonic(), "vfadd.vf");
+        assert!(vfadd.reads_rs1());
+        run(vfadd, 32, 0.5f32.to_bits(), &mut vregs);
+        assert_eq!([vregs.read(4, 0, 32), vregs.read(4, 1, 32)], [1.5f32.to_bits(), (-1.5f32).to_bits()]);
+
+        run(opfvf(0b100100), 32, 3.0f32.to_bits(), &mut vregs);
+        assert_eq!([vregs.read(4, 0, 32), vregs.read(4, 1, 32)], [3.0f32.to_bits(), (-6.0f32).to_bits()]);
+    }
+
+    #[test]
+    fn vfwmacc_vf_uses_the_low_sew_bits_of_the_scalar() {
+        let mut vregs = VectorRegisterFile::new(128);
+        // binary16 2.0 and 0.5, accumulating into binary32
+        vregs.write(8, 0, 16, 0x4000);
+        vregs.write(8, 1, 16, 0x3800);
+        vregs.write(4, 0, 32, 1.0f32.to_bits());
+        vregs.write(4, 1, 32, 1.0f32.to_bits());
+        // binary16 3.0 with junk in the upper half
+        run(opfvf(0b111100), 16, 0xFFFF_4200, &mut vregs);
+        assert_eq!([vregs.read(4, 0, 32), vregs.read(4, 1, 32)], [7.0f32.to_bits(), 2.5f32.to_bits()]);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::scalar::banks::Requestor;
+use crate::scalar::csr::{Trap, CAUSE_ILLEGAL_INSTRUCTION, CAUSE_LOAD_ACCESS_FAULT, CAUSE_STORE_ACCESS_FAULT};
+use crate::scalar::instruction::Instruction;
+use crate::scalar::memory::DataBus;
+use crate::vector::config::{VectorConfig, Vtype};
+use crate::vector::decode::{Addressing, VectorMemOp};
+use crate::vector::regfile::{group_mask, VectorRegisterFile};
+
+/// Default bytes moved per cycle, one 128-bit beat
+pub const DEFAULT_BYTES_PER_CYCLE: u32 = 16;
+
+/// Vector load/store unit.
+///
+/// Accesses are performed element by element at issue, in element order, so a
+/// fault leaves `vstart` at the faulting element with the earlier elements done.
+/// The accesses are split into beats of at most `bytes_per_cycle` bytes in which each
+/// DTCM bank serves a single word, so accesses to different words of the same bank
+/// start a new beat. The beats compete for the bank ports with the other requestors,
+/// and the unit stays busy until the last is granted, plus the DTCM latency.
+pub struct VectorLsu {
+    pub busy: bool,
+    pub remaining: u32,
+    pub current: Option<Instruction>,
+    /// Registers used by the current instruction
+    pub regs: u32,
+    pub bytes_per_cycle: u32,
+    /// DTCM bank grant the current instruction waits for
+    pub ticket: Option<u64>,
+}
+
+/// Register layout of a decoded access under the current `vtype`
+struct Layout {
+    /// Width of the data elements
+    data_eew: u32,
+    /// Registers per field of the data group
+    data_regs: u8,
+    /// Registers of the index group of indexed accesses
+    index_regs: u8,
+    /// Number of elements accessed
+    evl: u32,
+}
+
+impl VectorLsu {
+    /// Create a unit moving `bytes_per_cycle` bytes per cycle
+    pub fn new(bytes_per_cycle: u32) -> Self {
+        Self { busy: false, remaining: 0, current: None, regs: 0, bytes_per_cycle, ticket: None }
+    }
+
+    /// Issue a vector load or store with `rs1` holding the base address and `rs2` the stride.
+    /// Unmapped elements raise an access fault with `vstart` at the faulting element.
+    #[allow(clippy::too_many_arguments)]
+    pub fn issue(
+        &mut self,
+        instr: Instruction,
+        rs1: u32,
+        rs2: u32,
+        config: &mut VectorConfig,
+        vregs: &mut VectorRegisterFile,
+        bus: &mut DataBus,
+    ) -> Result<(), Trap> {
+        let illegal = Trap { cause: CAUSE_ILLEGAL_INSTRUCTION, tval: instr.raw };
+        let (Some(op), Some(vtype)) = (VectorMemOp::decode(&instr), config.vtype()) else {
+            return Err(illegal);
+        };
+        let layout = layout(&op, vtype, config.vl).ok_or(illegal)?;
+
+        let mut accesses = Vec::new();
+        let size = layout.data_eew / 8;
+        for i in config.vstart..layout.evl {
+            if op.masked && !vregs.mask_bit(0, i) {
+                continue;
+            }
+            let element = match op.addressing {
+                Addressing::UnitStride => rs1.wrapping_add(i * op.fields * size),
+                Addressing::Mask => rs1.wrapping_add(i),
+                Addressing::Strided => rs1.wrapping_add(i.wrapping_mul(rs2)),
+                Addressing::Indexed { .. } => rs1.wrapping_add(vregs.read(op.vs2, i, op.eew)),
+            };
+            for field in 0..op.fields {
+                let addr = element.wrapping_add(field * size);
+                let reg = op.vd + field as u8 * layout.data_regs;
+                if op.store {
+                    if !bus.store(addr, size, vregs.read(reg, i, layout.data_eew)) {
+                        config.vstart = i;
+                        return Err(Trap { cause: CAUSE_STORE_ACCESS_FAULT, tval: addr });
+                    }
+                } else {
+                    let Some(value) = bus.load(addr, size) else {
+                        config.vstart = i;
+                        return Err(Trap { cause: CAUSE_LOAD_ACCESS_FAULT, tval: addr });
+                    };
+                    vregs.write(reg, i, layout.data_eew, value);
+                }
+                accesses.push((addr, size));
+            }
+        }
+        config.vstart = 0;
+
+        self.busy = true;
+        let beats = bus.dtcm.beats(&accesses, self.bytes_per_cycle);
+        self.ticket = Some(bus.dtcm.arbiter.request(Requestor::VectorLsu, beats));
+        self.remaining = bus.dtcm.latency() as u32;
+        self.current = Some(instr);
+        self.regs = registers(&op, &layout);
+        Ok(())
+    }
+
+    pub fn tick(&mut self, bus: &mut DataBus) -> Option<(Instruction, u32)> {
+        if let Some(ticket) = self.ticket {
+            if !bus.dtcm.arbiter.is_granted(Requestor::VectorLsu, ticket) {
+                return None;
+            }
+            self.ticket = None;
+        }
+        if self.busy {
+            if self.remaining > 0 {
+                self.remaining -= 1;
+            } else {
+                self.busy = false;
+                return self.current.take().map(|instr| (instr, 0));
+            }
+        }
+        None
+    }
+}
+
+impl Default for VectorLsu {
+    fn default() -> Self {
+        Self::new(DEFAULT_BYTES_PER_CYCLE)
+    }
+}
+
+/// Registers a vector load or store would use under `config`, `None` if it is illegal
+pub fn registers_of(instr: &Instruction, config: &VectorConfig) -> Option<u32> {
+    let op = VectorMemOp::decode(instr)?;
+    let layout = layout(&op, config.vtype()?, config.vl)?;
+    Some(registers(&op, &layout))
+}
+
+fn registers(op: &VectorMemOp, layout: &Layout) -> u32 {
+    let mut regs = group_mask(op.vd, op.fields as u8 * layout.data_regs);
+    if matches!(op.addressing, Addressing::Indexed { .. }) {
+        regs |= group_mask(op.vs2, layout.index_regs);
+    }
+    if op.masked {
+        regs |= 1;
+    }
+    regs
+}
+
+/// Work out the register groups of an access, `None` if the encoding is reserved for this `vtype`
+fn layout(op: &VectorMemOp, vtype: Vtype, vl: u32) -> Option<Layout> {
+    // EMUL = EEW / SEW * LMUL, which must stay within 1/8 to 8
+    let emul_log2 = |eew: u32| vtype.lmul_log2 + eew.ilog2() as i32 - vtype.sew.ilog2() as i32;
+    let regs = |emul_log2: i32| (-3..=3).contains(&emul_log2).then(|| 1u8 << emul_log2.max(0));
+
+    let (data_eew, data_regs, index_regs, evl) = match op.addressing {
+        Addressing::Mask if op.masked => return None,
+        Addressing::Mask => (8, 1, 0, vl.div_ceil(8)),
+        Addressing::UnitStride | Addressing::Strided => (op.eew, regs(emul_log2(op.eew))?, 0, vl),
+        Addressing::Indexed { .. } => {
+            let index_regs = regs(emul_log2(op.eew))?;
+            if !op.vs2.is_multiple_of(index_regs) {
+                return None;
+            }
+            (vtype.sew, vtype.group_regs() as u8, index_regs, vl)
+        }
+    };
+
+    let total = op.fields as u8 * data_regs;
+    if total > 8 || op.vd as u32 + total as u32 > 32 || !op.vd.is_multiple_of(data_regs) {
+        return None;
+    }
+    // A masked load may not overwrite the mask in v0
+    if op.masked && !op.store && op.vd == 0 {
+        return None;
+    }
+    Some(Layout { data_eew, data_regs, index_regs, evl })
+}
@@ -0,0 +1,8 @@
+pub mod alu;
+pub mod backend;
+pub mod config;
+pub mod decode;
+pub mod fpu;
+pub mod lsu;
+pub mod permute;
+pub mod regfile;
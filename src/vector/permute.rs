@@ -0,0 +1,297 @@
+use crate::scalar::csr::{Trap, CAUSE_ILLEGAL_INSTRUCTION};
+use crate::scalar::instruction::Instruction;
+use crate::vector::alu::DEFAULT_DATAPATH_BITS;
+use crate::vector::config::{VectorConfig, Vtype};
+use crate::vector::decode::{Operand, Permute, VectorPermuteOp};
+use crate::vector::regfile::{group_mask, VectorRegisterFile};
+
+/// Timed vector mask and permutation unit.
+///
+/// Instructions execute functionally at issue and keep the unit busy while its
+/// crossbar moves `datapath_bits` worth of elements per cycle. Operations that keep
+/// elements in their lane take one pass over the body. A slide whose offset is not a
+/// multiple of the elements per pass reads two source chunks for every destination
+/// chunk, a vector-indexed gather reads every source chunk for every destination chunk,
+/// and operations that count set mask bits add the stages of their prefix tree.
+pub struct VectorPermuteUnit {
+    pub busy: bool,
+    pub remaining: u32,
+    pub current: Option<Instruction>,
+    /// Registers used by the current instruction
+    pub regs: u32,
+    /// Crossbar width in bits
+    pub datapath_bits: u32,
+}
+
+impl VectorPermuteUnit {
+    /// Create a unit with a crossbar of `datapath_bits`
+    pub fn new(datapath_bits: u32) -> Self {
+        Self { busy: false, remaining: 0, current: None, regs: 0, datapath_bits }
+    }
+
+    /// Issue a vector mask, permutation or move instruction with `rs1` holding `x[rs1]`.
+    /// A `vill` configuration and illegal register operands raise illegal instruction
+    /// and leave the unit free.
+    pub fn issue(
+        &mut self,
+        instr: Instruction,
+        rs1: u32,
+        config: &mut VectorConfig,
+        vregs: &mut VectorRegisterFile,
+    ) -> Result<(), Trap> {
+        let illegal = Trap { cause: CAUSE_ILLEGAL_INSTRUCTION, tval: instr.raw };
+        let (Some(op), Some(vtype)) = (VectorPermuteOp::decode(&instr), config.vtype()) else {
+            return Err(illegal);
+        };
+        if !is_legal(&op, vtype, config.vstart) {
+            return Err(illegal);
+        }
+
+        execute(&op, rs1, vtype, config, vregs);
+        self.busy = true;
+        self.remaining = self.cycles(&op, rs1, vtype, config);
+        self.current = Some(instr);
+        self.regs = registers(&op, vtype);
+        config.vstart = 0;
+        Ok(())
+    }
+
+    /// Cycles the crossbar needs for the body of an operation
+    fn cycles(&self, op: &VectorPermuteOp, rs1: u32, vtype: Vtype, config: &VectorConfig) -> u32 {
+        use Permute::*;
+        let lanes = (self.datapath_bits / vtype.sew).max(1);
+        let chunks = |elements: u32| elements.div_ceil(lanes).max(1);
+        let body = config.vl.saturating_sub(config.vstart);
+        // Mask registers are processed a full datapath of bits at a time
+        let mask_passes = config.vl.div_ceil(self.datapath_bits).max(1);
+        match op.op {
+            MaskAnd | MaskNand | MaskAndNot | MaskXor | MaskOr | MaskNor | MaskOrNot | MaskXnor => mask_passes,
+            SetBeforeFirst | SetIncludingFirst | SetOnlyFirst => mask_passes + self.datapath_bits.ilog2(),
+            Iota | Compress => chunks(config.vl) + lanes.ilog2(),
+            Index | Merge | Move => chunks(body),
+            MoveToElement => 1,
+            WholeMove(count) => (count as u32 * config.vlen).div_ceil(self.datapath_bits),
+            // The element crossing each chunk boundary takes one more cycle
+            Slide1Up | Slide1Down => chunks(body) + 1,
+            SlideUp | SlideDown if scalar_operand(op, rs1).is_multiple_of(lanes) => chunks(body),
+            SlideUp | SlideDown => 2 * chunks(body),
+            // A scalar index broadcasts one element
+            Gather if !matches!(op.operand, Operand::Vector(_)) => chunks(body) + 1,
+            Gather | GatherEi16 => chunks(body) * chunks(vtype.vlmax(config.vlen)),
+        }
+    }
+
+    pub fn tick(&mut self) -> Option<(Instruction, u32)> {
+        if self.busy {
+            if self.remaining > 0 {
+                self.remaining -= 1;
+            } else {
+                self.busy = false;
+                return self.current.take().map(|instr| (instr, 0));
+            }
+        }
+        None
+    }
+}
+
+impl Default for VectorPermuteUnit {
+    fn default() -> Self {
+        Self::new(DEFAULT_DATAPATH_BITS)
+    }
+}
+
+/// Registers a permutation instruction would use under `config`, `None` if it is illegal
+pub fn registers_of(instr: &Instruction, config: &VectorConfig) -> Option<u32> {
+    let op = VectorPermuteOp::decode(instr)?;
+    let vtype = config.vtype()?;
+    is_legal(&op, vtype, config.vstart).then(|| registers(&op, vtype))
+}
+
+fn registers(op: &VectorPermuteOp, vtype: Vtype) -> u32 {
+    use Permute::*;
+    let lmul = vtype.group_regs() as u8;
+    // Register counts of vd, vs2 and vs1, zero where the field is not a source
+    let (vd, vs2, vs1) = match op.op {
+        MaskAnd | MaskNand | MaskAndNot | MaskXor | MaskOr | MaskNor | MaskOrNot | MaskXnor => (1, 1, 1),
+        SetBeforeFirst | SetIncludingFirst | SetOnlyFirst => (1, 1, 0),
+        Iota => (lmul, 1, 0),
+        Index => (lmul, 0, 0),
+        Compress => (lmul, lmul, 1),
+        GatherEi16 => (lmul, lmul, index_regs(vtype).unwrap_or(1)),
+        Move => (lmul, 0, lmul),
+        MoveToElement => (1, 0, 0),
+        WholeMove(count) => (count, count, 0),
+        _ => (lmul, lmul, lmul),
+    };
+    let mut regs = group_mask(op.vd, vd) | group_mask(op.vs2, vs2) | op.masked as u32;
+    if let Operand::Vector(reg) = op.operand {
+        regs |= group_mask(reg, vs1);
+    }
+    regs
+}
+
+/// Registers in the group of 16-bit `vrgatherei16` indices, `None` if its EMUL is out of range
+fn index_regs(vtype: Vtype) -> Option<u8> {
+    let emul_log2 = vtype.lmul_log2 + 4 - vtype.sew.ilog2() as i32;
+    (-3..=3).contains(&emul_log2).then(|| 1 << emul_log2.max(0))
+}
+
+/// Check the register operands of an operation against the RVV constraints
+fn is_legal(op: &VectorPermuteOp, vtype: Vtype, vstart: u32) -> bool {
+    use Permute::*;
+    let lmul = vtype.group_regs() as u8;
+    let vs1 = match op.operand {
+        Operand::Vector(vs1) => Some(vs1),
+        _ => None,
+    };
+    let overlaps = |a: u8, a_regs: u8, b: u8, b_regs: u8| a < b + b_regs && b < a + a_regs;
+    // Aligned groups only overlap the mask in v0 when they start at v0
+    let masked_v0 = op.masked && op.vd == 0;
+    match op.op {
+        MaskAnd | MaskNand | MaskAndNot | MaskXor | MaskOr | MaskNor | MaskOrNot | MaskXnor => !op.masked,
+        SetBeforeFirst | SetIncludingFirst | SetOnlyFirst => vstart == 0 && op.vd != op.vs2 && !masked_v0,
+        Iota => {
+            vstart == 0 && op.vd.is_multiple_of(lmul) && !overlaps(op.vd, lmul, op.vs2, 1) && !masked_v0
+        }
+        Index => op.vd.is_multiple_of(lmul) && !masked_v0,
+        Compress => {
+            let vs1 = vs1.unwrap_or(0);
+            !op.masked
+                && vstart == 0
+                && op.vd.is_multiple_of(lmul)
+                && op.vs2.is_multiple_of(lmul)
+                && !overlaps(op.vd, lmul, op.vs2, lmul)
+                && !overlaps(op.vd, lmul, vs1, 1)
+        }
+        MoveToElement => !op.masked,
+        WholeMove(count) => !op.masked && op.vd.is_multiple_of(count) && op.vs2.is_multiple_of(count),
+        _ => {
+            let Some(vs1_regs) = (if op.op == GatherEi16 { index_regs(vtype) } else { Some(lmul) }) else {
+                return false;
+            };
+            let vs2_regs = if op.op == Move { 1 } else { lmul };
+            let aligned = op.vd.is_multiple_of(lmul)
+                && op.vs2.is_multiple_of(vs2_regs)
+                && vs1.is_none_or(|vs1| vs1.is_multiple_of(vs1_regs));
+            // Gathers and upward slides read sources after writing lower destination elements
+            let distinct = !matches!(op.op, Gather | GatherEi16 | SlideUp | Slide1Up)
+                || (!overlaps(op.vd, lmul, op.vs2, lmul)
+                    && vs1.is_none_or(|vs1| !overlaps(op.vd, lmul, vs1, vs1_regs)));
+            aligned && distinct && !masked_v0
+        }
+    }
+}
+
+/// `x[rs1]` or the immediate of an operation, zero for vector operands
+fn scalar_operand(op: &VectorPermuteOp, rs1: u32) -> u32 {
+    match op.operand {
+        Operand::Scalar => rs1,
+        Operand::Immediate(imm) => imm as u32,
+        Operand::Vector(_) => 0,
+    }
+}
+
+/// Execute an operation on the body elements from `vstart` to `vl`.
+/// Inactive and tail elements are left undisturbed.
+fn execute(op: &VectorPermuteOp, rs1: u32, vtype: Vtype, config: &VectorConfig, vregs: &mut VectorRegisterFile) {
+    use Permute::*;
+    let (sew, vstart, vl) = (vtype.sew, config.vstart, config.vl);
+    let vlmax = vtype.vlmax(config.vlen);
+    let scalar = scalar_operand(op, rs1);
+    let vs1 = match op.operand {
+        Operand::Vector(vs1) => vs1,
+        _ => 0,
+    };
+    let regs = &*vregs;
+    let source = |i: u32| regs.read(op.vs2, i, sew);
+    let operand = |i: u32| if let Operand::Vector(vs1) = op.operand { regs.read(vs1, i, sew) } else { scalar };
+    let active: Vec<u32> = (0..vl).filter(|i| !op.masked || regs.mask_bit(0, *i)).collect();
+    let body = active.iter().copied().filter(|i| *i >= vstart);
+
+    // Results are collected first, sources may overlap the destination
+    let results: Vec<(u32, u32)> = match op.op {
+        MaskAnd | MaskNand | MaskAndNot | MaskXor | MaskOr | MaskNor | MaskOrNot | MaskXnor => body
+            .map(|i| (i, mask_logic(op.op, regs.mask_bit(op.vs2, i), regs.mask_bit(vs1, i)) as u32))
+            .collect(),
+        SetBeforeFirst | SetIncludingFirst | SetOnlyFirst => {
+            let first = active.iter().copied().find(|i| regs.mask_bit(op.vs2, *i)).unwrap_or(u32::MAX);
+            let set = |i: u32| match op.op {
+                SetBeforeFirst => i < first,
+                SetIncludingFirst => i <= first,
+                _ => i == first,
+            };
+            active.iter().map(|&i| (i, set(i) as u32)).collect()
+        }
+        Iota => {
+            let mut count = 0;
+            active
+                .iter()
+                .map(|&i| {
+                    let value = count;
+                    count += regs.mask_bit(op.vs2, i) as u32;
+                    (i, value)
+                })
+                .collect()
+        }
+        Index => body.map(|i| (i, i)).collect(),
+        Compress => (0..vl)
+            .filter(|i| regs.mask_bit(vs1, *i))
+            .enumerate()
+            .map(|(packed, i)| (packed as u32, source(i)))
+            .collect(),
+        Gather | GatherEi16 => body
+            .map(|i| {
+                let index = match op.operand {
+                    Operand::Vector(vs1) => regs.read(vs1, i, if op.op == GatherEi16 { 16 } else { sew }),
+                    _ => scalar,
+                };
+                (i, if index < vlmax { source(index) } else { 0 })
+            })
+            .collect(),
+        SlideUp => body.filter(|i| *i >= scalar).map(|i| (i, source(i - scalar))).collect(),
+        SlideDown => body
+            .map(|i| match i.checked_add(scalar) {
+                Some(from) if from < vlmax => (i, source(from)),
+                _ => (i, 0),
+            })
+            .collect(),
+        Slide1Up => body.map(|i| (i, if i == 0 { scalar } else { source(i - 1) })).collect(),
+        Slide1Down => body.map(|i| (i, if i + 1 == vl { scalar } else { source(i + 1) })).collect(),
+        Merge => (vstart..vl).map(|i| (i, if regs.mask_bit(0, i) { operand(i) } else { source(i) })).collect(),
+        Move => (vstart..vl).map(|i| (i, operand(i))).collect(),
+        MoveToElement if vstart < vl => vec![(0, scalar)],
+        MoveToElement => Vec::new(),
+        // Whole registers move regardless of vl, as elements of SEW
+        WholeMove(count) => {
+            let elements = count as u32 * config.vlen / sew;
+            (vstart..elements).map(|i| (i, source(i))).collect()
+        }
+    };
+
+    let writes_mask = matches!(
+        op.op,
+        MaskAnd | MaskNand | MaskAndNot | MaskXor | MaskOr | MaskNor | MaskOrNot | MaskXnor
+            | SetBeforeFirst | SetIncludingFirst | SetOnlyFirst
+    );
+    for (i, value) in results {
+        if writes_mask {
+            vregs.set_mask_bit(op.vd, i, value != 0);
+        } else {
+            vregs.write(op.vd, i, sew, value);
+        }
+    }
+}
+
+/// Combine one bit of `vs2` and `vs1` for a mask-register logical operation
+fn mask_logic(op: Permute, a: bool, b: bool) -> bool {
+    match op {
+        Permute::MaskAnd => a & b,
+        Permute::MaskNand => !(a & b),
+        Permute::MaskAndNot => a & !b,
+        Permute::MaskXor => a ^ b,
+        Permute::MaskOr => a | b,
+        Permute::MaskNor => !(a | b),
+        Permute::MaskOrNot => a | !b,
+        _ => !(a ^ b),
+    }
+}
@@ -0,0 +1,69 @@
+/// Number of architectural vector registers
+pub const NUM_VREGS: usize = 32;
+
+/// Vector register file (v0..v31), each register `vlen` bits wide.
+///
+/// Registers are stored back to back so a register group of LMUL registers is one
+/// contiguous run of bytes, and element `i` of a group starting at `base` lives at
+/// byte `(base * vlenb) + i * sew / 8` in little-endian order. Mask registers hold
+/// one bit per element, element `i` at bit `i % 8` of byte `i / 8`.
+pub struct VectorRegisterFile {
+    vlenb: usize,
+    data: Vec<u8>,
+}
+
+impl VectorRegisterFile {
+    /// Create a zeroed register file with registers of `vlen` bits
+    pub fn new(vlen: u32) -> Self {
+        let vlenb = vlen as usize / 8;
+        Self { vlenb, data: vec![0; NUM_VREGS * vlenb] }
+    }
+
+    /// Register length in bytes
+    pub fn vlenb(&self) -> usize {
+        self.vlenb
+    }
+
+    /// Bytes of a single register
+    pub fn reg(&self, index: u8) -> &[u8] {
+        let start = index as usize * self.vlenb;
+        &self.data[start..start + self.vlenb]
+    }
+
+    /// Read element `index` of `sew` bits from the group starting at `base`, zero-extended
+    pub fn read(&self, base: u8, index: u32, sew: u32) -> u32 {
+        let offset = self.element_offset(base, index, sew);
+        self.data[offset..offset + sew as usize / 8].iter().rev().fold(0, |acc, b| (acc << 8) | *b as u32)
+    }
+
+    /// Write element `index` of `sew` bits to the group starting at `base`
+    pub fn write(&mut self, base: u8, index: u32, sew: u32, value: u32) {
+        let offset = self.element_offset(base, index, sew);
+        for (i, byte) in self.data[offset..offset + sew as usize / 8].iter_mut().enumerate() {
+            *byte = (value >> (8 * i)) as u8;
+        }
+    }
+
+    /// Read mask bit `index` of register `reg`
+    pub fn mask_bit(&self, reg: u8, index: u32) -> bool {
+        self.reg(reg)[index as usize / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// Write mask bit `index` of register `reg`
+    pub fn set_mask_bit(&mut self, reg: u8, index: u32, value: bool) {
+        let byte = &mut self.data[reg as usize * self.vlenb + index as usize / 8];
+        let bit = 1 << (index % 8);
+        *byte = if value { *byte | bit } else { *byte & !bit };
+    }
+
+    /// Byte offset of an element, groups run past the end of `base` into the following registers
+    fn element_offset(&self, base: u8, index: u32, sew: u32) -> usize {
+        base as usize * self.vlenb + index as usize * sew as usize / 8
+    }
+}
+
+/// Bit mask of the `count` registers starting at `base`
+pub fn group_mask(base: u8, count: u8) -> u32 {
+    let bits = if count >= 32 { u32::MAX } else { (1u32 << count) - 1 };
+    bits.checked_shl(base as u32).unwrap_or(0)
+}